@@ -1,6 +1,7 @@
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlCanvasElement, CanvasRenderingContext2d, WebGlRenderingContext};
+use webgl_common::Surface;
 extern crate js_sys;
 
 pub fn get_canvas(element_id: &str) -> HtmlCanvasElement {
@@ -14,32 +15,53 @@ pub fn get_canvas(element_id: &str) -> HtmlCanvasElement {
 
 #[wasm_bindgen]
 pub fn create_canvas1() -> Result<(), JsValue> {
-    let context = get_canvas("my_canvas3")
+    let canvas = get_canvas("my_canvas3");
+    let context = canvas
         .get_context("2d")
         .unwrap()
         .unwrap()
         .dyn_into::<CanvasRenderingContext2d>()
         .unwrap();
+    let surface = Surface::new(canvas);
 
-    context.set_font("20pt Calibri");
-    context.set_fill_style(&JsValue::from_str("green"));
-    context.fill_text("Welcome to Tutorialspoint", 70.0, 70.0)
-        .unwrap();
+    let draw = move |surface: &Surface| {
+        // Keep drawing in CSS pixels on a HiDPI backing store
+        let ratio = surface.pixel_ratio();
+        context.set_transform(ratio, 0.0, 0.0, ratio, 0.0, 0.0).unwrap();
+
+        context.set_font("20pt Calibri");
+        context.set_fill_style(&JsValue::from_str("green"));
+        context.fill_text("Welcome to Tutorialspoint", 70.0, 70.0)
+            .unwrap();
+    };
+    draw(&surface);
+
+    // Resizing the backing store clears it, so draw again
+    surface.observe(draw)?;
 
     Ok(())
 }
 
 #[wasm_bindgen]
 pub fn create_canvas2() -> Result<(), JsValue> {
-    let context = get_canvas("my_canvas4")
+    let canvas = get_canvas("my_canvas4");
+    let context = canvas
         .get_context("webgl")
         .unwrap()
         .unwrap()
         .dyn_into::<WebGlRenderingContext>()
         .unwrap();
+    let surface = Surface::new(canvas);
+
+    let draw = move |surface: &Surface| {
+        surface.set_viewport(&context);
+        context.clear_color(0.9, 0.9, 0.8, 1.0);
+        context.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+    };
+    draw(&surface);
 
-    context.clear_color(0.9, 0.9, 0.8, 1.0);
-    context.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+    // Resizing the backing store clears it, so draw again
+    surface.observe(draw)?;
 
     Ok(())
 }
\ No newline at end of file
@@ -1,9 +1,14 @@
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::f32::consts::PI;
 use wasm_bindgen::prelude::*;
 
 use wasm_bindgen::JsCast;
-use web_sys::{HtmlCanvasElement, WebGlRenderingContext};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, BlobPropertyBag, HtmlCanvasElement, ImageBitmap, WebGlRenderingContext};
+use webgl_common::gltf_scene::{self, GltfImage};
+use webgl_common::{builtin, mesh_tools, obj, primitives, Camera, Geometry, HotProgram, Mat4, Mesh, Projection, ProgramError, Quat, RenderMode, ShaderProgram, Surface, Texture, Variant, Vec3, Vec4, Vertex, WireframeMesh};
+use webgl_common::wireframe;
 extern crate js_sys;
 
 fn window() -> web_sys::Window {
@@ -25,132 +30,432 @@ fn get_canvas(element_id: &str) -> HtmlCanvasElement {
     canvas
 }
 
-#[wasm_bindgen]
-pub fn cube_rotate() -> Result<(), JsValue> {
-    /*============= Creating a canvas =================*/
-    let canvas = get_canvas("wasm_canvas");
-    let gl = canvas.get_context("webgl")
-        .unwrap()
-        .unwrap()
-        .dyn_into::<WebGlRenderingContext>()
-        .unwrap();
+#[derive(Clone, Copy, Vertex)]
+#[repr(C)]
+struct CubeVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
 
-    /*============ Defining and storing the geometry =========*/
-    let vertices = [
-        -1.0,-1.0,-1.0, 1.0,-1.0,-1.0, 1.0, 1.0,-1.0, -1.0, 1.0,-1.0,
-        -1.0,-1.0, 1.0, 1.0,-1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0,
-        -1.0,-1.0,-1.0, -1.0, 1.0,-1.0, -1.0, 1.0, 1.0, -1.0,-1.0, 1.0,
-        1.0,-1.0,-1.0, 1.0, 1.0,-1.0, 1.0, 1.0, 1.0, 1.0,-1.0, 1.0,
-        -1.0,-1.0,-1.0, -1.0,-1.0, 1.0, 1.0,-1.0, 1.0, 1.0,-1.0,-1.0,
-        -1.0, 1.0,-1.0, -1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,-1.0, 
-    ];
+#[derive(Clone, Copy, Vertex)]
+#[repr(C)]
+struct ShapeVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    uv: [f32; 2],
+    color: [f32; 3],
+}
 
-    let colors = [
-        5.0,3.0,7.0, 5.0,3.0,7.0, 5.0,3.0,7.0, 5.0,3.0,7.0,
-        1.0,1.0,3.0, 1.0,1.0,3.0, 1.0,1.0,3.0, 1.0,1.0,3.0,
-        0.0,0.0,1.0, 0.0,0.0,1.0, 0.0,0.0,1.0, 0.0,0.0,1.0,
-        1.0,0.0,0.0, 1.0,0.0,0.0, 1.0,0.0,0.0, 1.0,0.0,0.0,
-        1.0,1.0,0.0, 1.0,1.0,0.0, 1.0,1.0,0.0, 1.0,1.0,0.0,
-        0.0,1.0,0.0, 0.0,1.0,0.0, 0.0,1.0,0.0, 0.0,1.0,0.0
-    ];
+// Edges of RenderMode::Overlay, and their width in pixels
+const WIRE_COLOR: Vec4 = Vec4::new(0.05, 0.05, 0.05, 0.9);
+const WIRE_WIDTH: f32 = 1.5;
 
-    let indices = [
-        0,1,2, 0,2,3, 4,5,6, 4,6,7,
-        8,9,10, 8,10,11, 12,13,14, 12,14,15,
-        16,17,18, 16,18,19, 20,21,22, 20,22,23 
-    ];
+// Color of generated shapes and of OBJ faces without a material
+const SHAPE_COLOR: [f32; 3] = [0.85, 0.85, 0.8];
 
-    // Create and store data into vertex buffer
-    let vertex_buffer = gl.create_buffer().unwrap();
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
-    let vertices_array = unsafe {
-        js_sys::Float32Array::view(&vertices)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ARRAY_BUFFER, &vertices_array, WebGlRenderingContext::STATIC_DRAW);
+// Filled in once the browser has decoded the image; until then the part is drawn white
+type TextureSlot = Rc<RefCell<Option<Texture>>>;
 
-    // Create and store data into color buffer
-    let color_buffer = gl.create_buffer().unwrap();
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&color_buffer));
-    let colors_array = unsafe {
-        js_sys::Float32Array::view(&colors)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ARRAY_BUFFER, &colors_array, WebGlRenderingContext::STATIC_DRAW);
+struct ShapePart {
+    mesh: WireframeMesh,
+    texture: Option<TextureSlot>,
+}
 
-    // Create and store data into index buffer
-    let index_buffer = gl.create_buffer().unwrap();
-    gl.bind_buffer(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, Some(&index_buffer));
-    let indices_array = unsafe {
-        js_sys::Uint16Array::view(&indices)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, &indices_array, WebGlRenderingContext::STATIC_DRAW);
+// A generated shape or loaded model added from JS, drawn with the lit program
+struct Shape {
+    parts: Vec<ShapePart>,
+    position: Vec3,
+}
 
-    /*=================== Shaders =========================*/
+// Big meshes are fine now (u32 or split indices), this only keeps a typo from allocating millions of vertices
+const MAX_SEGMENTS: u32 = 1024;
 
-    let vert_code = "
-        attribute vec3 position;
-        uniform mat4 Pmatrix;
-        uniform mat4 Vmatrix;
-        uniform mat4 Mmatrix;
-
-        attribute vec3 color;
-        varying vec3 vColor;
-   
-        void main(void) {
-            gl_Position = Pmatrix*Vmatrix*Mmatrix*vec4(position, 1.);
-           vColor = color;
+// Handle returned to JS so a page can move the camera of the running scene and add shapes to it
+#[wasm_bindgen]
+pub struct Scene {
+    gl: WebGlRenderingContext,
+    camera: Rc<RefCell<Camera>>,
+    shapes: Rc<RefCell<Vec<Shape>>>,
+    render_mode: Rc<Cell<RenderMode>>,
+    #[cfg(feature = "hot-reload")]
+    program: HotProgram,
+}
+
+#[wasm_bindgen]
+impl Scene {
+    pub fn look_at(&self, eye_x: f32, eye_y: f32, eye_z: f32, target_x: f32, target_y: f32, target_z: f32) {
+        self.camera.borrow_mut().look_at(Vec3::new(eye_x, eye_y, eye_z), Vec3::new(target_x, target_y, target_z), Vec3::Y);
+    }
+
+    pub fn set_perspective(&self, fov: f32, near: f32, far: f32) {
+        self.camera.borrow_mut().set_perspective(fov, near, far);
+    }
+
+    // yaw/pitch in radians around the current target
+    pub fn set_orbit(&self, yaw: f32, pitch: f32, distance: f32) {
+        self.camera.borrow_mut().set_orbit(yaw, pitch, distance);
+    }
+
+    pub fn orbit_by(&self, d_yaw: f32, d_pitch: f32) {
+        self.camera.borrow_mut().orbit_by(d_yaw, d_pitch);
+    }
+
+    pub fn zoom_by(&self, factor: f32) {
+        self.camera.borrow_mut().zoom_by(factor);
+    }
+
+    // Blends into the orthographic projection over `duration_ms` (0 = switch right away)
+    pub fn use_orthographic(&self, duration_ms: f32) {
+        self.camera.borrow_mut().set_projection(Projection::Orthographic, duration_ms);
+    }
+
+    pub fn use_perspective(&self, duration_ms: f32) {
+        self.camera.borrow_mut().set_projection(Projection::Perspective, duration_ms);
+    }
+
+    // Height of the orthographic view; 0 or less matches the perspective framing at the target
+    pub fn set_ortho_size(&self, size: f32) {
+        self.camera.borrow_mut().ortho_size = if size > 0.0 { Some(size) } else { None };
+    }
+
+    // The add_* functions return an id for set_shape_position. New shapes are placed on a
+    // ring around the cube and turn with it.
+    pub fn add_sphere(&self, radius: f32, segments: u32) -> usize {
+        let segments = segments.min(MAX_SEGMENTS);
+        self.add_shape(primitives::sphere(radius, segments, segments / 2))
+    }
+
+    pub fn add_cylinder(&self, radius: f32, height: f32, segments: u32) -> usize {
+        self.add_shape(primitives::cylinder(radius, height, segments.min(MAX_SEGMENTS)))
+    }
+
+    pub fn add_cone(&self, radius: f32, height: f32, segments: u32) -> usize {
+        self.add_shape(primitives::cone(radius, height, segments.min(MAX_SEGMENTS)))
+    }
+
+    pub fn add_torus(&self, radius: f32, tube_radius: f32, segments: u32, tube_segments: u32) -> usize {
+        self.add_shape(primitives::torus(radius, tube_radius, segments.min(MAX_SEGMENTS), tube_segments.min(MAX_SEGMENTS)))
+    }
+
+    pub fn add_plane(&self, width: f32, depth: f32, segments: u32) -> usize {
+        let segments = segments.min(MAX_SEGMENTS);
+        self.add_shape(primitives::plane(width, depth, segments, segments))
+    }
+
+    // `height` is the straight part between the two hemispheres
+    pub fn add_capsule(&self, radius: f32, height: f32, segments: u32) -> usize {
+        let segments = segments.min(MAX_SEGMENTS);
+        self.add_shape(primitives::capsule(radius, height, segments, segments / 4))
+    }
+
+    pub fn add_cube(&self, size: f32, segments: u32) -> usize {
+        self.add_shape(primitives::cube(size, segments.min(MAX_SEGMENTS)))
+    }
+
+    // Bytes of an .obj file and optionally of its .mtl (see the mtllib line), as fetched by the page.
+    // The model is centered and scaled to fit a 2 x 2 x 2 box.
+    pub fn add_obj(&self, obj_bytes: &[u8], mtl_bytes: Option<Vec<u8>>) -> Result<usize, JsValue> {
+        let mut model = obj::parse_obj(obj_bytes)?;
+        let materials = match mtl_bytes {
+            Some(bytes) => obj::parse_mtl(&bytes)?,
+            None => Default::default(),
+        };
+
+        if let Some((min, max)) = model.bounds() {
+            let size = (max - min).x.max((max - min).y).max((max - min).z);
+            let scale = if size > 0.0 { 2.0 / size } else { 1.0 };
+            let fit = Mat4::scale(Vec3::ONE * scale) * Mat4::translation(-((min + max) * 0.5));
+            for mesh in &mut model.meshes {
+                mesh.geometry.transform(&fit);
+            }
+        }
+        for mesh in &mut model.meshes {
+            mesh_tools::optimize_vertex_cache(&mut mesh.geometry);
         }
-     ";
 
-    let frag_code = "
-        precision mediump float;
-        varying vec3 vColor;
-        void main(void) {
-            gl_FragColor = vec4(vColor, 1.);
+        let parts = model
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let color = mesh.material.as_ref().and_then(|name| materials.get(name)).map_or(SHAPE_COLOR, |m| m.diffuse);
+                ShapePart {
+                    mesh: shape_mesh(&self.gl, &mesh.geometry, color),
+                    texture: None,
+                }
+            })
+            .collect();
+        Ok(self.push_shape(parts))
+    }
+
+    // Bytes of a .glb, or of a .gltf with its buffers and images embedded as data: URIs.
+    // The scene is flattened to world space, then centered and scaled like add_obj.
+    // Base color textures are decoded by the browser and show up a few frames later.
+    pub fn add_gltf(&self, bytes: &[u8]) -> Result<usize, JsValue> {
+        let scene = gltf_scene::parse_gltf(bytes)?;
+        let mut primitives = scene.world_primitives();
+
+        let bounds = primitives.iter().filter_map(|p| p.geometry.bounds()).reduce(|(min_a, max_a), (min_b, max_b)| {
+            (
+                Vec3::new(min_a.x.min(min_b.x), min_a.y.min(min_b.y), min_a.z.min(min_b.z)),
+                Vec3::new(max_a.x.max(max_b.x), max_a.y.max(max_b.y), max_a.z.max(max_b.z)),
+            )
+        });
+        if let Some((min, max)) = bounds {
+            let size = (max - min).x.max((max - min).y).max((max - min).z);
+            let scale = if size > 0.0 { 2.0 / size } else { 1.0 };
+            let fit = Mat4::scale(Vec3::ONE * scale) * Mat4::translation(-((min + max) * 0.5));
+            for primitive in &mut primitives {
+                primitive.geometry.transform(&fit);
+            }
+        }
+        for primitive in &mut primitives {
+            mesh_tools::optimize_vertex_cache(&mut primitive.geometry);
         }
-    ";
 
-    let vert_shader = gl.create_shader(WebGlRenderingContext::VERTEX_SHADER).unwrap();
-    gl.shader_source(&vert_shader, &vert_code);
-    gl.compile_shader(&vert_shader);
+        // One slot per image, shared by every material that uses it
+        let slots: Vec<TextureSlot> = scene.images.iter().map(|_| Rc::new(RefCell::new(None))).collect();
+        let mut decoding = vec![false; slots.len()];
+
+        let parts = primitives
+            .iter()
+            .map(|primitive| {
+                let material = scene.material(primitive.material);
+                let [r, g, b, _] = material.base_color;
+                let texture = material.base_color_image.map(|image| {
+                    if !decoding[image] {
+                        decoding[image] = true;
+                        decode_texture(&self.gl, &scene.images[image], slots[image].clone());
+                    }
+                    slots[image].clone()
+                });
+                ShapePart {
+                    mesh: shape_mesh(&self.gl, &primitive.geometry, [r, g, b]),
+                    texture,
+                }
+            })
+            .collect();
+        Ok(self.push_shape(parts))
+    }
+
+    // "solid", "wireframe" (edges only, as LINES) or "overlay" (filled with the edges on top),
+    // for the cube and every shape from the next frame on
+    pub fn set_render_mode(&self, mode: &str) -> Result<(), JsValue> {
+        let mode = RenderMode::from_name(mode)
+            .ok_or_else(|| js_sys::Error::new(&format!("unknown render mode '{}', expected solid, wireframe or overlay", mode)))?;
+        self.render_mode.set(mode);
+        Ok(())
+    }
+
+    pub fn set_shape_position(&self, id: usize, x: f32, y: f32, z: f32) {
+        if let Some(shape) = self.shapes.borrow_mut().get_mut(id) {
+            shape.position = Vec3::new(x, y, z);
+        }
+    }
+
+    pub fn clear_shapes(&self) {
+        for shape in self.shapes.borrow_mut().drain(..) {
+            for part in shape.parts {
+                part.mesh.delete();
+                // Shared slots go with the last part using them; a decode still running keeps its own
+                if let Some(texture) = part.texture.and_then(|slot| Rc::try_unwrap(slot).ok()).and_then(RefCell::into_inner) {
+                    texture.delete();
+                }
+            }
+        }
+    }
+
+    fn add_shape(&self, geometry: Geometry) -> usize {
+        let mesh = shape_mesh(&self.gl, &geometry, SHAPE_COLOR);
+        self.push_shape(vec![ShapePart { mesh, texture: None }])
+    }
+
+    fn push_shape(&self, parts: Vec<ShapePart>) -> usize {
+        let mut shapes = self.shapes.borrow_mut();
+        let angle = shapes.len() as f32 * PI / 3.0;
+        shapes.push(Shape {
+            parts,
+            position: Vec3::new(3.0 * angle.cos(), 0.0, 3.0 * angle.sin()),
+        });
+        shapes.len() - 1
+    }
+}
 
-    let frag_shader = gl.create_shader(WebGlRenderingContext::FRAGMENT_SHADER).unwrap();
-    gl.shader_source(&frag_shader, &frag_code);
-    gl.compile_shader(&frag_shader);
+fn shape_mesh(gl: &WebGlRenderingContext, geometry: &Geometry, color: [f32; 3]) -> WireframeMesh {
+    let vertices: Vec<ShapeVertex> = geometry
+        .positions
+        .iter()
+        .zip(&geometry.normals)
+        .zip(&geometry.uvs)
+        .map(|((position, normal), uv)| ShapeVertex {
+            position: position.to_array(),
+            normal: normal.to_array(),
+            uv: *uv,
+            color,
+        })
+        .collect();
+    WireframeMesh::new(gl, &vertices, &geometry.indices)
+}
 
-    let shader_program = gl.create_program().unwrap();
-    gl.attach_shader(&shader_program, &vert_shader);
-    gl.attach_shader(&shader_program, &frag_shader);
-    gl.link_program(&shader_program);
+// Has the browser decode `image` and puts the texture into `slot` when it's done
+fn decode_texture(gl: &WebGlRenderingContext, image: &GltfImage, slot: TextureSlot) {
+    let gl = gl.clone();
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(&image.bytes[..]));
+    let mut options = BlobPropertyBag::new();
+    if let Some(mime_type) = &image.mime_type {
+        options.type_(mime_type);
+    }
+    wasm_bindgen_futures::spawn_local(async move {
+        let decoded = async {
+            let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+            let bitmap: ImageBitmap = JsFuture::from(window().create_image_bitmap_with_blob(&blob)?).await?.dyn_into()?;
+            Texture::from_image_bitmap(&gl, &bitmap)
+        };
+        match decoded.await {
+            Ok(texture) => *slot.borrow_mut() = Some(texture),
+            Err(err) => web_sys::console::error_1(&err),
+        }
+    });
+}
 
-    /* ====== Associating attributes to vertex shader =====*/
-    let p_matrix = gl.get_uniform_location(&shader_program, "Pmatrix").unwrap();
-    let v_matrix = gl.get_uniform_location(&shader_program, "Vmatrix").unwrap();
-    let m_matrix = gl.get_uniform_location(&shader_program, "Mmatrix").unwrap();
+// Dev mode, only in builds with `--features hot-reload`
+#[cfg(feature = "hot-reload")]
+#[wasm_bindgen]
+impl Scene {
+    // Swaps in new shader source (run through the same preprocessor) for every variant in use on the next frame.
+    // If it doesn't build, the old program keeps drawing and the error is thrown.
+    pub fn reload_shaders(&self, vert_code: &str, frag_code: &str) -> Result<(), JsValue> {
+        self.program.reload(vert_code, frag_code)?;
+        Ok(())
+    }
+
+    // Same, with the sources fetched first, e.g. straight from webgl_common/src/glsl on a dev server.
+    // Errors are also logged to the console since nobody may be awaiting the promise.
+    pub fn reload_shaders_from(&self, vert_url: String, frag_url: String) -> js_sys::Promise {
+        let program = self.program.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let reloaded = async {
+                let vert_code = fetch_text(&vert_url).await?;
+                let frag_code = fetch_text(&frag_url).await?;
+                program.reload(&vert_code, &frag_code)?;
+                Ok::<_, JsValue>(())
+            };
+            match reloaded.await {
+                Ok(()) => Ok(JsValue::UNDEFINED),
+                Err(err) => {
+                    web_sys::console::error_1(&err);
+                    Err(err)
+                }
+            }
+        })
+    }
+}
 
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
-    let position = gl.get_attrib_location(&shader_program, "position");
-    gl.vertex_attrib_pointer_with_f64(position as u32, 3, WebGlRenderingContext::FLOAT, false,0,0.0) ;
+#[cfg(feature = "hot-reload")]
+async fn fetch_text(url: &str) -> Result<String, JsValue> {
+    let response: web_sys::Response = JsFuture::from(window().fetch_with_str(url)).await?.dyn_into()?;
+    if !response.ok() {
+        return Err(js_sys::Error::new(&format!("{}: HTTP {}", url, response.status())).into());
+    }
+    let text = JsFuture::from(response.text()?).await?;
+    Ok(text.as_string().unwrap_or_default())
+}
 
-    // Position
-    gl.enable_vertex_attrib_array(position as u32);
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&color_buffer));
-    let color = gl.get_attrib_location(&shader_program, "color");
-    gl.vertex_attrib_pointer_with_f64(color as u32, 3, WebGlRenderingContext::FLOAT, false,0,0.0) ;
+// Uniforms the program doesn't use are skipped, a reloaded shader may have let the compiler drop them
+fn draw_mesh(program: &ShaderProgram, mesh: &Mesh, texture: Option<&Texture>, proj: &Mat4, view: &Mat4, model: &Mat4) -> Result<(), ProgramError> {
+    let has = |name: &str| program.uniforms().contains_key(name);
+    program.use_program();
+    for (name, matrix) in [("Pmatrix", proj), ("Vmatrix", view), ("Mmatrix", model)] {
+        if has(name) {
+            program.set_mat4(name, matrix)?;
+        }
+    }
+    if let Some(texture) = texture.filter(|_| has("u_texture")) {
+        texture.bind(0);
+        program.set_i32("u_texture", 0)?;
+    }
+    if has("u_wireColor") {
+        program.set_vec4("u_wireColor", WIRE_COLOR)?;
+    }
+    if has("u_wireWidth") {
+        program.set_f32("u_wireWidth", WIRE_WIDTH)?;
+    }
+    mesh.bind(program)?;
+    mesh.draw();
+    mesh.unbind(program);
+    Ok(())
+}
 
-    // Color
-    gl.enable_vertex_attrib_array(color as u32);
-    gl.use_program(Some(&shader_program));
+#[wasm_bindgen]
+pub fn cube_rotate() -> Result<Scene, JsValue> {
+    /*============= Creating a canvas =================*/
+    let canvas = get_canvas("wasm_canvas");
+    let gl = canvas.get_context("webgl")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<WebGlRenderingContext>()
+        .unwrap();
+    let surface = Surface::new(canvas);
 
-    /*==================== MATRIX =====================*/
-    let proj_matrix = get_projection(40.0, (canvas.width()/canvas.height()) as f32, 1.0, 100.0);
+    /*============ Defining and storing the geometry =========*/
+    // One color per face, in the order primitives::cube puts them: -Z, +Z, -X, +X, -Y, +Y
+    let face_colors = [
+        [5.0,3.0,7.0], [1.0,1.0,3.0], [0.0,0.0,1.0],
+        [1.0,0.0,0.0], [1.0,1.0,0.0], [0.0,1.0,0.0]
+    ];
+    let geometry = primitives::cube(2.0, 1);
+    let face_size = geometry.vertex_count() / face_colors.len();
+
+    // Position and color of each vertex go into one interleaved buffer
+    let cube: Vec<CubeVertex> = geometry.positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| CubeVertex {
+            position: p.to_array(),
+            color: face_colors[i / face_size],
+        })
+        .collect();
+    let mesh = WireframeMesh::new(&gl, &cube, &geometry.indices);
 
-    let mut mov_matrix = [1.0,0.0,0.0,0.0, 0.0,1.0,0.0,0.0, 0.0,0.0,1.0,0.0, 0.0,0.0,0.0,1.0];
-    let mut view_matrix = [1.0,0.0,0.0,0.0, 0.0,1.0,0.0,0.0, 0.0,0.0,1.0,0.0, 0.0,0.0,0.0,1.0];
+    /*=================== Shaders =========================*/
 
-    // translating z
-    view_matrix[14] = view_matrix[14]-6.0;//zoom
+    // The loop reads its programs from here every frame so a reload can swap them all in
+    let program = HotProgram::new(&gl, builtin::basic_programs(&gl));
+    let variant = Variant::VERTEX_COLOR | Variant::USE_MVP;
+    // Shapes added from JS are lit from their normals and textured, white when they have no texture
+    let shape_variant = variant | Variant::NORMAL_SHADING | Variant::TEXTURE;
+    let white = Texture::white(&gl)?;
+    // Same programs with the edges drawn over the fill, for RenderMode::Overlay
+    wireframe::enable_derivatives(&gl);
+    // Built now so a broken variant fails here rather than in the loop, and with the layouts each
+    // is drawn with so a reload can't ask them for an attribute they don't have
+    for (mode, overlay) in [(RenderMode::Solid, Variant::NONE), (RenderMode::Wireframe, Variant::NONE), (RenderMode::Overlay, Variant::WIREFRAME)] {
+        program.get(variant | overlay)?;
+        program.get(shape_variant | overlay)?;
+        program.draws(variant | overlay, mesh.mesh(mode).layout());
+    }
+    program.draws(shape_variant, &ShapeVertex::layout());
+    program.draws(shape_variant | Variant::WIREFRAME, &wireframe::overlay_layout(&ShapeVertex::layout()));
+
+    /*==================== MATRIX =====================*/
+    let camera = Rc::new(RefCell::new(Camera::new(40.0, surface.aspect(), 1.0, 100.0)));
+
+    // Orientation is kept as a quaternion and turned into the model matrix each frame,
+    // so the cube stays rigid however long the page is left open
+    let mut orientation = Quat::IDENTITY;
 
     /*================= Drawing ===========================*/
+    let shapes = Rc::new(RefCell::new(Vec::new()));
+    let render_mode = Rc::new(Cell::new(RenderMode::Solid));
+    let scene = Scene {
+        gl: gl.clone(),
+        camera: camera.clone(),
+        shapes: shapes.clone(),
+        render_mode: render_mode.clone(),
+        #[cfg(feature = "hot-reload")]
+        program: program.clone(),
+    };
+    // Drawing errors are logged when they change, not every frame
+    let mut last_error: Option<ProgramError> = None;
     let time_old = Rc::new(RefCell::new(0.0));
     let f = Rc::new(RefCell::new(None));
     let g = f.clone();
@@ -163,87 +468,63 @@ pub fn cube_rotate() -> Result<(), JsValue> {
         }
         */
         let dt = time - *time_old.borrow();
-        rotate_z(&mut mov_matrix, dt as f32 * 0.005);
-        rotate_y(&mut mov_matrix, dt as f32*0.002);
-        rotate_x(&mut mov_matrix, dt as f32*0.003);
+        orientation.rotate_z(dt as f32 * 0.005);
+        orientation.rotate_y(dt as f32*0.002);
+        orientation.rotate_x(dt as f32*0.003);
         *time_old.borrow_mut() = time;
+        camera.borrow_mut().update(dt as f32);
+
+        let mov_matrix = orientation.to_mat4();
+
+        for old in program.take_replaced() {
+            program.release(&old);
+        }
 
         gl.enable(WebGlRenderingContext::DEPTH_TEST);
         gl.depth_func(WebGlRenderingContext::LEQUAL);
         gl.clear_color(0.5, 0.5, 0.5, 0.9);
         gl.clear_depth(1.0);
-        gl.viewport(0, 0, canvas.width().try_into().unwrap(), canvas.height().try_into().unwrap());
+        // Follow CSS size / devicePixelRatio changes
+        if surface.resize() {
+            camera.borrow_mut().aspect = surface.aspect();
+        }
+        surface.set_viewport(&gl);
         gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT);
 
-        gl.uniform_matrix4fv_with_f32_array(Some(&p_matrix), false, &proj_matrix);
-        gl.uniform_matrix4fv_with_f32_array(Some(&v_matrix), false, &view_matrix);
-        gl.uniform_matrix4fv_with_f32_array(Some(&m_matrix), false, &mov_matrix);
-
-        gl.bind_buffer(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, Some(&index_buffer));
-        gl.draw_elements_with_i32(WebGlRenderingContext::TRIANGLES, indices.len() as i32, WebGlRenderingContext::UNSIGNED_SHORT, 0);
+        let proj_matrix = camera.borrow().projection_matrix();
+        let view_matrix = camera.borrow().view_matrix();
+        let mode = render_mode.get();
+        let overlay = match mode {
+            RenderMode::Overlay => Variant::WIREFRAME,
+            _ => Variant::NONE,
+        };
+        // The cube and the shapes use different programs, so both rebind every frame
+        let drawn = (|| {
+            let cube_program = program.get(variant | overlay)?;
+            let shape_program = program.get(shape_variant | overlay)?;
+            draw_mesh(&cube_program, mesh.mesh(mode), None, &proj_matrix, &view_matrix, &mov_matrix)?;
+            shapes.borrow().iter().try_for_each(|shape| {
+                let model = Mat4::translation(shape.position) * mov_matrix;
+                shape.parts.iter().try_for_each(|part| {
+                    let texture = part.texture.as_ref().map(|slot| slot.borrow());
+                    let texture = texture.as_ref().and_then(|t| t.as_ref()).unwrap_or(&white);
+                    draw_mesh(&shape_program, part.mesh.mesh(mode), Some(texture), &proj_matrix, &view_matrix, &model)
+                })
+            })
+        })();
+        // Skip the frame and keep going, a later reload may fix it; each new error is logged once
+        match drawn {
+            Err(err) if last_error.as_ref() != Some(&err) => {
+                web_sys::console::error_1(&err.clone().into());
+                last_error = Some(err);
+            }
+            Err(_) => {}
+            Ok(()) => last_error = None,
+        }
 
         request_animation_frame(f.borrow().as_ref().unwrap());
     }) as Box<dyn FnMut(f64)>));
     request_animation_frame(g.borrow().as_ref().unwrap());
 
-    Ok(())
-}
-
-fn get_projection(angle: f32, a: f32, z_min: f32, z_max: f32) -> [f32; 16] {
-    let ang = (angle * 0.5).to_radians().tan();
-
-    [
-        0.5/ang, 0.0, 0.0, 0.0,
-        0.0, 0.5*a/ang, 0.0, 0.0,
-        0.0, 0.0, -(z_max + z_min)/(z_max - z_min), -1.0,
-        0.0, 0.0, (-2.0*z_max*z_min) as f32/(z_max-z_min), 0.0
-    ]
-}
-
-/*==================== Rotation ====================*/
-fn rotate_z(matrix: &mut [f32; 16], angle: f32) {
-    let cos_angle = angle.cos();
-    let sin_angle = angle.sin();
-    let mv0 = matrix[0];
-    let mv4 = matrix[4];
-    let mv8 = matrix[8];
-
-    matrix[0] = cos_angle*matrix[0] - sin_angle*matrix[1];
-    matrix[4] = cos_angle*matrix[4] - sin_angle*matrix[5];
-    matrix[8] = cos_angle*matrix[8] - sin_angle*matrix[9];
-    matrix[1] = cos_angle*matrix[1] + sin_angle*mv0;
-    matrix[5] = cos_angle*matrix[5] + sin_angle*mv4;
-    matrix[9] = cos_angle*matrix[9] + sin_angle*mv8;
+    Ok(scene)
 }
-
-fn rotate_x(matrix: &mut [f32; 16], angle: f32) {
-    let cos_angle = angle.cos();
-    let sin_angle = angle.sin();
-    let mv1 = matrix[1];
-    let mv5 = matrix[5];
-    let mv9 = matrix[9];
-
-    matrix[1] = matrix[1]*cos_angle-matrix[2]*sin_angle;
-    matrix[5] = matrix[5]*cos_angle-matrix[6]*sin_angle;
-    matrix[9] = matrix[9]*cos_angle-matrix[10]*sin_angle;
-
-    matrix[2] = matrix[2]*cos_angle+mv1*sin_angle;
-    matrix[6] = matrix[6]*cos_angle+mv5*sin_angle;
-    matrix[10] = matrix[10]*cos_angle+mv9*sin_angle;
- }
-
- fn rotate_y(matrix: &mut [f32; 16], angle: f32) {
-    let cos_angle = angle.cos();
-    let sin_angle = angle.sin();
-    let mv0 = matrix[0];
-    let mv4 = matrix[4];
-    let mv8 = matrix[8];
-
-    matrix[0] = cos_angle*matrix[0]+sin_angle*matrix[2];
-    matrix[4] = cos_angle*matrix[4]+sin_angle*matrix[6];
-    matrix[8] = cos_angle*matrix[8]+sin_angle*matrix[10];
-
-    matrix[2] = cos_angle*matrix[2]-sin_angle*mv0;
-    matrix[6] = cos_angle*matrix[6]-sin_angle*mv4;
-    matrix[10] = cos_angle*matrix[10]-sin_angle*mv8;
- }
\ No newline at end of file
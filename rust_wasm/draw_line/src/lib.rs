@@ -1,6 +1,9 @@
+use std::cell::RefCell;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlCanvasElement, WebGlRenderingContext};
+use webgl_common::{builtin, curve, Buffer, BufferTarget, Cap, Join, Polyline, PolylineStyle, ShaderProgram, Surface, SvgPath, Usage, Variant, Vec3, Vec4};
 extern crate js_sys;
 
 pub fn get_canvas(element_id: &str) -> HtmlCanvasElement {
@@ -21,6 +24,7 @@ pub fn draw_line(canvas_id: &str, draw_type: &str) -> Result<WebGlRenderingConte
         .unwrap()
         .dyn_into::<WebGlRenderingContext>()
         .unwrap();
+    let surface = Surface::new(canvas);
 
     /*======= Defining and storing the geometry ======*/
     let vertices:[f32; 18] = [
@@ -33,105 +37,72 @@ pub fn draw_line(canvas_id: &str, draw_type: &str) -> Result<WebGlRenderingConte
     ];
 
     // Create an empty buffer object
-    let vertex_buffer = gl.create_buffer()
-        .unwrap();
+    let mut vertex_buffer = Buffer::new(&gl, BufferTarget::Array, Usage::Static);
 
     // Bind appropriate array buffer to it
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
+    vertex_buffer.bind();
 
     // Pass the vertex data to the buffer
-    let vertices_array = unsafe {
-        js_sys::Float32Array::view(&vertices)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ARRAY_BUFFER, &vertices_array, WebGlRenderingContext::STATIC_DRAW);
+    vertex_buffer.upload(&vertices);
 
     // Unbind the buffer
     gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
 
     /*=================== Shaders ====================*/
 
-    // Vertex shader source code
-    let vert_code = "
-        attribute vec3 coordinates;
-        void main(void) {
-            gl_Position = vec4(coordinates, 1.0);
-        }
-    ";
-
-    // Create a vertex shader object
-    let vert_shader = gl.create_shader(WebGlRenderingContext::VERTEX_SHADER)
-        .unwrap();
-
-    // Attach vertex shader source code
-    gl.shader_source(&vert_shader, vert_code);
-
-    // Compile the vertex shader
-    gl.compile_shader(&vert_shader);
-
-    // Fragment shader source code
-    let frag_code = "
-        void main(void) {
-            gl_FragColor = vec4(0.0, 0.0, 0.0, 0.1);
-        }
-    ";
-
-    // Create fragment shader object
-    let frag_shader = gl.create_shader(WebGlRenderingContext::FRAGMENT_SHADER)
-        .unwrap();
-
-    // Attach fragment shader source code
-    gl.shader_source(&frag_shader, frag_code);
-
-    // Compile the fragmentt shader
-    gl.compile_shader(&frag_shader);
-
-    // Create a shader program object to store
-    // the combined shader program
-    let shader_program = gl.create_program()
-        .unwrap();
-
-    // Attach a vertex shader
-    gl.attach_shader(&shader_program, &vert_shader);
-
-    // Attach a fragment shader
-    gl.attach_shader(&shader_program, &frag_shader);
-
-    // Link both the programs
-    gl.link_program(&shader_program);
+    // Compile the shared basic shader for this demo and link it
+    let mut programs = builtin::basic_programs(&gl);
+    let shader_program = programs.get(Variant::NONE)?;
 
     // Use the combined shader program object
-    gl.use_program(Some(&shader_program));
+    shader_program.use_program();
 
     /*======= Associating shaders to buffer objects ======*/
 
     // Bind vertex buffer object
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
+    vertex_buffer.bind();
 
     // Get the attribute location
-    let coord = gl.get_attrib_location(&shader_program, "coordinates");
+    let coord = shader_program.attrib_location("position")?;
 
     // Point an attribute to the currently bound VBO
-    gl.vertex_attrib_pointer_with_f64(coord as u32, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
+    gl.vertex_attrib_pointer_with_f64(coord, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
 
     // Enable the attribute
-    gl.enable_vertex_attrib_array(coord as u32);
+    gl.enable_vertex_attrib_array(coord);
 
     /*============ Drawing the triangle =============*/
+    let gl_draw_type = gl_draw_type(draw_type);
 
-    // Clear the canvas
-    gl.clear_color(0.5, 0.5, 0.5, 0.9);
+    let draw = {
+        let gl = gl.clone();
+        move |surface: &Surface| {
+            // Clear the canvas
+            gl.clear_color(0.5, 0.5, 0.5, 0.9);
 
-    // Enable the depth test
-    gl.enable(WebGlRenderingContext::DEPTH_TEST);
+            // Enable the depth test
+            gl.enable(WebGlRenderingContext::DEPTH_TEST);
 
-    // Clear the color and depth buffer
-    gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT);
+            // Clear the color and depth buffer
+            gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT);
 
-    // Set the view port
-    gl.viewport(0, 0, canvas.width().try_into().unwrap(), canvas.height().try_into().unwrap());
+            // Set the view port
+            surface.set_viewport(&gl);
 
-    // Draw the triangle
-    let gl_draw_type = match draw_type {
+            // Draw the triangle
+            gl.draw_arrays(gl_draw_type, 0, 6);
+        }
+    };
+    draw(&surface);
+
+    // Draw again whenever the canvas is resized
+    surface.observe(draw)?;
+
+    Ok(gl)
+}
+
+fn gl_draw_type(draw_type: &str) -> u32 {
+    match draw_type {
         "LINES" => WebGlRenderingContext::LINES,
         "LINE_STRIP" => WebGlRenderingContext::LINE_STRIP,
         "LINE_LOOP" => WebGlRenderingContext::LINE_LOOP,
@@ -139,9 +110,216 @@ pub fn draw_line(canvas_id: &str, draw_type: &str) -> Result<WebGlRenderingConte
         "TRIANGLE_FAN" => WebGlRenderingContext::TRIANGLE_FAN,
         "TRIANGLES" => WebGlRenderingContext::TRIANGLES,
         _ => WebGlRenderingContext::LINES
+    }
+}
+
+// Like draw_line, but the vertices are a curve flattened to within `tolerance` (in clip space
+// units) of the real thing. `kind` and the x, y pairs in `control_points` are as in
+// curve::flatten_chain, e.g. "cubic" with a start point and then two controls and an end per curve.
+#[wasm_bindgen]
+pub fn draw_curve(canvas_id: &str, draw_type: &str, kind: &str, control_points: &[f32], tolerance: f32) -> Result<WebGlRenderingContext, JsValue> {
+    /*======= Creating a canvas =========*/
+    let canvas = get_canvas(canvas_id);
+    let gl = canvas.get_context("webgl")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<WebGlRenderingContext>()
+        .unwrap();
+    let surface = Surface::new(canvas);
+
+    /*======= Flattening the curve ======*/
+    let strip = curve::flatten_chain(kind, &to_curve_points(control_points), tolerance)?;
+    let vertices: Vec<f32> = strip.iter().flat_map(|p| [p[0], p[1], 0.0]).collect();
+    let count = strip.len() as i32;
+
+    let mut vertex_buffer = Buffer::new(&gl, BufferTarget::Array, Usage::Static);
+    vertex_buffer.bind();
+    vertex_buffer.upload(&vertices);
+
+    /*=================== Shaders ====================*/
+    let mut programs = builtin::basic_programs(&gl);
+    let shader_program = programs.get(Variant::NONE)?;
+    shader_program.use_program();
+
+    let coord = shader_program.attrib_location("position")?;
+    gl.vertex_attrib_pointer_with_f64(coord, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
+    gl.enable_vertex_attrib_array(coord);
+
+    /*============ Drawing the curve =============*/
+    let gl_draw_type = gl_draw_type(draw_type);
+    let draw = {
+        let gl = gl.clone();
+        move |surface: &Surface| {
+            gl.clear_color(0.5, 0.5, 0.5, 0.9);
+            gl.enable(WebGlRenderingContext::DEPTH_TEST);
+            gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT);
+            surface.set_viewport(&gl);
+            gl.draw_arrays(gl_draw_type, 0, count);
+        }
     };
+    draw(&surface);
 
-    gl.draw_arrays(gl_draw_type, 0, 6);
+    // Draw again whenever the canvas is resized
+    surface.observe(draw)?;
 
     Ok(gl)
-}
\ No newline at end of file
+}
+
+fn to_curve_points(points: &[f32]) -> Vec<[f32; 2]> {
+    points.chunks_exact(2).map(|p| [p[0], p[1]]).collect()
+}
+
+// A wide line drawn with the polyline shader, with its points and style set from JS
+struct LineState {
+    line: Polyline,
+    style: PolylineStyle,
+    // Width in CSS pixels, scaled by devicePixelRatio when drawn
+    css_width: f32,
+}
+
+// Handle returned by draw_polyline
+#[wasm_bindgen]
+pub struct Polylines {
+    gl: WebGlRenderingContext,
+    surface: Surface,
+    program: Rc<ShaderProgram>,
+    state: Rc<RefCell<LineState>>,
+}
+
+#[wasm_bindgen]
+impl Polylines {
+    // x, y pairs in clip space (-1 to 1), as in draw_line; `closed` joins the last point to the first
+    pub fn set_points(&self, points: &[f32], closed: bool) {
+        let line = Polyline::new(&self.gl, &to_points(points), closed);
+        self.set_line(line);
+    }
+
+    // A curve instead of straight segments, as in draw_curve
+    pub fn set_curve(&self, kind: &str, control_points: &[f32], tolerance: f32, closed: bool) -> Result<(), JsValue> {
+        let strip = curve::flatten_chain(kind, &to_curve_points(control_points), tolerance)?;
+        let points: Vec<Vec3> = strip.iter().map(|p| Vec3::new(p[0], p[1], 0.0)).collect();
+        let line = Polyline::new(&self.gl, &points, closed);
+        self.set_line(line);
+        Ok(())
+    }
+
+    // Strokes every subpath of SVG path data, fitted to the canvas
+    pub fn set_svg_path(&self, d: &str) -> Result<(), JsValue> {
+        let lines: Vec<(Vec<Vec3>, bool)> = SvgPath::parse(d)?
+            .fit_to_clip(0.1)
+            .into_iter()
+            .map(|subpath| (subpath.points.iter().map(|p| Vec3::new(p[0], p[1], 0.0)).collect(), subpath.closed))
+            .collect();
+        let line = Polyline::from_lines(&self.gl, &lines);
+        self.set_line(line);
+        Ok(())
+    }
+
+    // In CSS pixels
+    pub fn set_width(&self, width: f32) {
+        self.state.borrow_mut().css_width = width;
+        self.redraw();
+    }
+
+    pub fn set_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.state.borrow_mut().style.color = Vec4::new(r, g, b, a);
+        self.redraw();
+    }
+
+    // "miter", "bevel" or "round"
+    pub fn set_join(&self, join: &str) -> Result<(), JsValue> {
+        let join = Join::from_name(join).ok_or_else(|| js_sys::Error::new(&format!("unknown join '{}'", join)))?;
+        self.state.borrow_mut().style.join = join;
+        self.redraw();
+        Ok(())
+    }
+
+    // "butt", "square" or "round"
+    pub fn set_cap(&self, cap: &str) -> Result<(), JsValue> {
+        let cap = Cap::from_name(cap).ok_or_else(|| js_sys::Error::new(&format!("unknown cap '{}'", cap)))?;
+        self.state.borrow_mut().style.cap = cap;
+        self.redraw();
+        Ok(())
+    }
+
+    // Miter length over line width past which miter joins turn into bevels
+    pub fn set_miter_limit(&self, limit: f32) {
+        self.state.borrow_mut().style.miter_limit = limit;
+        self.redraw();
+    }
+
+    fn set_line(&self, line: Polyline) {
+        let old = std::mem::replace(&mut self.state.borrow_mut().line, line);
+        old.delete();
+        self.redraw();
+    }
+
+    fn redraw(&self) {
+        if let Err(err) = draw_lines(&self.gl, &self.surface, &self.program, &self.state.borrow()) {
+            web_sys::console::error_1(&err.into());
+        }
+    }
+}
+
+fn to_points(points: &[f32]) -> Vec<Vec3> {
+    points.chunks_exact(2).map(|p| Vec3::new(p[0], p[1], 0.0)).collect()
+}
+
+fn draw_lines(gl: &WebGlRenderingContext, surface: &Surface, program: &ShaderProgram, state: &LineState) -> Result<(), webgl_common::ProgramError> {
+    gl.clear_color(0.5, 0.5, 0.5, 0.9);
+    gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+    surface.set_viewport(gl);
+
+    // The anti-aliased edges are blended over what's behind
+    gl.enable(WebGlRenderingContext::BLEND);
+    gl.blend_func(WebGlRenderingContext::SRC_ALPHA, WebGlRenderingContext::ONE_MINUS_SRC_ALPHA);
+
+    let style = PolylineStyle {
+        width: state.css_width * surface.pixel_ratio() as f32,
+        ..state.style
+    };
+    state.line.draw(program, &style, (surface.width(), surface.height()))
+}
+
+// Same canvas setup as draw_line, but the points come from JS and the line is `width` CSS pixels wide
+#[wasm_bindgen]
+pub fn draw_polyline(canvas_id: &str, points: &[f32], width: f32) -> Result<Polylines, JsValue> {
+    /*======= Creating a canvas =========*/
+    let canvas = get_canvas(canvas_id);
+    let gl = canvas.get_context("webgl")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<WebGlRenderingContext>()
+        .unwrap();
+    let surface = Surface::new(canvas);
+
+    /*=================== Shaders ====================*/
+    let mut programs = builtin::polyline_programs(&gl);
+    let program = programs.get(Variant::NONE)?;
+
+    /*============ Drawing the line =============*/
+    let state = Rc::new(RefCell::new(LineState {
+        line: Polyline::new(&gl, &to_points(points), false),
+        style: PolylineStyle {
+            color: Vec4::new(0.1, 0.1, 0.4, 1.0),
+            ..PolylineStyle::default()
+        },
+        css_width: width,
+    }));
+    let polylines = Polylines {
+        gl: gl.clone(),
+        surface: surface.clone(),
+        program: program.clone(),
+        state: state.clone(),
+    };
+    polylines.redraw();
+
+    // Draw again whenever the canvas is resized
+    surface.observe(move |surface| {
+        if let Err(err) = draw_lines(&gl, surface, &program, &state.borrow()) {
+            web_sys::console::error_1(&err.into());
+        }
+    })?;
+
+    Ok(polylines)
+}
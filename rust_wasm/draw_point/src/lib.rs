@@ -1,8 +1,19 @@
+use std::cell::RefCell;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlCanvasElement, WebGlRenderingContext};
+use webgl_common::points::{point_vertices, PointVertex};
+use webgl_common::{builtin, point_cloud, Buffer, BufferTarget, Camera, Mat4, Octree, PointShape, PointSprites, ProgramError, ShaderProgram, Surface, Usage, Variant};
 extern crate js_sys;
 
+fn request_animation_frame(f: &Closure<dyn FnMut(f64)>) {
+    web_sys::window()
+        .unwrap()
+        .request_animation_frame(f.as_ref().unchecked_ref())
+        .unwrap();
+}
+
 pub fn get_canvas(element_id: &str) -> HtmlCanvasElement {
     let document = web_sys::window().unwrap().document().unwrap();
     let canvas = document.get_element_by_id(element_id).unwrap();
@@ -21,6 +32,7 @@ pub fn draw_point() -> Result<WebGlRenderingContext, JsValue> {
         .unwrap()
         .dyn_into::<WebGlRenderingContext>()
         .unwrap();
+    let surface = Surface::new(canvas);
 
     /*==========Defining and storing the geometry=======*/
     let vertices: [f32; 9] = [
@@ -30,107 +42,351 @@ pub fn draw_point() -> Result<WebGlRenderingContext, JsValue> {
     ];
 
     // Create an empty buffer object to store the vertex buffer
-    let vertex_buffer = gl.create_buffer()
-        .unwrap();
+    let mut vertex_buffer = Buffer::new(&gl, BufferTarget::Array, Usage::Static);
 
     //Bind appropriate array buffer to it
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
+    vertex_buffer.bind();
 
     // Pass the vertex data to the buffer
-    let vertices_array = unsafe {
-        js_sys::Float32Array::view(&vertices)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ARRAY_BUFFER, &vertices_array, WebGlRenderingContext::STATIC_DRAW);
+    vertex_buffer.upload(&vertices);
 
     // Unbind the buffer
     gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
 
     /*=========================Shaders========================*/
 
-    // vertex shader source code
-    let vert_code = "
-        attribute vec3 coordinates;
+    // Compile the shared basic shader for this demo and link it
+    let mut programs = builtin::basic_programs(&gl);
+    let shader_program = programs.get(Variant::POINT_SIZE)?;
 
-        void main(void) {
-            gl_Position = vec4(coordinates, 1.0);
-            gl_PointSize = 10.0;
-        }
-    ";
+    // Use the combined shader program object
+    shader_program.use_program();
+    shader_program.set_f32("u_pointSize", 10.0)?;
 
-    // Create a vertex shader object
-    let vert_shader = gl.create_shader(WebGlRenderingContext::VERTEX_SHADER)
-        .unwrap();
+    /*======== Associating shaders to buffer objects ========*/
+
+    // Bind vertex buffer object
+    vertex_buffer.bind();
+
+    // Get the attribute location
+    let coord = shader_program.attrib_location("position")?;
+
+    // Point an attribute to the currently bound VBO
+    gl.vertex_attrib_pointer_with_f64(coord, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
+
+    // Enable the attribute
+    gl.enable_vertex_attrib_array(coord);
+
+    /*============= Drawing the primitive ===============*/
+    let draw = {
+        let gl = gl.clone();
+        move |surface: &Surface| {
+            // Clear the canvas
+            gl.clear_color(0.5, 0.5, 0.5, 0.9);
 
-    // Attach vertex shader source code
-    gl.shader_source(&vert_shader, vert_code);
+            // Enable the depth test
+            gl.enable(WebGlRenderingContext::DEPTH_TEST);
 
-    // Compile the vertex shader
-    gl.compile_shader(&vert_shader);
+            // Clear the color buffer bit
+            gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
 
-    // fragment shader source code
-    let frag_code = "
-        void main(void) {
-            gl_FragColor = vec4(0.0, 0.0, 0.0, 0.1);
+            // Set the view port
+            surface.set_viewport(&gl);
+
+            // Draw the triangle
+            gl.draw_arrays(WebGlRenderingContext::POINTS, 0, 3);
         }
-    ";
+    };
+    draw(&surface);
 
-    // Create fragment shader object
-    let frag_shader: web_sys::WebGlShader = gl.create_shader(WebGlRenderingContext::FRAGMENT_SHADER)
-        .unwrap();
+    // Draw again whenever the canvas is resized
+    surface.observe(draw)?;
 
-    // Attach fragment shader source code
-    gl.shader_source(&frag_shader, frag_code);
+    Ok(gl)
+}
 
-    // Compile the fragmentt shader
-    gl.compile_shader(&frag_shader);
+// Points whose positions, sizes and colors come from JS
+struct SpriteState {
+    sprites: PointSprites,
+    shape: PointShape,
+}
 
-    // Create a shader program object to store
-    // the combined shader program
-    let shader_program = gl.create_program()
-        .unwrap();
+// Handle returned by draw_sprites
+#[wasm_bindgen]
+pub struct Sprites {
+    gl: WebGlRenderingContext,
+    surface: Surface,
+    program: Rc<ShaderProgram>,
+    state: Rc<RefCell<SpriteState>>,
+}
 
-    // Attach a vertex shader
-    gl.attach_shader(&shader_program, &vert_shader);
+#[wasm_bindgen]
+impl Sprites {
+    // As in draw_sprites
+    pub fn set_points(&self, positions: &[f32], sizes: &[f32], colors: &[f32]) -> Result<(), JsValue> {
+        let sprites = PointSprites::new(&self.gl, &point_vertices(positions, sizes, colors)?);
+        let old = std::mem::replace(&mut self.state.borrow_mut().sprites, sprites);
+        old.delete();
+        self.redraw();
+        Ok(())
+    }
+
+    // "square", "round" or "soft"
+    pub fn set_shape(&self, shape: &str) -> Result<(), JsValue> {
+        self.state.borrow_mut().shape = to_shape(shape)?;
+        self.redraw();
+        Ok(())
+    }
+
+    // The largest point the GL draws, in CSS pixels
+    pub fn max_size(&self) -> f32 {
+        self.state.borrow().sprites.size_range()[1] / self.surface.pixel_ratio() as f32
+    }
+
+    fn redraw(&self) {
+        if let Err(err) = draw_sprite_state(&self.gl, &self.surface, &self.program, &self.state.borrow()) {
+            web_sys::console::error_1(&err.into());
+        }
+    }
+}
 
-    // Attach a fragment shader
-    gl.attach_shader(&shader_program, &frag_shader);
+fn to_shape(shape: &str) -> Result<PointShape, JsValue> {
+    PointShape::from_name(shape).ok_or_else(|| js_sys::Error::new(&format!("unknown point shape '{}'", shape)).into())
+}
 
-    // Link both programs
-    gl.link_program(&shader_program);
+fn draw_sprite_state(gl: &WebGlRenderingContext, surface: &Surface, program: &ShaderProgram, state: &SpriteState) -> Result<(), ProgramError> {
+    gl.clear_color(0.5, 0.5, 0.5, 0.9);
+    gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+    surface.set_viewport(gl);
 
-    // Use the combined shader program object
-    gl.use_program(Some(&shader_program));
+    // Round and soft sprites are see-through at the edges
+    gl.enable(WebGlRenderingContext::BLEND);
+    gl.blend_func(WebGlRenderingContext::SRC_ALPHA, WebGlRenderingContext::ONE_MINUS_SRC_ALPHA);
 
-    /*======== Associating shaders to buffer objects ========*/
+    state.sprites.draw(program, state.shape, surface.pixel_ratio() as f32)
+}
 
-    // Bind vertex buffer object
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
+// Like draw_point, but with x, y, z per point in `positions`, a size in CSS pixels per point in
+// `sizes` and r, g, b, a (0 to 1) per point in `colors`; one size or one color is used for all
+// the points. Sizes are held to ALIASED_POINT_SIZE_RANGE.
+#[wasm_bindgen]
+pub fn draw_sprites(canvas_id: &str, positions: &[f32], sizes: &[f32], colors: &[f32], shape: &str) -> Result<Sprites, JsValue> {
+    /*================Creating a canvas=================*/
+    let canvas = get_canvas(canvas_id);
+    let gl = canvas.get_context("webgl")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<WebGlRenderingContext>()
+        .unwrap();
+    let surface = Surface::new(canvas);
 
-    // Get the attribute location
-    let coord = gl.get_attrib_location(&shader_program, "coordinates");
+    /*=========================Shaders========================*/
+    let mut programs = builtin::point_programs(&gl);
+    let program = programs.get(Variant::NONE)?;
+
+    /*============= Drawing the points ===============*/
+    let state = Rc::new(RefCell::new(SpriteState {
+        sprites: PointSprites::new(&gl, &point_vertices(positions, sizes, colors)?),
+        shape: to_shape(shape)?,
+    }));
+    let sprites = Sprites {
+        gl: gl.clone(),
+        surface: surface.clone(),
+        program: program.clone(),
+        state: state.clone(),
+    };
+    sprites.redraw();
 
-    // Point an attribute to the currently bound VBO
-    gl.vertex_attrib_pointer_with_f64(coord as u32, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
+    // Draw again whenever the canvas is resized
+    surface.observe(move |surface| {
+        if let Err(err) = draw_sprite_state(&gl, surface, &program, &state.borrow()) {
+            web_sys::console::error_1(&err.into());
+        }
+    })?;
 
-    // Enable the attribute
-    gl.enable_vertex_attrib_array(coord as u32);
+    Ok(sprites)
+}
 
-    /*============= Drawing the primitive ===============*/
+// Points per octree node before it splits
+const LEAF_POINTS: usize = 16384;
+
+// Points of clouds without colors
+const CLOUD_COLOR: [u8; 4] = [210, 210, 200, 255];
+
+// A loaded point cloud, uploaded in octree order so each node is one draw_arrays run
+struct CloudState {
+    sprites: PointSprites,
+    octree: Octree,
+    // Most points drawn in a frame
+    budget: usize,
+    // CSS pixels
+    point_size: f32,
+    shape: PointShape,
+    // Drawn last frame
+    visible: usize,
+}
 
-    // Clear the canvas
-    gl.clear_color(0.5, 0.5, 0.5, 0.9);
+// Handle returned by view_point_cloud
+#[wasm_bindgen]
+pub struct PointCloudViewer {
+    camera: Rc<RefCell<Camera>>,
+    state: Rc<RefCell<CloudState>>,
+    points: usize,
+}
 
-    // Enable the depth test
+#[wasm_bindgen]
+impl PointCloudViewer {
+    // Radians around the middle of the cloud, e.g. from mouse drags
+    pub fn orbit_by(&self, d_yaw: f32, d_pitch: f32) {
+        self.camera.borrow_mut().orbit_by(d_yaw, d_pitch);
+    }
+
+    // factor < 1 moves closer, e.g. from the wheel
+    pub fn zoom_by(&self, factor: f32) {
+        self.camera.borrow_mut().zoom_by(factor);
+    }
+
+    pub fn set_point_budget(&self, budget: usize) {
+        self.state.borrow_mut().budget = budget;
+    }
+
+    pub fn set_point_size(&self, size: f32) {
+        self.state.borrow_mut().point_size = size;
+    }
+
+    // "square", "round" or "soft"
+    pub fn set_shape(&self, shape: &str) -> Result<(), JsValue> {
+        self.state.borrow_mut().shape = to_shape(shape)?;
+        Ok(())
+    }
+
+    // Points drawn in the last frame
+    pub fn visible_points(&self) -> usize {
+        self.state.borrow().visible
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.points
+    }
+}
+
+fn draw_cloud_state(gl: &WebGlRenderingContext, surface: &Surface, program: &ShaderProgram, camera: &mut Camera, state: &mut CloudState) -> Result<(), ProgramError> {
     gl.enable(WebGlRenderingContext::DEPTH_TEST);
+    gl.clear_color(0.5, 0.5, 0.5, 0.9);
+    gl.clear_depth(1.0);
+    // Follow CSS size / devicePixelRatio changes
+    if surface.resize() {
+        camera.aspect = surface.aspect();
+    }
+    surface.set_viewport(gl);
+    gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT);
+    gl.enable(WebGlRenderingContext::BLEND);
+    gl.blend_func(WebGlRenderingContext::SRC_ALPHA, WebGlRenderingContext::ONE_MINUS_SRC_ALPHA);
+
+    // Keep the depth range around the cloud wherever the orbit has gone
+    let (_, _, distance) = camera.orbit();
+    let radius = state.octree.nodes().first().map_or(1.0, |root| root.radius());
+    camera.near = (distance - radius).max(distance * 0.001);
+    camera.far = distance + radius;
+
+    let projection = camera.projection_matrix();
+    let view = camera.view_matrix();
+    let pixels_per_unit = projection.get(1, 1) * surface.height() as f32 * 0.5;
+    let nodes = state.octree.select(&(projection * view), camera.eye, pixels_per_unit, state.budget);
+    let ranges: Vec<(u32, u32)> = nodes
+        .iter()
+        .map(|&node| {
+            let node = &state.octree.nodes()[node as usize];
+            (node.first, node.count)
+        })
+        .collect();
+    state.visible = ranges.iter().map(|&(_, count)| count as usize).sum();
+
+    program.use_program();
+    program.set_mat4("Pmatrix", &projection)?;
+    program.set_mat4("Vmatrix", &view)?;
+    program.set_mat4("Mmatrix", &Mat4::IDENTITY)?;
+    // The points are all size 1, scaled here
+    let scale = surface.pixel_ratio() as f32 * state.point_size;
+    state.sprites.draw_ranges(program, state.shape, scale, &ranges)
+}
 
-    // Clear the color buffer bit
-    gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+// Shows the bytes of a .ply (ASCII or binary) or .xyz file, as fetched by the page, as points
+// `point_size` CSS pixels wide. Only the octree nodes in view are drawn, the ones biggest on
+// screen first, up to `point_budget` points a frame; move around with orbit_by and zoom_by.
+#[wasm_bindgen]
+pub fn view_point_cloud(canvas_id: &str, bytes: &[u8], format: &str, point_budget: usize, point_size: f32) -> Result<PointCloudViewer, JsValue> {
+    /*================Creating a canvas=================*/
+    let canvas = get_canvas(canvas_id);
+    let gl = canvas.get_context("webgl")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<WebGlRenderingContext>()
+        .unwrap();
+    let surface = Surface::new(canvas);
 
-    // Set the view port
-    gl.viewport(0, 0, canvas.width().try_into().unwrap(), canvas.height().try_into().unwrap());
+    /*==========Loading and sorting the points=======*/
+    let cloud = match format {
+        "ply" => point_cloud::parse_ply(bytes)?,
+        "xyz" => point_cloud::parse_xyz(bytes)?,
+        _ => return Err(js_sys::Error::new(&format!("unknown point cloud format '{}', expected ply or xyz", format)).into()),
+    };
+    let octree = Octree::new(&cloud, LEAF_POINTS);
+    let vertices: Vec<PointVertex> = octree
+        .order()
+        .iter()
+        .map(|&i| PointVertex {
+            position: cloud.positions[i as usize].to_array(),
+            size: 1.0,
+            color: cloud.colors.get(i as usize).copied().unwrap_or(CLOUD_COLOR),
+        })
+        .collect();
 
-    // Draw the triangle
-    gl.draw_arrays(WebGlRenderingContext::POINTS, 0, 3);
+    /*=========================Shaders========================*/
+    let mut programs = builtin::point_programs(&gl);
+    let program = programs.get(Variant::USE_MVP)?;
+
+    /*==================== Camera =====================*/
+    // Looking at the middle of the cloud from a little above, far enough back to see all of it
+    let mut camera = Camera::new(40.0, surface.aspect(), 0.1, 100.0);
+    if let Some(root) = octree.nodes().first() {
+        camera.target = root.center();
+        camera.set_orbit(0.6, 0.4, root.radius() * 2.0);
+    }
+    let camera = Rc::new(RefCell::new(camera));
+
+    /*============= Drawing the points ===============*/
+    let state = Rc::new(RefCell::new(CloudState {
+        sprites: PointSprites::new(&gl, &vertices),
+        octree,
+        budget: point_budget,
+        point_size,
+        shape: PointShape::Square,
+        visible: 0,
+    }));
+    let viewer = PointCloudViewer {
+        camera: camera.clone(),
+        state: state.clone(),
+        points: cloud.len(),
+    };
 
-    Ok(gl)
-}
\ No newline at end of file
+    // Drawing errors are logged when they change, not every frame
+    let mut last_error: Option<ProgramError> = None;
+    let f = Rc::new(RefCell::new(None));
+    let g = f.clone();
+    *g.borrow_mut() = Some(Closure::wrap(Box::new(move |_time: f64| {
+        // Skip the frame and keep going, the viewer stays responsive to the calls from JS
+        match draw_cloud_state(&gl, &surface, &program, &mut camera.borrow_mut(), &mut state.borrow_mut()) {
+            Err(err) if last_error.as_ref() != Some(&err) => {
+                web_sys::console::error_1(&err.clone().into());
+                last_error = Some(err);
+            }
+            Err(_) => {}
+            Ok(()) => last_error = None,
+        }
+        request_animation_frame(f.borrow().as_ref().unwrap());
+    }) as Box<dyn FnMut(f64)>));
+    request_animation_frame(g.borrow().as_ref().unwrap());
+
+    Ok(viewer)
+}
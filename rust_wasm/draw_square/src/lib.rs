@@ -1,6 +1,7 @@
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlCanvasElement, WebGlRenderingContext};
+use webgl_common::{builtin, mesh, svg, triangulate, Buffer, BufferTarget, FillRule, Mesh, Surface, SvgPath, Usage, Variant, VertexLayout};
 extern crate js_sys;
 
 pub fn get_canvas(element_id: &str) -> HtmlCanvasElement {
@@ -21,6 +22,7 @@ pub fn draw_square() -> Result<WebGlRenderingContext, JsValue> {
         .unwrap()
         .dyn_into::<WebGlRenderingContext>()
         .unwrap();
+    let surface = Surface::new(canvas);
 
     /*========== Defining and storing the geometry =========*/
 
@@ -32,126 +34,155 @@ pub fn draw_square() -> Result<WebGlRenderingContext, JsValue> {
     ];
 
     // Create an empty buffer object to store vertex buffer
-    let vertex_buffer = gl.create_buffer()
-        .unwrap();
+    let mut vertex_buffer = Buffer::new(&gl, BufferTarget::Array, Usage::Static);
 
     // Bind appropriate array buffer to it
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
+    vertex_buffer.bind();
 
     // Pass the vertex data to the buffer
-    let vertices_array = unsafe {
-        js_sys::Float32Array::view(&vertices)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ARRAY_BUFFER, &vertices_array, WebGlRenderingContext::STATIC_DRAW);
+    vertex_buffer.upload(&vertices);
 
     // Unbind the buffer
     gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
 
-    let indices = [3,2,1,3,1,0];
+    let indices: [u16 ; 6] = [3,2,1,3,1,0];
 
     // Create an empty buffer object to store Index buffer
-    let index_buffer = gl.create_buffer()
-        .unwrap();
+    let mut index_buffer = Buffer::new(&gl, BufferTarget::ElementArray, Usage::Static);
 
     // Bind appropriate array buffer to it
-    gl.bind_buffer(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, Some(&index_buffer));
+    index_buffer.bind();
 
     // Pass the vertex data to the buffer
-    let indicies_array = unsafe {
-        js_sys::Uint16Array::view(&indices)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, &indicies_array, WebGlRenderingContext::STATIC_DRAW);
+    index_buffer.upload(&indices);
 
     // Unbind the buffer
     gl.bind_buffer(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, None);
 
     /*====================== Shaders =======================*/
 
-    // Vertex shader source code
-    let vert_code ="
-        attribute vec3 coordinates;
-        void main(void) {
-            gl_Position = vec4(coordinates, 1.0);
-        }
-    ";
-
-    // Create a vertex shader object
-    let vert_shader = gl.create_shader(WebGlRenderingContext::VERTEX_SHADER)
-        .unwrap();
-
-    // Attach vertex shader source code
-    gl.shader_source(&vert_shader, vert_code);
-
-    // Compile the vertex shader
-    gl.compile_shader(&vert_shader);
-
-    // Fragment shader source code
-    let frag_code = "
-        void main(void) {
-            gl_FragColor = vec4(0.0, 0.0, 0.0, 0.1);
-        }
-    ";
-
-    // Create fragment shader object 
-    let frag_shader = gl.create_shader(WebGlRenderingContext::FRAGMENT_SHADER)
-        .unwrap();
-
-    // Attach fragment shader source code
-    gl.shader_source(&frag_shader, frag_code);
-
-    // Compile the fragmentt shader
-    gl.compile_shader(&frag_shader);
-
-    // Create a shader program object to
-    // store the combined shader program
-    let shader_program = gl.create_program()
-        .unwrap();
-
-    // Attach a vertex shader
-    gl.attach_shader(&shader_program, &vert_shader);
-
-    // Attach a fragment shader
-    gl.attach_shader(&shader_program, &frag_shader);
-
-    // Link both the programs
-    gl.link_program(&shader_program);
+    // Compile the shared basic shader for this demo and link it
+    let mut programs = builtin::basic_programs(&gl);
+    let shader_program = programs.get(Variant::NONE)?;
 
     // Use the combined shader program object
-    gl.use_program(Some(&shader_program));
+    shader_program.use_program();
 
     /* ======= Associating shaders to buffer objects =======*/
 
     // Bind vertex buffer object
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
+    vertex_buffer.bind();
 
     // Bind index buffer object
-    gl.bind_buffer(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, Some(&index_buffer)); 
+    index_buffer.bind(); 
 
     // Get the attribute location
-    let coord = gl.get_attrib_location(&shader_program, "coordinates");
+    let coord = shader_program.attrib_location("position")?;
 
     // Point an attribute to the currently bound VBO
-    gl.vertex_attrib_pointer_with_f64(coord as u32, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
+    gl.vertex_attrib_pointer_with_f64(coord, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
 
     // Enable the attribute
-    gl.enable_vertex_attrib_array(coord as u32);
+    gl.enable_vertex_attrib_array(coord);
 
     /*============= Drawing the Quad ================*/
+    let draw = {
+        let gl = gl.clone();
+        move |surface: &Surface| {
+            // Clear the canvas
+            gl.clear_color(0.5, 0.5, 0.5, 0.9);
 
-    // Clear the canvas
-    gl.clear_color(0.5, 0.5, 0.5, 0.9);
+            // Enable the depth test
+            gl.enable(WebGlRenderingContext::DEPTH_TEST);
 
-    // Enable the depth test
-    gl.enable(WebGlRenderingContext::DEPTH_TEST);
+            // Clear the color buffer bit
+            gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
 
-    // Clear the color buffer bit
-    gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+            // Set the view port
+            surface.set_viewport(&gl);
 
-    // Set the view port
-    gl.viewport(0,0,canvas.width().try_into().unwrap(),canvas.height().try_into().unwrap());
+            // Draw the triangle
+            gl.draw_elements_with_f64(WebGlRenderingContext::TRIANGLES, indices.len().try_into().unwrap(), WebGlRenderingContext::UNSIGNED_SHORT,0.0);
+        }
+    };
+    draw(&surface);
 
-    // Draw the triangle
-    gl.draw_elements_with_f64(WebGlRenderingContext::TRIANGLES, indices.len().try_into().unwrap(), WebGlRenderingContext::UNSIGNED_SHORT,0.0);
+    // Draw again whenever the canvas is resized
+    surface.observe(draw)?;
 
     Ok(gl)
-}
\ No newline at end of file
+}
+
+// Any simple polygon instead of the square: x, y pairs in clip space, the outline followed by each
+// hole, and the index of the point each hole starts at. `colors` is an r, g, b per point drawn as
+// in draw_color, or empty for the square's flat color.
+#[wasm_bindgen]
+pub fn draw_polygon(canvas_id: &str, points: &[f32], holes: &[u32], colors: &[f32]) -> Result<WebGlRenderingContext, JsValue> {
+    /*========== Triangulating the polygon =========*/
+    let point_list: Vec<triangulate::Point> = points.chunks_exact(2).map(|p| [p[0], p[1]]).collect();
+    if !colors.is_empty() && colors.len() != 3 * point_list.len() {
+        return Err(js_sys::Error::new(&format!("{} colors for {} points", colors.len() / 3, point_list.len())).into());
+    }
+    let hole_starts: Vec<usize> = holes.iter().map(|&start| start as usize).collect();
+    let indices = triangulate::triangulate(&point_list, &hole_starts);
+
+    // z is left to the attribute default of 0
+    if colors.is_empty() {
+        draw_filled(canvas_id, VertexLayout::new().position(2), &points[..2 * point_list.len()], &indices, Variant::NONE)
+    } else {
+        let vertices = mesh::interleave(&[(points, 2), (colors, 3)]);
+        draw_filled(canvas_id, VertexLayout::new().position(2).color(3), &vertices, &indices, Variant::VERTEX_COLOR)
+    }
+}
+
+// An SVG icon's path data, filled by `fill_rule` ("nonzero" or "evenodd") and fitted to the canvas
+#[wasm_bindgen]
+pub fn draw_svg_fill(canvas_id: &str, d: &str, fill_rule: &str) -> Result<WebGlRenderingContext, JsValue> {
+    let rule = FillRule::from_name(fill_rule).ok_or_else(|| js_sys::Error::new(&format!("unknown fill rule '{}'", fill_rule)))?;
+    let subpaths = SvgPath::parse(d)?.fit_to_clip(0.1);
+    let (points, indices) = svg::fill(&subpaths, rule);
+    let vertices: Vec<f32> = points.iter().flatten().copied().collect();
+    draw_filled(canvas_id, VertexLayout::new().position(2), &vertices, &indices, Variant::NONE)
+}
+
+fn draw_filled(canvas_id: &str, layout: VertexLayout, vertices: &[f32], indices: &[u32], variant: Variant) -> Result<WebGlRenderingContext, JsValue> {
+    /*============ Creating a canvas =================*/
+    let canvas = get_canvas(canvas_id);
+    let gl = canvas.get_context("webgl")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<WebGlRenderingContext>()
+        .unwrap();
+    let surface = Surface::new(canvas);
+
+    // Degenerate input triangulates to nothing; an empty index list would make Mesh draw the
+    // vertices as they come, so there is no mesh at all then and the canvas is only cleared
+    let mesh = if indices.is_empty() { None } else { Some(Mesh::from_f32(&gl, layout, vertices, indices)) };
+
+    /*====================== Shaders =======================*/
+    let mut programs = builtin::basic_programs(&gl);
+    let shader_program = programs.get(variant)?;
+    shader_program.use_program();
+    if let Some(mesh) = &mesh {
+        mesh.bind(&shader_program)?;
+    }
+
+    /*============= Drawing the polygon ================*/
+    let draw = {
+        let gl = gl.clone();
+        move |surface: &Surface| {
+            gl.clear_color(0.5, 0.5, 0.5, 0.9);
+            gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+            surface.set_viewport(&gl);
+            if let Some(mesh) = &mesh {
+                mesh.draw();
+            }
+        }
+    };
+    draw(&surface);
+
+    // Draw again whenever the canvas is resized
+    surface.observe(draw)?;
+
+    Ok(gl)
+}
@@ -1,6 +1,7 @@
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlCanvasElement, WebGlRenderingContext};
+use webgl_common::{builtin, Buffer, BufferTarget, Surface, Usage, Variant};
 extern crate js_sys;
 
 pub fn get_canvas(element_id: &str) -> HtmlCanvasElement {
@@ -24,107 +25,69 @@ pub fn draw_triangle() -> Result<WebGlRenderingContext, JsValue> {
         .unwrap()
         .dyn_into::<WebGlRenderingContext>()
         .unwrap();
+    let surface = Surface::new(canvas);
 
     /* Step2: Define the geometry and store it in buffer objects */
     let vertices: [f32; 6] = [-0.5, 0.5, -0.5, -0.5, 0.0, -0.5];
-    let vertices_array = unsafe { js_sys::Float32Array::view(&vertices) };
 
     // Create a new buffer object
-    let vertex_buffer = gl.create_buffer().unwrap();
+    let mut vertex_buffer = Buffer::new(&gl, BufferTarget::Array, Usage::Static);
 
     // Bind an empty array buffer to it
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
+    vertex_buffer.bind();
 
     // Pass the vertices data to the buffer
-    gl.buffer_data_with_array_buffer_view(
-        WebGlRenderingContext::ARRAY_BUFFER,
-        &vertices_array,
-        WebGlRenderingContext::STATIC_DRAW
-    );
+    vertex_buffer.upload(&vertices);
 
     // Unbind the buffer
     gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None);
 
     /* Step3: Create and compile Shader programs */
-    let vert_code ="
-        attribute vec2 coordinates;
-        void main(void) {
-            gl_Position = vec4(coordinates,0.0, 1.0);
-        }
-    ";
-
-    //Create a vertex shader object
-    let vert_shader = gl.create_shader(WebGlRenderingContext::VERTEX_SHADER)
-        .ok_or_else(|| JsValue::from_str("create vert shader error"))?;
-
-    //Attach vertex shader source code
-    gl.shader_source(&vert_shader, vert_code);
-
-    //Compile the vertex shader
-    gl.compile_shader(&vert_shader);
-
-    //Fragment shader source code
-    let frag_code = "
-        void main(void) {
-            gl_FragColor = vec4(0.0, 0.0, 0.0, 0.1);
-        }
-    ";
-
-    // Create fragment shader object
-    let frag_shader = gl.create_shader(WebGlRenderingContext::FRAGMENT_SHADER)
-        .ok_or_else(|| JsValue::from_str("create frag shader error"))?;
-
-    // Attach fragment shader source code
-    gl.shader_source(&frag_shader, frag_code);
-
-    // Compile the fragment shader
-    gl.compile_shader(&frag_shader);
-
-    // Create a shader program object to store combined shader program
-    let shader_program = gl.create_program().unwrap();
-
-    // Attach a vertex shader
-    gl.attach_shader(&shader_program, &vert_shader);
-
-    // Attach a fragment shader
-    gl.attach_shader(&shader_program, &frag_shader);
-
-    // Link both programs
-    gl.link_program(&shader_program);
+    // Compile the shared basic shader for this demo and link it
+    let mut programs = builtin::basic_programs(&gl);
+    let shader_program = programs.get(Variant::NONE)?;
 
     // Use the combined shader program object
-    gl.use_program(Some(&shader_program));
+    shader_program.use_program();
 
     /* Step 4: Associate the shader programs to buffer objects */
 
     //Bind vertex buffer object
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
+    vertex_buffer.bind();
 
     //Get the attribute location
-    let coord = gl.get_attrib_location(&shader_program, "coordinates");
+    let coord = shader_program.attrib_location("position")?;
 
     //point an attribute to the currently bound VBO
-    gl.vertex_attrib_pointer_with_i32(coord as u32, 2, WebGlRenderingContext::FLOAT, false, 0, 0);
+    gl.vertex_attrib_pointer_with_i32(coord, 2, WebGlRenderingContext::FLOAT, false, 0, 0);
 
     //Enable the attribute
-    gl.enable_vertex_attrib_array(coord as u32);
+    gl.enable_vertex_attrib_array(coord);
 
     /* Step5: Drawing the required object (triangle) */
+    let draw = {
+        let gl = gl.clone();
+        move |surface: &Surface| {
+            // Clear the canvas
+            gl.clear_color(0.5, 0.5, 0.5, 0.9);
+
+            // Enable the depth test
+            gl.enable(WebGlRenderingContext::DEPTH_TEST);
 
-    // Clear the canvas
-    gl.clear_color(0.5, 0.5, 0.5, 0.9);
+            // Clear the color buffer bit
+            gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
 
-    // Enable the depth test
-    gl.enable(WebGlRenderingContext::DEPTH_TEST); 
-         
-    // Clear the color buffer bit
-    gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+            // Set the view port
+            surface.set_viewport(&gl);
 
-    // Set the view port
-    gl.viewport(0, 0, canvas.width().try_into().unwrap(), canvas.height().try_into().unwrap());
+            // Draw the triangle
+            gl.draw_arrays(WebGlRenderingContext::TRIANGLES, 0, 3);
+        }
+    };
+    draw(&surface);
 
-    // Draw the triangle
-    gl.draw_arrays(WebGlRenderingContext::TRIANGLES, 0, 3);
+    // Draw again whenever the canvas is resized
+    surface.observe(draw)?;
 
     Ok(gl)
 }
\ No newline at end of file
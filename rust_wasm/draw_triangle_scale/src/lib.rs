@@ -1,6 +1,7 @@
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlCanvasElement, WebGlRenderingContext};
+use webgl_common::{builtin, Buffer, BufferTarget, Mat4, Surface, Usage, Variant, Vec3};
 extern crate js_sys;
 
 pub fn get_canvas(element_id: &str) -> HtmlCanvasElement {
@@ -21,6 +22,7 @@ pub fn draw_triangle_scale() -> Result<WebGlRenderingContext, JsValue> {
         .unwrap()
         .dyn_into::<WebGlRenderingContext>()
         .unwrap();
+    let surface = Surface::new(canvas);
 
     /*===========Defining and storing the geometry==============*/
     let vertices =  [
@@ -30,49 +32,17 @@ pub fn draw_triangle_scale() -> Result<WebGlRenderingContext, JsValue> {
     ];
 
     //Create an empty buffer object and store vertex data
-    let vertices_array = unsafe {
-        js_sys::Float32Array::view(&vertices)
-    };
-    let vertex_buffer = gl.create_buffer().unwrap();                                                  
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ARRAY_BUFFER, &vertices_array, WebGlRenderingContext::STATIC_DRAW);           
+    let mut vertex_buffer = Buffer::new(&gl, BufferTarget::Array, Usage::Static);                                                  
+    vertex_buffer.bind();
+    vertex_buffer.upload(&vertices);           
     gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, None); 
 
     /*========================Shaders============================*/
 
-    //Vertex shader source code
-    let vert_code = "
-        attribute vec4 coordinates;
-        uniform mat4 u_xformMatrix;
-        void main(void) {
-            gl_Position = u_xformMatrix * coordinates;
-        }
-    ";
-
-    //Create a vertex shader program object and compile it                
-    let vert_shader = gl.create_shader(WebGlRenderingContext::VERTEX_SHADER).unwrap();
-    gl.shader_source(&vert_shader, &vert_code);
-    gl.compile_shader(&vert_shader);
-
-    //fragment shader source code
-    let frag_code = "
-        void main(void) {
-            gl_FragColor = vec4(0.0, 0.0, 0.0, 0.1);
-        }
-    ";
-
-    //Create a fragment shader program object and compile it 
-    let frag_shader = gl.create_shader(WebGlRenderingContext::FRAGMENT_SHADER).unwrap();
-    gl.shader_source(&frag_shader, &frag_code);
-    gl.compile_shader(&frag_shader);
+    let mut programs = builtin::basic_programs(&gl);
+    let shader_program = programs.get(Variant::USE_TRANSFORM)?;
 
-    //Create and use combiened shader program
-    let shader_program = gl.create_program().unwrap();
-    gl.attach_shader(&shader_program, &vert_shader);
-    gl.attach_shader(&shader_program, &frag_shader);
-    gl.link_program(&shader_program);
-
-    gl.use_program(Some(&shader_program));
+    shader_program.use_program();
 
     /*===================scaling==========================*/
 
@@ -80,34 +50,33 @@ pub fn draw_triangle_scale() -> Result<WebGlRenderingContext, JsValue> {
     let sy = 1.5;
     let sz = 1.0;
 
-    let form_matrix = [
-        sx,   0.0,  0.0,  0.0,
-        0.0,  sy,   0.0,  0.0,
-        0.0,  0.0,  sz,   0.0,
-        0.0,  0.0,  0.0,  1.0  
-    ];
-
-    let form_matrix_array = unsafe {
-        js_sys::Float32Array::view(&form_matrix)
-    };
+    let form_matrix = Mat4::scale(Vec3::new(sx, sy, sz));
 
-    let u_xform_matrix = gl.get_uniform_location(&shader_program, "u_xformMatrix").unwrap();
-    gl.uniform_matrix4fv_with_f32_sequence(Some(&u_xform_matrix), false, &form_matrix_array);
+    shader_program.set_mat4("u_xformMatrix", &form_matrix)?;
 
     /* ===========Associating shaders to buffer objects============*/
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));   
+    vertex_buffer.bind();   
 
-    let coord = gl.get_attrib_location(&shader_program, "coordinates"); 
-    gl.vertex_attrib_pointer_with_f64(coord as u32, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
-    gl.enable_vertex_attrib_array(coord as u32);
+    let coord = shader_program.attrib_location("position")?; 
+    gl.vertex_attrib_pointer_with_f64(coord, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
+    gl.enable_vertex_attrib_array(coord);
 
     /*=================Drawing the Quad========================*/ 
-    gl.clear_color(0.5, 0.5, 0.5, 0.9);
-    gl.enable(WebGlRenderingContext::DEPTH_TEST);
+    let draw = {
+        let gl = gl.clone();
+        move |surface: &Surface| {
+            gl.clear_color(0.5, 0.5, 0.5, 0.9);
+            gl.enable(WebGlRenderingContext::DEPTH_TEST);
+
+            gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+            surface.set_viewport(&gl);
+            gl.draw_arrays(WebGlRenderingContext::TRIANGLES, 0, 3);
+        }
+    };
+    draw(&surface);
 
-    gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
-    gl.viewport(0,0,canvas.width().try_into().unwrap(),canvas.height().try_into().unwrap());
-    gl.draw_arrays(WebGlRenderingContext::TRIANGLES, 0, 3);
+    // Draw again whenever the canvas is resized
+    surface.observe(draw)?;
 
     Ok(gl)
 }
\ No newline at end of file
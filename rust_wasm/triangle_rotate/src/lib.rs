@@ -4,6 +4,7 @@ use wasm_bindgen::prelude::*;
 
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlCanvasElement, WebGlRenderingContext};
+use webgl_common::{builtin, Camera, Mat4, Mesh, Projection, Surface, Variant, Vec3, Vertex};
 extern crate js_sys;
 
 pub fn window() -> web_sys::Window {
@@ -40,8 +41,59 @@ pub fn get_canvas(element_id: &str) -> HtmlCanvasElement {
     canvas
 }
 
+#[derive(Clone, Copy, Vertex)]
+#[repr(C)]
+struct ColorVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+// Handle returned to JS so a page can move the camera of the running scene
+#[wasm_bindgen]
+pub struct Scene {
+    camera: Rc<RefCell<Camera>>,
+}
+
 #[wasm_bindgen]
-pub fn triangle_rotate() -> Result<(), JsValue> {
+impl Scene {
+    pub fn look_at(&self, eye_x: f32, eye_y: f32, eye_z: f32, target_x: f32, target_y: f32, target_z: f32) {
+        self.camera.borrow_mut().look_at(Vec3::new(eye_x, eye_y, eye_z), Vec3::new(target_x, target_y, target_z), Vec3::Y);
+    }
+
+    pub fn set_perspective(&self, fov: f32, near: f32, far: f32) {
+        self.camera.borrow_mut().set_perspective(fov, near, far);
+    }
+
+    // yaw/pitch in radians around the current target
+    pub fn set_orbit(&self, yaw: f32, pitch: f32, distance: f32) {
+        self.camera.borrow_mut().set_orbit(yaw, pitch, distance);
+    }
+
+    pub fn orbit_by(&self, d_yaw: f32, d_pitch: f32) {
+        self.camera.borrow_mut().orbit_by(d_yaw, d_pitch);
+    }
+
+    pub fn zoom_by(&self, factor: f32) {
+        self.camera.borrow_mut().zoom_by(factor);
+    }
+
+    // Blends into the orthographic projection over `duration_ms` (0 = switch right away)
+    pub fn use_orthographic(&self, duration_ms: f32) {
+        self.camera.borrow_mut().set_projection(Projection::Orthographic, duration_ms);
+    }
+
+    pub fn use_perspective(&self, duration_ms: f32) {
+        self.camera.borrow_mut().set_projection(Projection::Perspective, duration_ms);
+    }
+
+    // Height of the orthographic view; 0 or less matches the perspective framing at the target
+    pub fn set_ortho_size(&self, size: f32) {
+        self.camera.borrow_mut().ortho_size = if size > 0.0 { Some(size) } else { None };
+    }
+}
+
+#[wasm_bindgen]
+pub fn triangle_rotate() -> Result<Scene, JsValue> {
     /*=================Creating a canvas=========================*/
     let canvas = get_canvas("wasm_canvas");
     let gl = canvas.get_context("webgl")
@@ -49,120 +101,34 @@ pub fn triangle_rotate() -> Result<(), JsValue> {
         .unwrap()
         .dyn_into::<WebGlRenderingContext>()
         .unwrap();
+    let surface = Surface::new(canvas);
 
     /*===========Defining and storing the geometry==============*/
+    let white = [1.0, 1.0, 1.0];
     let vertices = [
-         -1.0, -1.0, 0.0, 
-         1.0, -1.0, 0.0, 
-         1.0, 1.0, 0.0
+        ColorVertex { position: [-1.0, -1.0, 0.0], color: white },
+        ColorVertex { position: [ 1.0, -1.0, 0.0], color: white },
+        ColorVertex { position: [ 1.0,  1.0, 0.0], color: white },
     ];
 
-    let colors = [
-        1.0, 1.0, 1.0,
-        1.0, 1.0, 1.0,
-        1.0, 1.0, 1.0
-    ];
+    let indices: [u16 ; 3] = [0,1,2];
 
-    let indices = [0,1,2];
-
-    //Create and store data into vertex buffer
-    let vertex_buffer = gl.create_buffer().unwrap();
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
-    let vertices_array = unsafe {
-        js_sys::Float32Array::view(&vertices)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ARRAY_BUFFER, &vertices_array, WebGlRenderingContext::STATIC_DRAW);
-
-    //Create and store data into color buffer
-    let color_buffer = gl.create_buffer().unwrap();
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&color_buffer));
-    let colors_array = unsafe {
-        js_sys::Float32Array::view(&colors)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ARRAY_BUFFER, &colors_array, WebGlRenderingContext::STATIC_DRAW);
-
-    //Create and store data into index buffer
-    let index_buffer = gl.create_buffer().unwrap();
-    gl.bind_buffer(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, Some(&index_buffer));
-    let indices_array = unsafe {
-        js_sys::Uint16Array::view(&indices)
-    };
-    gl.buffer_data_with_array_buffer_view(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, &indices_array, WebGlRenderingContext::STATIC_DRAW);
+    let mesh = Mesh::from_vertices(&gl, &vertices, &indices);
 
     /*==========================Shaders=========================*/
-    let vert_code = "
-        attribute vec3 position;
-
-        uniform mat4 Pmatrix;
-        uniform mat4 Vmatrix;
-        uniform mat4 Mmatrix;
-
-        attribute vec3 color;
-        varying vec3 vColor;
-
-        void main(void) {
-            gl_Position = Pmatrix*Vmatrix*Mmatrix*vec4(position, 1.);
-            vColor = color;
-        }
-    ";
-
-    let frag_code = "
-        precision mediump float;
-        varying vec3 vColor;
-        void main(void) {
-            gl_FragColor = vec4(vColor, 1.);
-        }
-    ";
-
-    let vert_shader = gl.create_shader(WebGlRenderingContext::VERTEX_SHADER).unwrap();
-    gl.shader_source(&vert_shader, &vert_code);
-    gl.compile_shader(&vert_shader);
-
-    let frag_shader = gl.create_shader(WebGlRenderingContext::FRAGMENT_SHADER).unwrap();
-    gl.shader_source(&frag_shader, &frag_code);
-    gl.compile_shader(&frag_shader);
-
-    let shader_program = gl.create_program().unwrap();
-    gl.attach_shader(&shader_program, &vert_shader);
-    gl.attach_shader(&shader_program, &frag_shader);
-    gl.link_program(&shader_program);
+    let mut programs = builtin::basic_programs(&gl);
+    let shader_program = programs.get(Variant::VERTEX_COLOR | Variant::USE_MVP)?;
 
     /*===========associating attributes to vertex shader ============*/
-    let p_matrix_loc = gl.get_uniform_location(&shader_program, "Pmatrix").unwrap();
-    let v_matrix_loc = gl.get_uniform_location(&shader_program, "Vmatrix").unwrap();
-    let m_matrix_loc = gl.get_uniform_location(&shader_program, "Mmatrix").unwrap();
-
-    let position = gl.get_attrib_location(&shader_program, "position");
-    let color = gl.get_attrib_location(&shader_program, "color");
-
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
-    gl.vertex_attrib_pointer_with_f64(position as u32, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
-    gl.enable_vertex_attrib_array(position as u32);
-
-    gl.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&color_buffer));
-    gl.vertex_attrib_pointer_with_f64(color as u32, 3, WebGlRenderingContext::FLOAT, false, 0, 0.0);
-    gl.enable_vertex_attrib_array(color as u32);
+    mesh.bind(&shader_program)?;
 
-    gl.use_program(Some(&shader_program));
+    shader_program.use_program();
 
-    let proj_matrix = get_projection(40.0, canvas.width() as f32/canvas.height() as f32, 1.0, 100.0);
-    let mut view_matrix = [
-        1.0, 0.0, 0.0, 0.0, 
-        0.0, 1.0, 0.0, 0.0, 
-        0.0, 0.0, 1.0, 0.0, 
-        0.0, 0.0, 0.0, 1.0
-    ];
-    let mut mov_matrix = [
-        1.0, 0.0, 0.0, 0.0, 
-        0.0, 1.0, 0.0, 0.0, 
-        0.0, 0.0, 1.0, 0.0, 
-        0.0, 0.0, 0.0, 1.0
-    ];
-
-    //translating z
-    view_matrix[14] = view_matrix[14]-6.0; //zoom
+    let camera = Rc::new(RefCell::new(Camera::new(40.0, surface.aspect(), 1.0, 100.0)));
+    let mut mov_matrix = Mat4::IDENTITY;
 
     /*=================Drawing===========================*/
+    let scene = Scene { camera: camera.clone() };
     let time_old = Rc::new(RefCell::new(0.0));
     let f = Rc::new(RefCell::new(None));
     let g = f.clone();
@@ -175,55 +141,38 @@ pub fn triangle_rotate() -> Result<(), JsValue> {
         }
         */
         let dt = time - *time_old.borrow();
-        rotate_z(&mut mov_matrix, dt as f32 * 0.002);
+        mov_matrix.rotate_z(dt as f32 * 0.002);
         *time_old.borrow_mut() = time;
+        camera.borrow_mut().update(dt as f32);
 
         gl.enable(WebGlRenderingContext::DEPTH_TEST);
         gl.depth_func(WebGlRenderingContext::LEQUAL);
         gl.clear_color(0.5, 0.5, 0.5, 0.9);
         gl.clear_depth(1.0);
-        gl.viewport(0, 0, canvas.width().try_into().unwrap(), canvas.height().try_into().unwrap());
+        // Follow CSS size / devicePixelRatio changes
+        if surface.resize() {
+            camera.borrow_mut().aspect = surface.aspect();
+        }
+        surface.set_viewport(&gl);
         gl.clear(WebGlRenderingContext::COLOR_BUFFER_BIT | WebGlRenderingContext::DEPTH_BUFFER_BIT);
 
-        gl.uniform_matrix4fv_with_f32_array(Some(&p_matrix_loc), false, &proj_matrix);
-        gl.uniform_matrix4fv_with_f32_array(Some(&v_matrix_loc), false, &view_matrix);
-        gl.uniform_matrix4fv_with_f32_array(Some(&m_matrix_loc), false, &mov_matrix);
+        let proj_matrix = camera.borrow().projection_matrix();
+        let view_matrix = camera.borrow().view_matrix();
+        let uniforms = shader_program.set_mat4("Pmatrix", &proj_matrix)
+            .and_then(|_| shader_program.set_mat4("Vmatrix", &view_matrix))
+            .and_then(|_| shader_program.set_mat4("Mmatrix", &mov_matrix));
+        if let Err(err) = uniforms {
+            // Nothing sensible to draw with, stop the loop
+            web_sys::console::error_1(&err.into());
+            let _ = f.borrow_mut().take();
+            return;
+        }
 
-        gl.bind_buffer(WebGlRenderingContext::ELEMENT_ARRAY_BUFFER, Some(&index_buffer));
-        gl.draw_elements_with_i32(WebGlRenderingContext::TRIANGLES, indices.len() as i32, WebGlRenderingContext::UNSIGNED_SHORT, 0);
+        mesh.draw();
 
         request_animation_frame(f.borrow().as_ref().unwrap());
     }) as Box<dyn FnMut(f64)>));
     request_animation_frame(g.borrow().as_ref().unwrap());
 
-    Ok(())
-}
-
-pub fn get_projection(angle: f32, a: f32, z_min: f32, z_max: f32) -> [f32; 16] {
-    //let radians = (angle * 0.5) * std::f32::consts::PI / 180.0;
-    //let ang = radians.tan();
-
-    let ang = (angle * 0.5).to_radians().tan();
-
-    [
-        0.5/ang, 0.0, 0.0, 0.0,
-        0.0, 0.5*a/ang, 0.0, 0.0,
-        0.0, 0.0, -(z_max + z_min)/(z_max - z_min), -1.0,
-        0.0, 0.0, (-2.0*z_max*z_min) as f32/(z_max-z_min), 0.0
-    ]
+    Ok(scene)
 }
-
-pub fn rotate_z(matrix: &mut [f32; 16], angle: f32) {
-    let cos_angle = angle.cos();
-    let sin_angle = angle.sin();
-    let mv0 = matrix[0];
-    let mv4 = matrix[4];
-    let mv8 = matrix[8];
-
-    matrix[0] = cos_angle*matrix[0] - sin_angle*matrix[1];
-    matrix[4] = cos_angle*matrix[4] - sin_angle*matrix[5];
-    matrix[8] = cos_angle*matrix[8] - sin_angle*matrix[9];
-    matrix[1] = cos_angle*matrix[1] + sin_angle*mv0;
-    matrix[5] = cos_angle*matrix[5] + sin_angle*mv4;
-    matrix[9] = cos_angle*matrix[9] + sin_angle*mv8;
-}
\ No newline at end of file
@@ -5,6 +5,7 @@ use rand::Rng;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlCanvasElement, CanvasRenderingContext2d};
+use webgl_common::Surface;
 extern crate js_sys;
 
 fn window() -> web_sys::Window {
@@ -46,7 +47,7 @@ impl Ball {
         let x_speed = random_speed_x * 4.0;
         let y_speed = random_speed_y * 4.0;
 
-        Ball { x: x_pos, y: y_pos, x_speed: x_speed, y_speed: y_speed }
+        Ball { x: x_pos, y: y_pos, x_speed, y_speed }
     }
 }
 
@@ -80,17 +81,23 @@ pub fn wasm_canvas_stress() -> Result<(), JsValue> {
         .unwrap()
         .dyn_into::<CanvasRenderingContext2d>()
         .unwrap();
+    let surface = Surface::new(canvas);
 
     let mut balls: Vec<Ball> = Vec::new();
     let mut ball_count = 0;
-    let canvas_width = canvas.width();
-    let canvas_height = canvas.height();
 
     let time_old = Rc::new(RefCell::new(0.0));
     let f = Rc::new(RefCell::new(None));
     let g = f.clone();
 
     *g.borrow_mut() = Some(Closure::wrap(Box::new(move |time: f64| {
+        // Backing store follows CSS size × devicePixelRatio; the balls keep moving in CSS pixels
+        surface.resize();
+        let ratio = surface.pixel_ratio();
+        let canvas_width = (surface.width() as f64 / ratio) as u32;
+        let canvas_height = (surface.height() as f64 / ratio) as u32;
+        ctx.set_transform(ratio, 0.0, 0.0, ratio, 0.0, 0.0).unwrap();
+
         ctx.clear_rect(0.0, 0.0, canvas_width as f64, canvas_height as f64);
 
         let delta_time = time - *time_old.borrow();
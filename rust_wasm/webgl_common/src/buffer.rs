@@ -0,0 +1,195 @@
+use std::fmt;
+
+use js_sys::{Float32Array, Object, Uint16Array, Uint32Array, Uint8Array};
+use wasm_bindgen::prelude::*;
+use web_sys::{WebGlBuffer, WebGlRenderingContext};
+
+type Gl = WebGlRenderingContext;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferTarget {
+    Array,
+    ElementArray,
+}
+
+impl BufferTarget {
+    pub fn gl_enum(&self) -> u32 {
+        match self {
+            BufferTarget::Array => Gl::ARRAY_BUFFER,
+            BufferTarget::ElementArray => Gl::ELEMENT_ARRAY_BUFFER,
+        }
+    }
+}
+
+// How often the contents change, passed to buffer_data as a hint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Usage {
+    // Uploaded once, drawn many times
+    Static,
+    // Updated now and then
+    Dynamic,
+    // Replaced about every frame
+    Stream,
+}
+
+impl Usage {
+    pub fn gl_enum(&self) -> u32 {
+        match self {
+            Usage::Static => Gl::STATIC_DRAW,
+            Usage::Dynamic => Gl::DYNAMIC_DRAW,
+            Usage::Stream => Gl::STREAM_DRAW,
+        }
+    }
+}
+
+// Element types that can be uploaded. The data is copied into a new JS typed array,
+// so nothing on the JS side points into wasm memory when it grows.
+pub trait BufferElement: Copy {
+    fn to_typed_array(data: &[Self]) -> Object;
+}
+
+impl BufferElement for f32 {
+    fn to_typed_array(data: &[Self]) -> Object {
+        Float32Array::from(data).into()
+    }
+}
+
+impl BufferElement for u16 {
+    fn to_typed_array(data: &[Self]) -> Object {
+        Uint16Array::from(data).into()
+    }
+}
+
+impl BufferElement for u32 {
+    fn to_typed_array(data: &[Self]) -> Object {
+        Uint32Array::from(data).into()
+    }
+}
+
+impl BufferElement for u8 {
+    fn to_typed_array(data: &[Self]) -> Object {
+        Uint8Array::from(data).into()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferError {
+    // Byte range the update tried to write
+    pub offset: usize,
+    pub len: usize,
+    // Bytes the buffer holds
+    pub size: usize,
+}
+
+impl fmt::Display for BufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "buffer update of {} bytes at {} doesn't fit in {} bytes", self.len, self.offset, self.size)
+    }
+}
+
+impl From<BufferError> for JsValue {
+    fn from(err: BufferError) -> JsValue {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+// A GL buffer that remembers its target, usage and size
+pub struct Buffer {
+    gl: Gl,
+    buffer: WebGlBuffer,
+    target: BufferTarget,
+    usage: Usage,
+    // In bytes
+    size: usize,
+}
+
+impl Buffer {
+    pub fn new(gl: &Gl, target: BufferTarget, usage: Usage) -> Self {
+        Buffer {
+            gl: gl.clone(),
+            buffer: gl.create_buffer().unwrap(),
+            target,
+            usage,
+            size: 0,
+        }
+    }
+
+    pub fn with_data<T: BufferElement>(gl: &Gl, target: BufferTarget, data: &[T], usage: Usage) -> Self {
+        let mut buffer = Buffer::new(gl, target, usage);
+        buffer.upload(data);
+        buffer
+    }
+
+    pub fn gl_buffer(&self) -> &WebGlBuffer {
+        &self.buffer
+    }
+
+    pub fn target(&self) -> BufferTarget {
+        self.target
+    }
+
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn bind(&self) {
+        self.gl.bind_buffer(self.target.gl_enum(), Some(&self.buffer));
+    }
+
+    // Replaces the whole contents; the buffer takes the size of `data`. Leaves the buffer bound.
+    pub fn upload<T: BufferElement>(&mut self, data: &[T]) {
+        self.bind();
+        self.gl
+            .buffer_data_with_array_buffer_view(self.target.gl_enum(), &T::to_typed_array(data), self.usage.gl_enum());
+        self.size = std::mem::size_of_val(data);
+    }
+
+    // Overwrites part of the contents starting `offset` elements of T in. Leaves the buffer bound.
+    pub fn update<T: BufferElement>(&self, offset: usize, data: &[T]) -> Result<(), BufferError> {
+        let byte_offset = offset * std::mem::size_of::<T>();
+        check_range(byte_offset, std::mem::size_of_val(data), self.size)?;
+        self.bind();
+        self.gl
+            .buffer_sub_data_with_i32_and_array_buffer_view(self.target.gl_enum(), byte_offset as i32, &T::to_typed_array(data));
+        Ok(())
+    }
+
+    pub fn delete(self) {
+        self.gl.delete_buffer(Some(&self.buffer));
+    }
+}
+
+fn check_range(offset: usize, len: usize, size: usize) -> Result<(), BufferError> {
+    match offset.checked_add(len) {
+        Some(end) if end <= size => Ok(()),
+        _ => Err(BufferError { offset, len, size }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_have_to_fit() {
+        assert!(check_range(0, 48, 48).is_ok());
+        assert!(check_range(12, 24, 48).is_ok());
+        assert!(check_range(48, 0, 48).is_ok());
+
+        let err = check_range(40, 12, 48).unwrap_err();
+        assert_eq!(err.to_string(), "buffer update of 12 bytes at 40 doesn't fit in 48 bytes");
+        assert!(check_range(usize::MAX, 2, 48).is_err());
+    }
+
+    #[test]
+    fn usage_hints() {
+        assert_eq!(Usage::Static.gl_enum(), Gl::STATIC_DRAW);
+        assert_eq!(Usage::Dynamic.gl_enum(), Gl::DYNAMIC_DRAW);
+        assert_eq!(Usage::Stream.gl_enum(), Gl::STREAM_DRAW);
+        assert_eq!(BufferTarget::ElementArray.gl_enum(), Gl::ELEMENT_ARRAY_BUFFER);
+    }
+}
@@ -0,0 +1,107 @@
+use web_sys::WebGlRenderingContext;
+
+use crate::preprocess::Preprocessor;
+use crate::program::ProgramCache;
+
+// The shader every demo draws with: flat color or per-vertex color, optionally textured, lit from the
+// normals, with a wireframe overlay and moved by a single transform or the full Pmatrix/Vmatrix/Mmatrix chain. See preprocess::Variant.
+pub const BASIC_VERT: &str = include_str!("glsl/basic.vert");
+pub const BASIC_FRAG: &str = include_str!("glsl/basic.frag");
+
+// Wide anti-aliased lines, see polyline.rs. Only the transform flags of the variant apply.
+pub const POLYLINE_VERT: &str = include_str!("glsl/polyline.vert");
+pub const POLYLINE_FRAG: &str = include_str!("glsl/polyline.frag");
+
+// Point sprites with per-point size and color, see points.rs. Only the transform flags of the variant apply.
+pub const POINT_VERT: &str = include_str!("glsl/point.vert");
+pub const POINT_FRAG: &str = include_str!("glsl/point.frag");
+
+// Named snippets available to #include
+pub const SNIPPETS: [(&str, &str); 1] = [("transform", include_str!("glsl/transform.glsl"))];
+
+pub fn preprocessor() -> Preprocessor {
+    let mut preprocessor = Preprocessor::new();
+    for (name, source) in SNIPPETS {
+        preprocessor.add_snippet(name, source);
+    }
+    preprocessor
+}
+
+pub fn basic_programs(gl: &WebGlRenderingContext) -> ProgramCache {
+    ProgramCache::new(gl, preprocessor(), BASIC_VERT, BASIC_FRAG).with_names("basic.vert", "basic.frag")
+}
+
+pub fn polyline_programs(gl: &WebGlRenderingContext) -> ProgramCache {
+    ProgramCache::new(gl, preprocessor(), POLYLINE_VERT, POLYLINE_FRAG).with_names("polyline.vert", "polyline.frag")
+}
+
+pub fn point_programs(gl: &WebGlRenderingContext) -> ProgramCache {
+    ProgramCache::new(gl, preprocessor(), POINT_VERT, POINT_FRAG).with_names("point.vert", "point.frag")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocess::Variant;
+    use crate::validate::validate_program;
+
+    #[test]
+    fn every_variant_preprocesses_and_validates() {
+        let pre = preprocessor();
+        for bits in 0..128 {
+            let variant = [
+                Variant::VERTEX_COLOR,
+                Variant::USE_MVP,
+                Variant::USE_TRANSFORM,
+                Variant::POINT_SIZE,
+                Variant::NORMAL_SHADING,
+                Variant::TEXTURE,
+                Variant::WIREFRAME,
+            ]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| bits & (1 << i) != 0)
+                .fold(Variant::NONE, |v, (_, flag)| v | *flag);
+            let vert = pre.process(BASIC_VERT, variant).unwrap();
+            let frag = pre.process(BASIC_FRAG, variant).unwrap();
+            assert!(vert.contains("vec4 transform(vec4 position)"));
+            assert!(!vert.contains("#include"));
+            assert_eq!(frag.contains("#define VERTEX_COLOR"), variant.contains(Variant::VERTEX_COLOR));
+            if let Err(err) = validate_program(&vert, &frag) {
+                panic!("{:?}\n{}", variant, err);
+            }
+        }
+    }
+
+    #[test]
+    fn polyline_validates() {
+        let pre = preprocessor();
+        for variant in [Variant::NONE, Variant::USE_MVP, Variant::USE_TRANSFORM] {
+            let vert = pre.process(POLYLINE_VERT, variant).unwrap();
+            let frag = pre.process(POLYLINE_FRAG, variant).unwrap();
+            if let Err(err) = validate_program(&vert, &frag) {
+                panic!("{:?}\n{}", variant, err);
+            }
+        }
+    }
+
+    #[test]
+    fn point_validates() {
+        let pre = preprocessor();
+        for variant in [Variant::NONE, Variant::USE_MVP, Variant::USE_TRANSFORM] {
+            let vert = pre.process(POINT_VERT, variant).unwrap();
+            let frag = pre.process(POINT_FRAG, variant).unwrap();
+            if let Err(err) = validate_program(&vert, &frag) {
+                panic!("{:?}\n{}", variant, err);
+            }
+        }
+    }
+
+    #[test]
+    fn flat_color_can_be_overridden() {
+        let mut pre = preprocessor();
+        pre.define("FLAT_COLOR", "vec4(1.0, 0.0, 0.0, 1.0)");
+        let frag = pre.process(BASIC_FRAG, Variant::NONE).unwrap();
+        validate_program(&pre.process(BASIC_VERT, Variant::NONE).unwrap(), &frag).unwrap();
+    }
+}
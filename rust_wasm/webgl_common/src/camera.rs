@@ -0,0 +1,259 @@
+use crate::math::{Mat4, Vec3};
+
+// Keeps the orbit away from the poles, where `up` and the view direction line up
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.001;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    // Field of view in degrees, like the old get_projection `angle`
+    pub fov: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+    // Height of the orthographic view volume. None keeps whatever the perspective view
+    // shows at the target distance, so switching modes doesn't jump.
+    pub ortho_size: Option<f32>,
+    projection: Projection,
+    // 0 = perspective, 1 = orthographic, in between while a transition runs
+    blend: f32,
+    // Blend change per millisecond of the current transition
+    blend_speed: f32,
+}
+
+impl Camera {
+    // Starts where the demos' hardcoded view matrix was: 6 units back on +Z, looking at the origin
+    pub fn new(fov: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Camera {
+            eye: Vec3::new(0.0, 0.0, 6.0),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            fov,
+            aspect,
+            near,
+            far,
+            ortho_size: None,
+            projection: Projection::Perspective,
+            blend: 0.0,
+            blend_speed: 0.0,
+        }
+    }
+
+    pub fn look_at(&mut self, eye: Vec3, target: Vec3, up: Vec3) {
+        self.eye = eye;
+        self.target = target;
+        self.up = up;
+    }
+
+    pub fn set_perspective(&mut self, fov: f32, near: f32, far: f32) {
+        self.fov = fov;
+        self.near = near;
+        self.far = far;
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_at(self.eye, self.target, self.up)
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        if self.blend <= 0.0 {
+            self.perspective_matrix()
+        } else if self.blend >= 1.0 {
+            self.orthographic_matrix()
+        } else {
+            // Smoothstep so the transition eases in and out
+            let t = self.blend * self.blend * (3.0 - 2.0 * self.blend);
+            self.perspective_matrix().lerp(&self.orthographic_matrix(), t)
+        }
+    }
+
+    pub fn perspective_matrix(&self) -> Mat4 {
+        Mat4::perspective(self.fov, self.aspect, self.near, self.far)
+    }
+
+    pub fn orthographic_matrix(&self) -> Mat4 {
+        let size = self.ortho_size.unwrap_or_else(|| self.matched_ortho_size());
+        Mat4::orthographic_size(size, self.aspect, self.near, self.far)
+    }
+
+    // Orthographic height that frames the target the same way the perspective projection does
+    fn matched_ortho_size(&self) -> f32 {
+        let distance = (self.target - self.eye).length();
+        2.0 * distance / self.perspective_matrix().get(1, 1)
+    }
+
+    /*==================== Projection mode ====================*/
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    // Switches projection, blending over `duration_ms` (0 switches immediately).
+    // The blend only moves forward when update() is called from the frame loop.
+    pub fn set_projection(&mut self, projection: Projection, duration_ms: f32) {
+        self.projection = projection;
+        let target = self.blend_target();
+        if duration_ms <= 0.0 {
+            self.blend = target;
+            self.blend_speed = 0.0;
+        } else {
+            self.blend_speed = 1.0 / duration_ms;
+        }
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.blend != self.blend_target()
+    }
+
+    pub fn update(&mut self, dt_ms: f32) {
+        let target = self.blend_target();
+        if self.blend == target {
+            return;
+        }
+        let step = self.blend_speed * dt_ms.max(0.0);
+        self.blend = if self.blend < target {
+            (self.blend + step).min(target)
+        } else {
+            (self.blend - step).max(target)
+        };
+    }
+
+    fn blend_target(&self) -> f32 {
+        match self.projection {
+            Projection::Perspective => 0.0,
+            Projection::Orthographic => 1.0,
+        }
+    }
+
+    /*==================== Orbit ====================*/
+    // Orbit mode places the eye on a sphere around `target`.
+    // yaw turns around the world Y axis (0 = on +Z), pitch lifts towards +Y, both in radians.
+    pub fn set_orbit(&mut self, yaw: f32, pitch: f32, distance: f32) {
+        let pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        let distance = distance.max(f32::EPSILON);
+        let (sy, cy) = yaw.sin_cos();
+        let (sp, cp) = pitch.sin_cos();
+
+        self.eye = self.target + Vec3::new(cp * sy, sp, cp * cy) * distance;
+        self.up = Vec3::Y;
+    }
+
+    // Current (yaw, pitch, distance) of the eye around the target
+    pub fn orbit(&self) -> (f32, f32, f32) {
+        let offset = self.eye - self.target;
+        let distance = offset.length();
+        if distance == 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+        let yaw = offset.x.atan2(offset.z);
+        let pitch = (offset.y / distance).clamp(-1.0, 1.0).asin();
+        (yaw, pitch, distance)
+    }
+
+    pub fn orbit_by(&mut self, d_yaw: f32, d_pitch: f32) {
+        let (yaw, pitch, distance) = self.orbit();
+        self.set_orbit(yaw + d_yaw, pitch + d_pitch, distance);
+    }
+
+    // factor < 1 moves closer, > 1 moves away
+    pub fn zoom_by(&mut self, factor: f32) {
+        let (yaw, pitch, distance) = self.orbit();
+        self.set_orbit(yaw, pitch, distance * factor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        for (i, (x, y)) in a.iter().zip(b.iter()).enumerate() {
+            assert!((x - y).abs() < 1e-5, "element {}: {} != {}\n{:?}\n{:?}", i, x, y, a, b);
+        }
+    }
+
+    #[test]
+    fn default_camera_matches_old_view_and_projection() {
+        let camera = Camera::new(40.0, 1.0, 1.0, 100.0);
+        assert_close(camera.view_matrix().as_slice(), Mat4::translation(Vec3::new(0.0, 0.0, -6.0)).as_slice());
+        assert_close(camera.projection_matrix().as_slice(), Mat4::perspective(40.0, 1.0, 1.0, 100.0).as_slice());
+    }
+
+    #[test]
+    fn orthographic_switch_keeps_target_framing() {
+        let mut camera = Camera::new(40.0, 1.0, 1.0, 100.0);
+        let edge = Vec3::new(0.0, 1.0, 0.0);
+
+        let clip = |camera: &Camera| (camera.projection_matrix() * camera.view_matrix()).transform_point3(edge);
+        let before = clip(&camera);
+
+        camera.set_projection(Projection::Orthographic, 0.0);
+        assert_eq!(camera.projection(), Projection::Orthographic);
+        assert_close(camera.projection_matrix().as_slice(), camera.orthographic_matrix().as_slice());
+        assert!((clip(&camera).y - before.y).abs() < 1e-5);
+
+        camera.ortho_size = Some(4.0);
+        assert_close(camera.projection_matrix().as_slice(), Mat4::orthographic_size(4.0, 1.0, 1.0, 100.0).as_slice());
+    }
+
+    #[test]
+    fn projection_blend_advances_with_update() {
+        let mut camera = Camera::new(40.0, 1.0, 1.0, 100.0);
+        camera.set_projection(Projection::Orthographic, 500.0);
+        assert!(camera.is_transitioning());
+        assert_close(camera.projection_matrix().as_slice(), camera.perspective_matrix().as_slice());
+
+        camera.update(250.0);
+        let halfway = camera.projection_matrix();
+        let expected = camera.perspective_matrix().lerp(&camera.orthographic_matrix(), 0.5);
+        assert_close(halfway.as_slice(), expected.as_slice());
+
+        camera.update(1000.0);
+        assert!(!camera.is_transitioning());
+        assert_close(camera.projection_matrix().as_slice(), camera.orthographic_matrix().as_slice());
+
+        // Switching back mid-way reverses from where it is
+        camera.set_projection(Projection::Perspective, 100.0);
+        camera.update(50.0);
+        assert!(camera.is_transitioning());
+        camera.update(50.0);
+        assert_close(camera.projection_matrix().as_slice(), camera.perspective_matrix().as_slice());
+    }
+
+    #[test]
+    fn orbit_round_trips() {
+        let mut camera = Camera::new(40.0, 1.0, 1.0, 100.0);
+        camera.target = Vec3::new(1.0, 2.0, 3.0);
+        camera.set_orbit(0.8, -0.3, 10.0);
+
+        let (yaw, pitch, distance) = camera.orbit();
+        assert_close(&[yaw, pitch, distance], &[0.8, -0.3, 10.0]);
+        assert!(((camera.eye - camera.target).length() - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orbit_keeps_target_centered() {
+        let mut camera = Camera::new(40.0, 1.0, 1.0, 100.0);
+        camera.orbit_by(1.0, 0.5);
+        camera.zoom_by(0.5);
+
+        let p = camera.view_matrix().transform_point3(camera.target);
+        assert_close(&p.to_array(), &[0.0, 0.0, -3.0]);
+    }
+
+    #[test]
+    fn pitch_is_clamped_at_the_poles() {
+        let mut camera = Camera::new(40.0, 1.0, 1.0, 100.0);
+        camera.set_orbit(0.0, 10.0, 5.0);
+        let (_, pitch, _) = camera.orbit();
+        assert!(pitch < std::f32::consts::FRAC_PI_2);
+        assert!(camera.view_matrix().as_slice().iter().all(|v| v.is_finite()));
+    }
+}
@@ -0,0 +1,299 @@
+// Curves turned into line strips. Every flatten_* appends the points after the curve's start, so
+// pieces can be chained onto what is already in `out`; `tolerance` is how far the strip may stray
+// from the true curve, in the units of the points.
+use std::f32::consts::{PI, TAU};
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::triangulate::Point;
+
+// Per curve piece, however small the tolerance
+pub const MAX_SEGMENTS: usize = 1024;
+
+// Subdivisions of a cubic, which gives at most MAX_SEGMENTS pieces
+const MAX_DEPTH: u32 = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CurveError {
+    UnknownKind(String),
+    // Not a count the kind can be chained from, e.g. 1 + 3n for cubics
+    PointCount { kind: String, count: usize },
+}
+
+impl fmt::Display for CurveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurveError::UnknownKind(kind) => write!(f, "unknown curve '{}'", kind),
+            CurveError::PointCount { kind, count } => write!(f, "{} points don't make a {} curve", count, kind),
+        }
+    }
+}
+
+impl From<CurveError> for JsValue {
+    fn from(err: CurveError) -> JsValue {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    (b[0] - a[0]).hypot(b[1] - a[1])
+}
+
+// From p to the nearest point of the segment a b
+fn distance_to_segment(p: Point, a: Point, b: Point) -> f32 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 > 0.0 { (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length2).clamp(0.0, 1.0) } else { 0.0 };
+    distance(p, lerp(a, b, t))
+}
+
+// A tolerance of 0 or less would never be met
+fn clamp_tolerance(tolerance: f32) -> f32 {
+    tolerance.max(1e-6)
+}
+
+// The second derivative is constant, so the number of even steps can be worked out up front
+pub fn flatten_quadratic(out: &mut Vec<Point>, p0: Point, p1: Point, p2: Point, tolerance: f32) {
+    let bend = (p0[0] - 2.0 * p1[0] + p2[0]).hypot(p0[1] - 2.0 * p1[1] + p2[1]);
+    let steps = (bend / (4.0 * clamp_tolerance(tolerance))).sqrt().ceil().clamp(1.0, MAX_SEGMENTS as f32) as usize;
+    for k in 1..=steps {
+        let t = k as f32 / steps as f32;
+        out.push(lerp(lerp(p0, p1, t), lerp(p1, p2, t), t));
+    }
+}
+
+// Halved until both inner control points are within the tolerance of the chord, which bounds the
+// whole piece since it stays inside its control points. The chord is the segment, not the line
+// through it, or control points in line but past the ends would pass as flat.
+pub fn flatten_cubic(out: &mut Vec<Point>, p0: Point, p1: Point, p2: Point, p3: Point, tolerance: f32) {
+    subdivide_cubic(out, [p0, p1, p2, p3], clamp_tolerance(tolerance), 0);
+}
+
+fn subdivide_cubic(out: &mut Vec<Point>, [p0, p1, p2, p3]: [Point; 4], tolerance: f32, depth: u32) {
+    if depth >= MAX_DEPTH || (distance_to_segment(p1, p0, p3) <= tolerance && distance_to_segment(p2, p0, p3) <= tolerance) {
+        out.push(p3);
+        return;
+    }
+    let (a, b, c) = (lerp(p0, p1, 0.5), lerp(p1, p2, 0.5), lerp(p2, p3, 0.5));
+    let (d, e) = (lerp(a, b, 0.5), lerp(b, c, 0.5));
+    let middle = lerp(d, e, 0.5);
+    subdivide_cubic(out, [p0, a, d, middle], tolerance, depth + 1);
+    subdivide_cubic(out, [middle, e, c, p3], tolerance, depth + 1);
+}
+
+// An elliptical arc, `start` and `sweep` in radians before the ellipse is turned by `rotation`;
+// a negative sweep goes clockwise. Steps are sized for the larger radius.
+pub fn flatten_arc(out: &mut Vec<Point>, center: Point, radii: [f32; 2], rotation: f32, start: f32, sweep: f32, tolerance: f32) {
+    let radius = radii[0].abs().max(radii[1].abs());
+    // The gap between a chord and its arc is r (1 - cos(step / 2))
+    let step = if radius > tolerance { 2.0 * (1.0 - clamp_tolerance(tolerance) / radius).acos() } else { PI };
+    let steps = (sweep.abs() / step).ceil().clamp(1.0, MAX_SEGMENTS as f32) as usize;
+    let (sin, cos) = rotation.sin_cos();
+    for k in 1..=steps {
+        let angle = start + sweep * k as f32 / steps as f32;
+        let (x, y) = (radii[0] * angle.cos(), radii[1] * angle.sin());
+        out.push([center[0] + x * cos - y * sin, center[1] + x * sin + y * cos]);
+    }
+}
+
+// The circle through `start`, `through` and `end`, from start to end by way of through. Three
+// points in a line are a straight segment.
+pub fn flatten_arc_through(out: &mut Vec<Point>, start: Point, through: Point, end: Point, tolerance: f32) {
+    let (a, b) = ([through[0] - start[0], through[1] - start[1]], [end[0] - start[0], end[1] - start[1]]);
+    let d = 2.0 * (a[0] * b[1] - a[1] * b[0]);
+    if d.abs() <= f32::EPSILON * (a[0].abs() + a[1].abs()) * (b[0].abs() + b[1].abs()) {
+        out.push(end);
+        return;
+    }
+    let (aa, bb) = (a[0] * a[0] + a[1] * a[1], b[0] * b[0] + b[1] * b[1]);
+    let center = [start[0] + (b[1] * aa - a[1] * bb) / d, start[1] + (a[0] * bb - b[0] * aa) / d];
+    let angle = |p: Point| (p[1] - center[1]).atan2(p[0] - center[0]);
+    let (from, via, to) = (angle(start), angle(through), angle(end));
+    let counter_clockwise = (to - from).rem_euclid(TAU);
+    // Clockwise when going counter-clockwise would miss `through`
+    let sweep = if (via - from).rem_euclid(TAU) <= counter_clockwise { counter_clockwise } else { counter_clockwise - TAU };
+    let radius = distance(center, start);
+    flatten_arc(out, center, [radius, radius], 0.0, from, sweep, tolerance);
+    // Land exactly on the end point rather than where the angles put it
+    if let Some(last) = out.last_mut() {
+        *last = end;
+    }
+}
+
+// Uniform Catmull-Rom through every point, as cubic Beziers. The ends of an open spline use
+// themselves as their missing neighbour.
+pub fn flatten_catmull_rom(out: &mut Vec<Point>, points: &[Point], closed: bool, tolerance: f32) {
+    let n = points.len();
+    if n < 2 {
+        return;
+    }
+    let at = |i: isize| {
+        if closed {
+            points[i.rem_euclid(n as isize) as usize]
+        } else {
+            points[i.clamp(0, n as isize - 1) as usize]
+        }
+    };
+    let spans = if closed { n } else { n - 1 };
+    for i in 0..spans as isize {
+        let (p0, p1, p2, p3) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+        let c1 = [p1[0] + (p2[0] - p0[0]) / 6.0, p1[1] + (p2[1] - p0[1]) / 6.0];
+        let c2 = [p2[0] - (p3[0] - p1[0]) / 6.0, p2[1] - (p3[1] - p1[1]) / 6.0];
+        flatten_cubic(out, p1, c1, c2, p2, tolerance);
+    }
+}
+
+// The strip for a chain of curves as JS passes it:
+//  "quadratic"         start, then control and end for each curve
+//  "cubic"             start, then two controls and end for each curve
+//  "arc"               start, then a point on the arc and its end for each arc
+//  "catmull-rom"       the points the spline goes through, 2 or more
+//  "catmull-rom-closed" the same, back round to the first point
+pub fn flatten_chain(kind: &str, points: &[Point], tolerance: f32) -> Result<Vec<Point>, CurveError> {
+    let per_curve = match kind {
+        "quadratic" | "arc" => 2,
+        "cubic" => 3,
+        "catmull-rom" | "catmull-rom-closed" => 1,
+        _ => return Err(CurveError::UnknownKind(kind.to_string())),
+    };
+    let count = points.len();
+    if count < 2 || !(count - 1).is_multiple_of(per_curve) {
+        return Err(CurveError::PointCount { kind: kind.to_string(), count });
+    }
+
+    let mut out = vec![points[0]];
+    match kind {
+        "catmull-rom" => flatten_catmull_rom(&mut out, points, false, tolerance),
+        "catmull-rom-closed" => flatten_catmull_rom(&mut out, points, true, tolerance),
+        _ => {
+            for curve in points[1..].chunks_exact(per_curve) {
+                let start = *out.last().unwrap();
+                match (kind, curve) {
+                    ("quadratic", &[control, end]) => flatten_quadratic(&mut out, start, control, end, tolerance),
+                    ("arc", &[through, end]) => flatten_arc_through(&mut out, start, through, end, tolerance),
+                    (_, &[c1, c2, end]) => flatten_cubic(&mut out, start, c1, c2, end, tolerance),
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // How far the exact curve gets from the strip, sampled densely
+    fn worst_gap(strip: &[Point], curve: impl Fn(f32) -> Point) -> f32 {
+        let to_segment = |p: Point, a: Point, b: Point| {
+            let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+            let length2 = dx * dx + dy * dy;
+            let t = if length2 > 0.0 { (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length2).clamp(0.0, 1.0) } else { 0.0 };
+            distance(p, lerp(a, b, t))
+        };
+        (0..=2000)
+            .map(|k| {
+                let p = curve(k as f32 / 2000.0);
+                strip.windows(2).map(|s| to_segment(p, s[0], s[1])).fold(f32::MAX, f32::min)
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn beziers_stay_within_tolerance() {
+        let (p0, p1, p2, p3) = ([0.0, 0.0], [1.0, 2.0], [3.0, -2.0], [4.0, 0.0]);
+        for tolerance in [0.1, 0.01, 0.001] {
+            let mut strip = vec![p0];
+            flatten_quadratic(&mut strip, p0, p1, p3, tolerance);
+            let quadratic = |t: f32| lerp(lerp(p0, p1, t), lerp(p1, p3, t), t);
+            assert!(worst_gap(&strip, quadratic) <= tolerance * 1.01);
+            assert_eq!(*strip.last().unwrap(), p3);
+
+            let mut strip = vec![p0];
+            flatten_cubic(&mut strip, p0, p1, p2, p3, tolerance);
+            let cubic = |t: f32| {
+                let (a, b, c) = (lerp(p0, p1, t), lerp(p1, p2, t), lerp(p2, p3, t));
+                lerp(lerp(a, b, t), lerp(b, c, t), t)
+            };
+            assert!(worst_gap(&strip, cubic) <= tolerance * 1.01);
+        }
+
+        // Finer tolerance, more points; a straight curve needs just the one segment
+        let count = |tolerance| {
+            let mut strip = vec![p0];
+            flatten_cubic(&mut strip, p0, p1, p2, p3, tolerance);
+            strip.len()
+        };
+        assert!(count(0.001) > count(0.1));
+        let mut strip = vec![p0];
+        flatten_cubic(&mut strip, p0, [1.0, 0.0], [2.0, 0.0], p3, 0.0);
+        assert_eq!(strip, vec![p0, p3]);
+
+        // In line, but the controls reach past the ends: out to x ≈ 2.9 and back behind 0
+        let (q0, q1, q2, q3) = ([0.0, 0.0], [10.0, 0.0], [-10.0, 0.0], [1.0, 0.0]);
+        let mut strip = vec![q0];
+        flatten_cubic(&mut strip, q0, q1, q2, q3, 0.01);
+        let overshoot = |t: f32| {
+            let (a, b, c) = (lerp(q0, q1, t), lerp(q1, q2, t), lerp(q2, q3, t));
+            lerp(lerp(a, b, t), lerp(b, c, t), t)
+        };
+        assert!(worst_gap(&strip, overshoot) <= 0.01 * 1.01);
+        assert!(strip.iter().any(|p| p[0] > 2.8) && strip.iter().any(|p| p[0] < -0.5));
+    }
+
+    #[test]
+    fn arcs() {
+        let mut strip = vec![[1.0, 0.0]];
+        flatten_arc(&mut strip, [0.0, 0.0], [1.0, 1.0], 0.0, 0.0, PI / 2.0, 0.01);
+        assert!(strip.iter().all(|p| (distance(*p, [0.0, 0.0]) - 1.0).abs() < 1e-5));
+        assert!(worst_gap(&strip, |t: f32| [(t * PI / 2.0).cos(), (t * PI / 2.0).sin()]) <= 0.01);
+        assert!(distance(*strip.last().unwrap(), [0.0, 1.0]) < 1e-6);
+
+        // Counter-clockwise over the top, or clockwise under it, depending on the middle point
+        let mut over = vec![[1.0, 0.0]];
+        flatten_arc_through(&mut over, [1.0, 0.0], [0.0, 1.0], [-1.0, 0.0], 0.01);
+        assert!(over.iter().all(|p| p[1] >= -1e-6));
+        let mut under = vec![[1.0, 0.0]];
+        flatten_arc_through(&mut under, [1.0, 0.0], [0.0, -1.0], [-1.0, 0.0], 0.01);
+        assert!(under.iter().all(|p| p[1] <= 1e-6));
+        assert_eq!(*under.last().unwrap(), [-1.0, 0.0]);
+
+        // Three points in a line
+        let mut line = vec![[0.0, 0.0]];
+        flatten_arc_through(&mut line, [0.0, 0.0], [1.0, 1.0], [2.0, 2.0], 0.01);
+        assert_eq!(line, vec![[0.0, 0.0], [2.0, 2.0]]);
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_its_points() {
+        let points = [[0.0, 0.0], [1.0, 1.0], [2.0, 0.0], [3.0, 1.0]];
+        let mut strip = vec![points[0]];
+        flatten_catmull_rom(&mut strip, &points, false, 0.01);
+        for p in points {
+            assert!(strip.contains(&p));
+        }
+        assert_eq!(*strip.last().unwrap(), points[3]);
+
+        let mut closed = vec![points[0]];
+        flatten_catmull_rom(&mut closed, &points, true, 0.01);
+        assert_eq!(*closed.last().unwrap(), points[0]);
+        assert!(closed.len() > strip.len());
+    }
+
+    #[test]
+    fn chains_from_js() {
+        let cubic = flatten_chain("cubic", &[[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [1.0, -1.0], [2.0, -1.0], [2.0, 0.0]], 0.01).unwrap();
+        assert_eq!((cubic[0], *cubic.last().unwrap()), ([0.0, 0.0], [2.0, 0.0]));
+        assert!(cubic.contains(&[1.0, 0.0]));
+
+        assert_eq!(flatten_chain("arc", &[[0.0, 0.0], [1.0, 1.0]], 0.1), Err(CurveError::PointCount { kind: "arc".to_string(), count: 2 }));
+        assert_eq!(flatten_chain("catmull-rom", &[[0.0, 0.0]], 0.1).unwrap_err().to_string(), "1 points don't make a catmull-rom curve");
+        assert_eq!(flatten_chain("bspline", &[], 0.1), Err(CurveError::UnknownKind("bspline".to_string())));
+    }
+}
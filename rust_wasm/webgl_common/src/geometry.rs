@@ -0,0 +1,180 @@
+use web_sys::WebGlRenderingContext;
+
+use crate::math::{Mat4, Vec3};
+use crate::mesh::Mesh;
+use crate::Vertex;
+
+// What a Geometry uploads per vertex
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[repr(C)]
+pub struct GeometryVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+// Indexed triangles with a normal and uv per vertex, built on the CPU before it goes into a Mesh
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Geometry {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<[f32; 2]>,
+    // Three per triangle, counter-clockwise seen from the front
+    pub indices: Vec<u32>,
+}
+
+impl Geometry {
+    pub fn new() -> Self {
+        Geometry::default()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    // Returns the index of the new vertex
+    pub fn push_vertex(&mut self, position: Vec3, normal: Vec3, uv: [f32; 2]) -> u32 {
+        let index = self.positions.len() as u32;
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.uvs.push(uv);
+        index
+    }
+
+    pub fn push_triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    // Smallest and largest corner of the box around the positions, None when there are none
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        let first = *self.positions.first()?;
+        Some(self.positions.iter().fold((first, first), |(min, max), p| {
+            (
+                Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            )
+        }))
+    }
+
+    // Replaces the normals with the area-weighted average of the faces around each vertex
+    pub fn compute_normals(&mut self) {
+        self.normals = vec![Vec3::ZERO; self.positions.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| self.positions[triangle[k] as usize]);
+            // Twice the area long
+            let face = (b - a).cross(c - a);
+            for &i in triangle {
+                self.normals[i as usize] += face;
+            }
+        }
+        for normal in &mut self.normals {
+            *normal = normal.normalize();
+        }
+    }
+
+    // Moves the positions by `matrix` and turns the normals with it
+    pub fn transform(&mut self, matrix: &Mat4) {
+        // Inverse transpose keeps normals perpendicular under non-uniform scale
+        let normal_matrix = matrix.inverse().map(|m| m.transpose()).unwrap_or(*matrix);
+        for position in &mut self.positions {
+            *position = matrix.transform_point3(*position);
+        }
+        for normal in &mut self.normals {
+            *normal = normal_matrix.transform_vector3(*normal).normalize();
+        }
+    }
+
+    // Adds the vertices and triangles of `other`
+    pub fn append(&mut self, other: &Geometry) {
+        let base = self.positions.len() as u32;
+        self.positions.extend_from_slice(&other.positions);
+        self.normals.extend_from_slice(&other.normals);
+        self.uvs.extend_from_slice(&other.uvs);
+        self.indices.extend(other.indices.iter().map(|i| base + i));
+    }
+
+    pub fn vertices(&self) -> Vec<GeometryVertex> {
+        self.positions
+            .iter()
+            .zip(&self.normals)
+            .zip(&self.uvs)
+            .map(|((position, normal), uv)| GeometryVertex {
+                position: position.to_array(),
+                normal: normal.to_array(),
+                uv: *uv,
+            })
+            .collect()
+    }
+
+    pub fn to_mesh(&self, gl: &WebGlRenderingContext) -> Mesh {
+        Mesh::from_vertices(gl, &self.vertices(), &self.indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{NORMAL, POSITION, UV};
+
+    fn triangle() -> Geometry {
+        let mut geometry = Geometry::new();
+        let a = geometry.push_vertex(Vec3::ZERO, Vec3::Z, [0.0, 0.0]);
+        let b = geometry.push_vertex(Vec3::X, Vec3::Z, [1.0, 0.0]);
+        let c = geometry.push_vertex(Vec3::Y, Vec3::Z, [0.0, 1.0]);
+        geometry.push_triangle(a, b, c);
+        geometry
+    }
+
+    #[test]
+    fn append_offsets_indices() {
+        let mut geometry = triangle();
+        geometry.append(&triangle());
+        assert_eq!(geometry.vertex_count(), 6);
+        assert_eq!(geometry.indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(geometry.triangle_count(), 2);
+        assert_eq!(geometry.bounds(), Some((Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0))));
+        assert_eq!(Geometry::new().bounds(), None);
+    }
+
+    #[test]
+    fn transform_turns_normals() {
+        let mut geometry = triangle();
+        geometry.transform(&(Mat4::translation(Vec3::new(0.0, 0.0, 2.0)) * Mat4::rotation_x(std::f32::consts::FRAC_PI_2)));
+        let close = |a: Vec3, b: Vec3| (a - b).length() < 1e-5;
+        assert!(close(geometry.positions[1], Vec3::new(1.0, 0.0, 2.0)));
+        assert!(close(geometry.positions[2], Vec3::new(0.0, 0.0, 3.0)));
+        // The face turned from +Z to -Y
+        assert!(close(geometry.normals[0], Vec3::new(0.0, -1.0, 0.0)));
+
+        geometry.transform(&Mat4::scale(Vec3::new(4.0, 1.0, 1.0)));
+        assert!((geometry.normals[0].length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn computed_normals_average_the_faces() {
+        // Two triangles folded along the X axis, one facing +Z and one facing +Y
+        let mut geometry = Geometry::new();
+        for p in [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::new(0.0, 0.0, -1.0)] {
+            geometry.push_vertex(p, Vec3::ZERO, [0.0, 0.0]);
+        }
+        geometry.indices = vec![0, 1, 2, 0, 1, 3];
+        geometry.compute_normals();
+        assert_eq!(geometry.normals[2], Vec3::Z);
+        assert_eq!(geometry.normals[3], Vec3::Y);
+        let shared = geometry.normals[0];
+        assert!((shared - Vec3::new(0.0, 1.0, 1.0).normalize()).length() < 1e-6);
+    }
+
+    #[test]
+    fn vertex_layout() {
+        let layout = GeometryVertex::layout();
+        let names: Vec<&str> = layout.attributes().iter().map(|a| a.name).collect();
+        assert_eq!(names, vec![POSITION, NORMAL, UV]);
+        assert_eq!(layout.stride(), 32);
+        assert_eq!(triangle().vertices()[2].uv, [0.0, 1.0]);
+    }
+}
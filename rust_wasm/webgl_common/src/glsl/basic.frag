@@ -0,0 +1,63 @@
+#ifdef WIREFRAME
+// fwidth keeps the overlay edges the same width in pixels; without it they scale with the triangle
+#extension GL_OES_standard_derivatives : enable
+#endif
+
+precision mediump float;
+
+// Color used without VERTEX_COLOR, can be overridden with a define
+#ifndef FLAT_COLOR
+#define FLAT_COLOR vec4(0.0, 0.0, 0.0, 0.1)
+#endif
+
+// Direction towards the light for NORMAL_SHADING, in world space
+#ifndef LIGHT_DIR
+#define LIGHT_DIR vec3(0.4, 0.8, 0.45)
+#endif
+
+#ifdef VERTEX_COLOR
+varying vec3 vColor;
+#endif
+
+#ifdef NORMAL_SHADING
+varying vec3 vNormal;
+#endif
+
+#ifdef TEXTURE
+uniform sampler2D u_texture;
+varying vec2 vUv;
+#endif
+
+#ifdef WIREFRAME
+// Overlay edge color, its alpha is how much it covers the fill, and width in pixels
+uniform vec4 u_wireColor;
+uniform float u_wireWidth;
+varying vec3 vBarycentric;
+#endif
+
+void main(void) {
+#ifdef VERTEX_COLOR
+    vec4 color = vec4(vColor, 1.0);
+#else
+    vec4 color = FLAT_COLOR;
+#endif
+#ifdef TEXTURE
+    color *= texture2D(u_texture, vUv);
+#endif
+#ifdef NORMAL_SHADING
+    // Ambient plus diffuse
+    float light = 0.3 + 0.7 * max(dot(normalize(vNormal), normalize(LIGHT_DIR)), 0.0);
+    color.rgb *= light;
+#endif
+#ifdef WIREFRAME
+    // A component near 0 means the fragment is near the edge opposite that corner
+#ifdef GL_OES_standard_derivatives
+    vec3 edge = smoothstep(vec3(0.0), fwidth(vBarycentric) * u_wireWidth, vBarycentric);
+#else
+    vec3 edge = smoothstep(vec3(0.0), vec3(0.01 * u_wireWidth), vBarycentric);
+#endif
+    float wire = (1.0 - min(min(edge.x, edge.y), edge.z)) * u_wireColor.a;
+    color.rgb = mix(color.rgb, u_wireColor.rgb, wire);
+#endif
+    gl_FragColor = color;
+}
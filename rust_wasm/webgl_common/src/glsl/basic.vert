@@ -0,0 +1,46 @@
+#include "transform"
+
+attribute vec3 position;
+
+#ifdef VERTEX_COLOR
+attribute vec3 color;
+varying vec3 vColor;
+#endif
+
+#ifdef NORMAL_SHADING
+attribute vec3 normal;
+varying vec3 vNormal;
+#endif
+
+#ifdef TEXTURE
+attribute vec2 uv;
+varying vec2 vUv;
+#endif
+
+#ifdef WIREFRAME
+attribute vec3 barycentric;
+varying vec3 vBarycentric;
+#endif
+
+#ifdef POINT_SIZE
+uniform float u_pointSize;
+#endif
+
+void main(void) {
+    gl_Position = transform(vec4(position, 1.0));
+#ifdef VERTEX_COLOR
+    vColor = color;
+#endif
+#ifdef NORMAL_SHADING
+    vNormal = transform_normal(normal);
+#endif
+#ifdef TEXTURE
+    vUv = uv;
+#endif
+#ifdef WIREFRAME
+    vBarycentric = barycentric;
+#endif
+#ifdef POINT_SIZE
+    gl_PointSize = u_pointSize;
+#endif
+}
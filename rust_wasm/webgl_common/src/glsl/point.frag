@@ -0,0 +1,24 @@
+precision mediump float;
+
+// PointShape: 0 square, 1 round, 2 soft
+uniform int u_shape;
+
+varying vec4 vColor;
+varying float vSize;
+
+void main(void) {
+    // Pixels from the middle of the sprite
+    float r = length(gl_PointCoord - 0.5) * vSize;
+    float coverage = 1.0;
+    if (u_shape == 1) {
+        // The last pixel of the disc fades out, which is the anti-aliasing
+        coverage = clamp(vSize * 0.5 - r + 0.5, 0.0, 1.0);
+    } else if (u_shape == 2) {
+        // Fades all the way from the middle to the edge
+        coverage = 1.0 - smoothstep(0.0, 1.0, 2.0 * r / vSize);
+    }
+    if (coverage <= 0.0) {
+        discard;
+    }
+    gl_FragColor = vec4(vColor.rgb, vColor.a * coverage);
+}
@@ -0,0 +1,22 @@
+#include "transform"
+
+// One sprite per point, with its own size in CSS pixels and color; see points.rs
+attribute vec3 position;
+attribute float size;
+attribute vec4 color;
+
+// devicePixelRatio, so sizes look the same on every screen
+uniform float u_pointScale;
+// ALIASED_POINT_SIZE_RANGE; sizes outside it would be clamped by the GL anyway, but the fragment
+// shader has to know the size it really got
+uniform vec2 u_sizeRange;
+
+varying vec4 vColor;
+varying float vSize;
+
+void main(void) {
+    gl_Position = transform(vec4(position, 1.0));
+    vSize = clamp(size * u_pointScale, u_sizeRange.x, u_sizeRange.y);
+    gl_PointSize = vSize;
+    vColor = color;
+}
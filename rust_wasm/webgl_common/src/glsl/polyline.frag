@@ -0,0 +1,22 @@
+precision mediump float;
+
+uniform vec4 u_color;
+
+varying vec4 vDist;
+varying vec4 vLimit;
+varying vec3 vRound;
+
+void main(void) {
+    // Distance inside the nearest edge, in pixels
+    vec4 inside = vLimit - vDist;
+    float d = min(min(inside.x, inside.y), min(inside.z, inside.w));
+    if (vRound.z > 0.0) {
+        d = min(d, vRound.z - length(vRound.xy));
+    }
+    // The last pixel fades out, which is the anti-aliasing
+    float coverage = clamp(d + 0.5, 0.0, 1.0);
+    if (coverage <= 0.0) {
+        discard;
+    }
+    gl_FragColor = vec4(u_color.rgb, u_color.a * coverage);
+}
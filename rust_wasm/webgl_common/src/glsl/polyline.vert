@@ -0,0 +1,108 @@
+// Lines of any width: every piece of the polyline is a quad whose corners are moved out from the
+// line here, in pixels, after the points are projected. See polyline.rs for the vertices.
+#include "transform"
+
+// Drawing buffer size in pixels
+uniform vec2 u_resolution;
+// Line width in pixels
+uniform float u_width;
+// Miter length over line width past which a miter join is drawn as a bevel
+uniform float u_miterLimit;
+// 0 miter, 1 bevel, 2 round
+uniform int u_join;
+// 0 butt, 1 square, 2 round
+uniform int u_cap;
+
+// The point the quad hangs off and its neighbours on the line (the point itself at the ends)
+attribute vec3 prev;
+attribute vec3 point;
+attribute vec3 next;
+// Piece (0 segment to `next`, 1 join wedge, 2 round disc), corner, corner, flags
+attribute vec4 corner;
+
+// Signed distances to up to four edges and how far inside each one may go, plus a circle for
+// round joins and caps (offset from its center, radius); all in pixels
+varying vec4 vDist;
+varying vec4 vLimit;
+varying vec3 vRound;
+
+// Anti-aliasing fringe outside the line, in pixels
+const float FRINGE = 1.0;
+// Stands for "no edge", small enough for mediump in the fragment shader
+const float FAR = 10000.0;
+
+vec2 to_screen(vec4 clip) {
+    return clip.xy / clip.w * u_resolution * 0.5;
+}
+
+vec2 safe_normalize(vec2 v) {
+    float len = length(v);
+    return len > 1e-6 ? v / len : vec2(0.0);
+}
+
+void main(void) {
+    vec4 clip = transform(vec4(point, 1.0));
+    vec2 p = to_screen(clip);
+    vec2 a = to_screen(transform(vec4(prev, 1.0)));
+    vec2 b = to_screen(transform(vec4(next, 1.0)));
+    float hw = u_width * 0.5;
+    int piece = int(corner.x + 0.5);
+
+    vec2 offset = vec2(0.0);
+    vDist = vec4(0.0);
+    vLimit = vec4(FAR);
+    vRound = vec3(0.0);
+
+    if (piece == 0) {
+        // From `point` to `next`; corner is (0 or 1 along, -1 or 1 across), flags 1 and 2 mark
+        // the start and the end of the whole line, which get caps
+        vec2 d = safe_normalize(b - p);
+        vec2 n = vec2(-d.y, d.x);
+        float len = length(b - p);
+        bool capStart = mod(corner.w, 2.0) > 0.5;
+        bool capEnd = corner.w > 1.5;
+        // Butt and square caps end in an edge; round caps and joins are covered by other pieces
+        bool edgeStart = capStart && u_cap != 2;
+        bool edgeEnd = capEnd && u_cap != 2;
+        float extStart = capStart && u_cap == 1 ? hw : 0.0;
+        float extEnd = capEnd && u_cap == 1 ? hw : 0.0;
+        float along = corner.y < 0.5
+            ? -(extStart + (edgeStart ? FRINGE : 0.0))
+            : len + extEnd + (edgeEnd ? FRINGE : 0.0);
+        offset = d * along + n * corner.z * (hw + FRINGE);
+        vDist = vec4(dot(offset, n), -dot(offset, n), -dot(offset, d), dot(offset, d) - len);
+        vLimit = vec4(hw, hw, edgeStart ? extStart : FAR, edgeEnd ? extEnd : FAR);
+    } else if (piece == 1) {
+        // Fills the outside of the bend at `point`; corner.y 0 is the point itself, 1 and 3 the
+        // ends of the two segments' outer edges and 2 the tip
+        vec2 d0 = safe_normalize(p - a);
+        vec2 d1 = safe_normalize(b - p);
+        float turn = d0.x * d1.y - d0.y * d1.x;
+        float outside = turn > 0.0 ? -1.0 : 1.0;
+        vec2 n0 = vec2(-d0.y, d0.x) * outside;
+        vec2 n1 = vec2(-d1.y, d1.x) * outside;
+        vec2 m = safe_normalize(n0 + n1);
+        float cos_half = max(dot(m, n0), 1e-4);
+        bool miter = u_join == 0 && 1.0 / cos_half <= u_miterLimit;
+        float tip = miter ? hw / cos_half : hw * cos_half;
+        if (u_join != 2) {
+            if (corner.y > 2.5) {
+                offset = n1 * (hw + FRINGE);
+            } else if (corner.y > 1.5) {
+                offset = m * (tip + FRINGE / cos_half);
+            } else if (corner.y > 0.5) {
+                offset = n0 * (hw + FRINGE);
+            }
+        }
+        vDist = vec4(dot(offset, n0), dot(offset, n1), dot(offset, m), 0.0);
+        vLimit = vec4(hw, hw, miter ? FAR : hw * cos_half, FAR);
+    } else {
+        // A disc at `point`, for round joins or, with flag 1, a round cap
+        bool on = corner.w > 0.5 ? u_cap == 2 : u_join == 2;
+        offset = on ? corner.yz * (hw + FRINGE) : vec2(0.0);
+        vRound = vec3(offset, hw);
+    }
+
+    // Pieces that are switched off collapse onto the point and draw nothing
+    gl_Position = vec4((p + offset) / (u_resolution * 0.5) * clip.w, clip.z, clip.w);
+}
@@ -0,0 +1,33 @@
+// Where the vertex ends up in clip space, depending on the variant
+#if defined(USE_MVP)
+uniform mat4 Pmatrix;
+uniform mat4 Vmatrix;
+uniform mat4 Mmatrix;
+
+vec4 transform(vec4 position) {
+    return Pmatrix * Vmatrix * Mmatrix * position;
+}
+
+// Into world space, assuming Mmatrix has no non-uniform scale
+vec3 transform_normal(vec3 normal) {
+    return (Mmatrix * vec4(normal, 0.0)).xyz;
+}
+#elif defined(USE_TRANSFORM)
+uniform mat4 u_xformMatrix;
+
+vec4 transform(vec4 position) {
+    return u_xformMatrix * position;
+}
+
+vec3 transform_normal(vec3 normal) {
+    return (u_xformMatrix * vec4(normal, 0.0)).xyz;
+}
+#else
+vec4 transform(vec4 position) {
+    return position;
+}
+
+vec3 transform_normal(vec3 normal) {
+    return normal;
+}
+#endif
@@ -0,0 +1,514 @@
+// glTF 2.0 from a .glb or a .gltf with its buffers and images embedded as data: URIs.
+// The `gltf` crate parses the JSON and reads the accessors; this module resolves the buffers and
+// images and turns the meshes, materials, cameras and node hierarchy into plain data to draw.
+use std::fmt;
+
+use base64::Engine;
+use gltf::camera::Projection as GltfProjection;
+use gltf::mesh::Mode;
+use wasm_bindgen::prelude::*;
+
+use crate::geometry::Geometry;
+use crate::math::{Mat4, Vec3};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GltfError {
+    // The JSON or the GLB container
+    Parse(String),
+    // Buffers and images have to be in the file
+    ExternalUri(String),
+    BadDataUri(String),
+    MissingPositions { mesh: usize },
+    // Accessors hold whatever the file says, nothing checks them against each other
+    IndexOutOfRange { mesh: usize, index: u32, vertices: usize },
+    AttributeCount { mesh: usize, attribute: &'static str, count: usize, vertices: usize },
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfError::Parse(message) => write!(f, "glTF parse error: {}", message),
+            GltfError::ExternalUri(uri) => write!(f, "'{}' isn't embedded, only .glb and data: URIs are supported", uri),
+            GltfError::BadDataUri(message) => write!(f, "bad data: URI: {}", message),
+            GltfError::MissingPositions { mesh } => write!(f, "mesh {} has a primitive without POSITION", mesh),
+            GltfError::IndexOutOfRange { mesh, index, vertices } => {
+                write!(f, "mesh {} has index {} but only {} vertices", mesh, index, vertices)
+            }
+            GltfError::AttributeCount { mesh, attribute, count, vertices } => {
+                write!(f, "mesh {} has {} {} values for {} vertices", mesh, count, attribute, vertices)
+            }
+        }
+    }
+}
+
+impl From<GltfError> for JsValue {
+    fn from(err: GltfError) -> JsValue {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    // Relative to the parent
+    pub local: Mat4,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfPrimitive {
+    pub geometry: Geometry,
+    pub material: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfMesh {
+    pub name: Option<String>,
+    // Only triangle primitives; points and lines are left out
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    // Linear RGBA
+    pub base_color: [f32; 4],
+    // Index into GltfScene::images, read with TEXCOORD_0
+    pub base_color_image: Option<usize>,
+}
+
+impl Default for GltfMaterial {
+    // What the spec draws primitives without a material with
+    fn default() -> Self {
+        GltfMaterial {
+            name: None,
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            base_color_image: None,
+        }
+    }
+}
+
+// Encoded image bytes, for the browser to decode
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GltfImage {
+    pub mime_type: Option<String>,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GltfCameraProjection {
+    // yfov in radians; no aspect ratio means the viewport's
+    Perspective { yfov: f32, aspect: Option<f32>, znear: f32, zfar: Option<f32> },
+    Orthographic { xmag: f32, ymag: f32, znear: f32, zfar: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfCamera {
+    pub name: Option<String>,
+    // Looks down its node's -Z with +Y up
+    pub projection: GltfCameraProjection,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GltfScene {
+    pub nodes: Vec<GltfNode>,
+    // Top nodes of the default scene, or of the first one
+    pub roots: Vec<usize>,
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+    pub images: Vec<GltfImage>,
+    pub cameras: Vec<GltfCamera>,
+}
+
+impl GltfScene {
+    // World matrix of every node, None for nodes the roots don't reach
+    pub fn world_matrices(&self) -> Vec<Option<Mat4>> {
+        let mut world = vec![None; self.nodes.len()];
+        let mut stack: Vec<(usize, Mat4)> = self.roots.iter().map(|&root| (root, Mat4::IDENTITY)).collect();
+        while let Some((index, parent)) = stack.pop() {
+            // A node listed twice would be a cycle, the spec doesn't allow it
+            if world[index].is_some() {
+                continue;
+            }
+            let node = &self.nodes[index];
+            let matrix = parent * node.local;
+            world[index] = Some(matrix);
+            stack.extend(node.children.iter().map(|&child| (child, matrix)));
+        }
+        world
+    }
+
+    // (world matrix, mesh index) for every node in the scene that has a mesh
+    pub fn mesh_instances(&self) -> Vec<(Mat4, usize)> {
+        self.world_matrices()
+            .into_iter()
+            .zip(&self.nodes)
+            .filter_map(|(world, node)| Some((world?, node.mesh?)))
+            .collect()
+    }
+
+    // (world matrix, camera index) for every node in the scene that has a camera
+    pub fn camera_instances(&self) -> Vec<(Mat4, usize)> {
+        self.world_matrices()
+            .into_iter()
+            .zip(&self.nodes)
+            .filter_map(|(world, node)| Some((world?, node.camera?)))
+            .collect()
+    }
+
+    // Every mesh instance with its node's transform applied, in world space
+    pub fn world_primitives(&self) -> Vec<GltfPrimitive> {
+        let mut out = Vec::new();
+        for (world, mesh) in self.mesh_instances() {
+            for primitive in &self.meshes[mesh].primitives {
+                let mut placed = primitive.clone();
+                placed.geometry.transform(&world);
+                out.push(placed);
+            }
+        }
+        out
+    }
+
+    pub fn material(&self, index: Option<usize>) -> GltfMaterial {
+        index.and_then(|i| self.materials.get(i)).cloned().unwrap_or_default()
+    }
+}
+
+pub fn parse_gltf(bytes: &[u8]) -> Result<GltfScene, GltfError> {
+    let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(bytes).map_err(|err| GltfError::Parse(err.to_string()))?;
+
+    let buffers = document
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => blob.clone().ok_or_else(|| GltfError::Parse("GLB without a BIN chunk".to_string())),
+            gltf::buffer::Source::Uri(uri) => decode_data_uri(uri).map(|(_, data)| data),
+        })
+        .collect::<Result<Vec<Vec<u8>>, GltfError>>()?;
+
+    let images = document
+        .images()
+        .map(|image| match image.source() {
+            gltf::image::Source::View { view, mime_type } => {
+                let start = view.offset();
+                let data = buffers[view.buffer().index()]
+                    .get(start..start + view.length())
+                    .ok_or_else(|| GltfError::Parse(format!("image {} runs past the end of its buffer", image.index())))?;
+                Ok(GltfImage {
+                    mime_type: Some(mime_type.to_string()),
+                    bytes: data.to_vec(),
+                })
+            }
+            gltf::image::Source::Uri { uri, mime_type } => {
+                let (uri_type, data) = decode_data_uri(uri)?;
+                Ok(GltfImage {
+                    mime_type: mime_type.map(str::to_string).or(uri_type),
+                    bytes: data,
+                })
+            }
+        })
+        .collect::<Result<Vec<GltfImage>, GltfError>>()?;
+
+    let meshes = document
+        .meshes()
+        .map(|mesh| {
+            let mut primitives = Vec::new();
+            for primitive in mesh.primitives() {
+                let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+                let positions: Vec<[f32; 3]> = reader
+                    .read_positions()
+                    .ok_or(GltfError::MissingPositions { mesh: mesh.index() })?
+                    .collect();
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+                if let Some(&index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+                    return Err(GltfError::IndexOutOfRange { mesh: mesh.index(), index, vertices: positions.len() });
+                }
+                let counted = |attribute: &'static str, count: usize| {
+                    if count == positions.len() {
+                        Ok(())
+                    } else {
+                        Err(GltfError::AttributeCount { mesh: mesh.index(), attribute, count, vertices: positions.len() })
+                    }
+                };
+                let Some(indices) = triangle_list(primitive.mode(), &indices) else {
+                    continue;
+                };
+
+                let mut geometry = Geometry::new();
+                geometry.positions = positions.iter().map(|&[x, y, z]| Vec3::new(x, y, z)).collect();
+                geometry.uvs = match reader.read_tex_coords(0) {
+                    Some(uvs) => uvs.into_f32().collect(),
+                    None => vec![[0.0, 0.0]; positions.len()],
+                };
+                counted("TEXCOORD_0", geometry.uvs.len())?;
+                geometry.indices = indices;
+                match reader.read_normals() {
+                    Some(normals) => {
+                        geometry.normals = normals.map(|[x, y, z]| Vec3::new(x, y, z)).collect();
+                        counted("NORMAL", geometry.normals.len())?;
+                    }
+                    None => geometry.compute_normals(),
+                }
+
+                primitives.push(GltfPrimitive {
+                    geometry,
+                    material: primitive.material().index(),
+                });
+            }
+            Ok(GltfMesh {
+                name: mesh.name().map(str::to_string),
+                primitives,
+            })
+        })
+        .collect::<Result<Vec<GltfMesh>, GltfError>>()?;
+
+    let materials = document
+        .materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            GltfMaterial {
+                name: material.name().map(str::to_string),
+                base_color: pbr.base_color_factor(),
+                base_color_image: pbr
+                    .base_color_texture()
+                    .filter(|info| info.tex_coord() == 0)
+                    .map(|info| info.texture().source().index()),
+            }
+        })
+        .collect();
+
+    let cameras = document
+        .cameras()
+        .map(|camera| GltfCamera {
+            name: camera.name().map(str::to_string),
+            projection: match camera.projection() {
+                GltfProjection::Perspective(p) => GltfCameraProjection::Perspective {
+                    yfov: p.yfov(),
+                    aspect: p.aspect_ratio(),
+                    znear: p.znear(),
+                    zfar: p.zfar(),
+                },
+                GltfProjection::Orthographic(o) => GltfCameraProjection::Orthographic {
+                    xmag: o.xmag(),
+                    ymag: o.ymag(),
+                    znear: o.znear(),
+                    zfar: o.zfar(),
+                },
+            },
+        })
+        .collect();
+
+    let nodes = document
+        .nodes()
+        .map(|node| {
+            // Column-major like Mat4
+            let cols = node.transform().matrix();
+            GltfNode {
+                name: node.name().map(str::to_string),
+                local: Mat4::from_cols_array(std::array::from_fn(|i| cols[i / 4][i % 4])),
+                children: node.children().map(|child| child.index()).collect(),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                camera: node.camera().map(|camera| camera.index()),
+            }
+        })
+        .collect();
+
+    let roots = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .map(|scene| scene.nodes().map(|node| node.index()).collect())
+        .unwrap_or_default();
+
+    Ok(GltfScene {
+        nodes,
+        roots,
+        meshes,
+        materials,
+        images,
+        cameras,
+    })
+}
+
+// Strips and fans unrolled into separate triangles, None for points and lines
+fn triangle_list(mode: Mode, indices: &[u32]) -> Option<Vec<u32>> {
+    match mode {
+        Mode::Triangles => Some(indices.to_vec()),
+        Mode::TriangleStrip => Some(
+            (0..indices.len().saturating_sub(2))
+                .flat_map(|i| {
+                    // Every other triangle is flipped to keep the winding
+                    if i % 2 == 0 {
+                        [indices[i], indices[i + 1], indices[i + 2]]
+                    } else {
+                        [indices[i + 1], indices[i], indices[i + 2]]
+                    }
+                })
+                .collect(),
+        ),
+        Mode::TriangleFan => Some(
+            (1..indices.len().saturating_sub(1))
+                .flat_map(|i| [indices[0], indices[i], indices[i + 1]])
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+// `data:[<mime type>][;base64],<data>`, returns the mime type and the bytes
+fn decode_data_uri(uri: &str) -> Result<(Option<String>, Vec<u8>), GltfError> {
+    let Some(rest) = uri.strip_prefix("data:") else {
+        return Err(GltfError::ExternalUri(uri.to_string()));
+    };
+    let (header, data) = rest
+        .split_once(',')
+        .ok_or_else(|| GltfError::BadDataUri("no ',' before the data".to_string()))?;
+    let Some(mime_type) = header.strip_suffix(";base64") else {
+        return Err(GltfError::BadDataUri("only base64 data is supported".to_string()));
+    };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|err| GltfError::BadDataUri(err.to_string()))?;
+    let mime_type = if mime_type.is_empty() { None } else { Some(mime_type.to_string()) };
+    Ok((mime_type, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE_GLB: &[u8] = include_bytes!("../fixtures/scene.glb");
+    const SCENE_GLTF: &[u8] = include_bytes!("../fixtures/scene.gltf");
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-5
+    }
+
+    // Both fixtures hold the same scene, one as .glb and one with data: URIs
+    fn check_scene(scene: &GltfScene) {
+        let names: Vec<Option<&str>> = scene.nodes.iter().map(|n| n.name.as_deref()).collect();
+        assert_eq!(names, vec![Some("root"), Some("quad"), Some("camera"), Some("strip"), Some("unused")]);
+        assert_eq!(scene.roots, vec![0]);
+        assert_eq!(scene.nodes[0].children, vec![1, 2, 3]);
+
+        // root moves everything up by 1, quad is scaled by 2
+        let world = scene.world_matrices();
+        assert!(close(world[1].unwrap().transform_point3(Vec3::new(0.5, 0.5, 0.0)), Vec3::new(1.0, 2.0, 0.0)));
+        assert!(world[4].is_none());
+
+        let instances = scene.mesh_instances();
+        assert_eq!(instances.iter().map(|(_, mesh)| *mesh).collect::<Vec<_>>(), vec![0, 1]);
+
+        let quad = &scene.meshes[0].primitives[0];
+        assert_eq!(quad.geometry.vertex_count(), 4);
+        assert_eq!(quad.geometry.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(quad.geometry.uvs[0], [0.0, 1.0]);
+        assert_eq!(quad.material, Some(0));
+
+        // A strip of 4 vertices is 2 triangles, and without NORMAL the normals come from the faces
+        let strip = &scene.meshes[1].primitives[0];
+        assert_eq!(strip.geometry.indices, vec![0, 1, 2, 2, 1, 3]);
+        assert!(strip.geometry.normals.iter().all(|n| *n == Vec3::Y));
+
+        let material = scene.material(quad.material);
+        assert_eq!(material.name.as_deref(), Some("tinted"));
+        assert_eq!(material.base_color, [1.0, 0.5, 0.25, 1.0]);
+        assert_eq!(material.base_color_image, Some(0));
+        assert_eq!(scene.material(strip.material).base_color, [1.0; 4]);
+
+        assert_eq!(scene.images.len(), 1);
+        assert_eq!(scene.images[0].mime_type.as_deref(), Some("image/png"));
+        assert!(scene.images[0].bytes.starts_with(b"\x89PNG"));
+
+        assert_eq!(
+            scene.cameras[0].projection,
+            GltfCameraProjection::Perspective { yfov: 0.8, aspect: None, znear: 0.1, zfar: Some(100.0) }
+        );
+        let (camera_world, camera) = scene.camera_instances()[0];
+        assert_eq!(camera, 0);
+        assert!(close(camera_world.transform_point3(Vec3::ZERO), Vec3::new(0.0, 1.0, 5.0)));
+    }
+
+    #[test]
+    fn glb() {
+        check_scene(&parse_gltf(SCENE_GLB).unwrap());
+    }
+
+    #[test]
+    fn embedded_gltf() {
+        check_scene(&parse_gltf(SCENE_GLTF).unwrap());
+    }
+
+    #[test]
+    fn world_primitives_are_placed() {
+        let scene = parse_gltf(SCENE_GLB).unwrap();
+        let placed = scene.world_primitives();
+        assert_eq!(placed.len(), 2);
+        let (min, max) = placed[0].geometry.bounds().unwrap();
+        assert!(close(min, Vec3::new(-1.0, 0.0, 0.0)) && close(max, Vec3::new(1.0, 2.0, 0.0)));
+        // The strip node turns a quarter around Y, +X goes to -Z
+        let (min, max) = placed[1].geometry.bounds().unwrap();
+        assert!(close(min, Vec3::new(-1.0, 1.0, -1.0)) && close(max, Vec3::new(0.0, 1.0, 0.0)));
+        assert!(placed[1].geometry.normals.iter().all(|n| close(*n, Vec3::Y)));
+    }
+
+    #[test]
+    fn triangle_lists() {
+        assert_eq!(triangle_list(Mode::TriangleFan, &[0, 1, 2, 3]), Some(vec![0, 1, 2, 0, 2, 3]));
+        assert_eq!(triangle_list(Mode::TriangleStrip, &[0, 1]), Some(vec![]));
+        assert_eq!(triangle_list(Mode::Lines, &[0, 1]), None);
+    }
+
+    // One triangle with its indices and `normals` normals
+    fn triangle(indices: [u16; 3], normals: usize) -> Vec<u8> {
+        let mut bin: Vec<u8> = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]].iter().flatten().flat_map(|v| v.to_le_bytes()).collect();
+        bin.extend((0..normals).flat_map(|_| [0.0f32, 0.0, 1.0]).flat_map(|v| v.to_le_bytes()));
+        bin.extend(indices.iter().flat_map(|i| i.to_le_bytes()));
+        let data = base64::engine::general_purpose::STANDARD.encode(&bin);
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "byteLength": {len}, "uri": "data:application/octet-stream;base64,{data}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": {normal_bytes} }},
+                    {{ "buffer": 0, "byteOffset": {index_offset}, "byteLength": 6 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": {normals}, "type": "VEC3" }},
+                    {{ "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+                ],
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }}, "indices": 2 }}] }}]
+            }}"#,
+            len = bin.len(),
+            normal_bytes = normals * 12,
+            index_offset = 36 + normals * 12,
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn accessors_have_to_agree() {
+        assert_eq!(parse_gltf(&triangle([0, 1, 2], 3)).unwrap().meshes[0].primitives[0].geometry.vertex_count(), 3);
+        assert_eq!(
+            parse_gltf(&triangle([0, 1, 7], 3)).map(|_| ()).unwrap_err(),
+            GltfError::IndexOutOfRange { mesh: 0, index: 7, vertices: 3 }
+        );
+        assert_eq!(
+            parse_gltf(&triangle([0, 1, 2], 2)).map(|_| ()).unwrap_err(),
+            GltfError::AttributeCount { mesh: 0, attribute: "NORMAL", count: 2, vertices: 3 }
+        );
+    }
+
+    #[test]
+    fn data_uris() {
+        assert_eq!(decode_data_uri("data:;base64,AAEC").unwrap(), (None, vec![0, 1, 2]));
+        assert_eq!(decode_data_uri("scene.bin").unwrap_err(), GltfError::ExternalUri("scene.bin".to_string()));
+        assert!(matches!(decode_data_uri("data:text/plain,hello"), Err(GltfError::BadDataUri(_))));
+        assert!(matches!(parse_gltf(b"{\"asset\": {}}"), Err(GltfError::Parse(_))));
+    }
+}
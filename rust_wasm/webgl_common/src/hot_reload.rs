@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::mesh::VertexLayout;
+use crate::preprocess::Variant;
+use crate::program::{ProgramCache, ProgramError, ShaderProgram};
+
+// The programs a running frame loop draws with, replaceable from outside the loop.
+// Every variant of the cache is rebuilt on reload, so the loop should get() its programs each frame.
+// Clones share the same cache, so the loop and a JS-facing handle can each hold one.
+#[derive(Clone)]
+pub struct HotProgram {
+    gl: WebGlRenderingContext,
+    programs: Rc<RefCell<ProgramCache>>,
+    // Programs that were swapped out, until the loop has rebound its attributes
+    replaced: Rc<RefCell<Vec<Rc<ShaderProgram>>>>,
+    // The vertex layouts each variant is drawn with, see draws()
+    layouts: Rc<RefCell<Vec<(Variant, VertexLayout)>>>,
+}
+
+impl HotProgram {
+    pub fn new(gl: &WebGlRenderingContext, programs: ProgramCache) -> Self {
+        HotProgram {
+            gl: gl.clone(),
+            programs: Rc::new(RefCell::new(programs)),
+            replaced: Rc::new(RefCell::new(Vec::new())),
+            layouts: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    // Tells reload() that meshes with `layout` are drawn with `variant`, so a program reading an
+    // attribute the layout doesn't have is turned down instead of failing in Mesh::bind
+    pub fn draws(&self, variant: Variant, layout: &VertexLayout) {
+        let mut layouts = self.layouts.borrow_mut();
+        if !layouts.iter().any(|(v, l)| *v == variant && l == layout) {
+            layouts.push((variant, layout.clone()));
+        }
+    }
+
+    // The current program for `variant`, built from the current sources the first time
+    pub fn get(&self, variant: Variant) -> Result<Rc<ShaderProgram>, ProgramError> {
+        self.programs.borrow_mut().get(variant)
+    }
+
+    // Builds new sources for every variant in use, with the same preprocessor.
+    // On any error the running programs stay in place and the error is returned.
+    // Attributes and uniforms the new programs share with the old ones have to keep their type, and
+    // every attribute they read has to be in the layouts given to draws(). Ones the compiler dropped
+    // are fine, the loop skips what a program doesn't use.
+    pub fn reload(&self, vert_code: &str, frag_code: &str) -> Result<(), ProgramError> {
+        let layouts = self.layouts.borrow();
+        let check = |variant: Variant, old: &ShaderProgram, new: &ShaderProgram| {
+            check_interface(old, new)?;
+            for name in new.attributes().keys() {
+                if layouts.iter().any(|(v, layout)| *v == variant && layout.get(name).is_none()) {
+                    return Err(ProgramError::MissingVertexAttribute(name.clone()));
+                }
+            }
+            Ok(())
+        };
+        let old = self.programs.borrow_mut().reload(vert_code, frag_code, check)?;
+        // Two reloads within one frame: the first replacements never got drawn with
+        let mut replaced = self.replaced.borrow_mut();
+        for skipped in replaced.drain(..) {
+            self.gl.delete_program(Some(skipped.program()));
+        }
+        *replaced = old;
+        Ok(())
+    }
+
+    // Called by the frame loop. Returns the programs that were swapped out since the last call,
+    // the loop should rebind its attributes for the new ones and then release() the old ones.
+    pub fn take_replaced(&self) -> Vec<Rc<ShaderProgram>> {
+        std::mem::take(&mut *self.replaced.borrow_mut())
+    }
+
+    pub fn release(&self, old: &ShaderProgram) {
+        for attribute in old.attributes().values() {
+            self.gl.disable_vertex_attrib_array(attribute.location);
+        }
+        self.gl.delete_program(Some(old.program()));
+    }
+}
+
+fn check_interface(old: &ShaderProgram, new: &ShaderProgram) -> Result<(), ProgramError> {
+    for (name, attribute) in old.attributes() {
+        match new.attributes().get(name) {
+            Some(found) if found.gl_type != attribute.gl_type => return Err(changed(name, attribute.gl_type, found.gl_type)),
+            _ => {}
+        }
+    }
+    for (name, uniform) in old.uniforms() {
+        match new.uniforms().get(name) {
+            Some(found) if found.gl_type != uniform.gl_type => return Err(changed(name, uniform.gl_type, found.gl_type)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn changed(name: &str, old: u32, new: u32) -> ProgramError {
+    ProgramError::InterfaceChanged {
+        name: name.to_string(),
+        old,
+        new,
+    }
+}
@@ -0,0 +1,56 @@
+// Code shared by the rust_wasm demo crates.
+// Each demo stays its own cdylib and pulls this in as a path dependency.
+
+// Lets #[derive(Vertex)], which names ::webgl_common, be used inside this crate too
+extern crate self as webgl_common;
+
+pub mod buffer;
+pub mod builtin;
+pub mod camera;
+pub mod curve;
+pub mod geometry;
+pub mod gltf_scene;
+pub mod hot_reload;
+pub mod math;
+pub mod mesh;
+pub mod mesh_tools;
+pub mod obj;
+pub mod octree;
+pub mod point_cloud;
+pub mod points;
+pub mod polyline;
+pub mod preprocess;
+pub mod primitives;
+pub mod program;
+pub mod quat;
+pub mod shader;
+pub mod surface;
+pub mod svg;
+pub mod texture;
+pub mod triangulate;
+pub mod wireframe;
+#[cfg(test)]
+mod validate;
+
+pub use buffer::{Buffer, BufferError, BufferTarget, Usage};
+pub use camera::{Camera, Projection};
+pub use curve::CurveError;
+pub use geometry::{Geometry, GeometryVertex};
+pub use gltf_scene::{GltfError, GltfScene};
+pub use hot_reload::HotProgram;
+pub use math::{Mat4, Vec3, Vec4};
+pub use mesh::{IndexFormat, Mesh, MeshIndex, Vertex, VertexAttribute, VertexLayout};
+pub use webgl_derive::Vertex;
+pub use obj::{ObjError, ObjMaterial, ObjMesh, ObjModel};
+pub use octree::{Octree, OctreeNode};
+pub use point_cloud::{PointCloud, PointCloudError};
+pub use points::{PointShape, PointSprites};
+pub use polyline::{Cap, Join, Polyline, PolylineStyle};
+pub use preprocess::{PreprocessError, Preprocessor, SourceMap, Variant};
+pub use program::{ProgramCache, ProgramError, ShaderProgram};
+pub use quat::Quat;
+pub use shader::{build_program, ShaderError, ShaderStage};
+pub use surface::Surface;
+pub use svg::{FillRule, SvgError, SvgPath};
+pub use texture::Texture;
+pub use wireframe::{RenderMode, WireframeMesh};
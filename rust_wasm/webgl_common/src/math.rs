@@ -0,0 +1,659 @@
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/*==================== Vec3 ====================*/
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    pub const ONE: Vec3 = Vec3 { x: 1.0, y: 1.0, z: 1.0 };
+    pub const X: Vec3 = Vec3 { x: 1.0, y: 0.0, z: 0.0 };
+    pub const Y: Vec3 = Vec3 { x: 0.0, y: 1.0, z: 0.0 };
+    pub const Z: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 1.0 };
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    pub fn dot(self, other: Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    // A zero vector stays zero instead of turning into NaN
+    pub fn normalize(self) -> Vec3 {
+        let len = self.length();
+        if len > 0.0 {
+            self * (1.0 / len)
+        } else {
+            self
+        }
+    }
+
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+    fn add(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, rhs: Vec3) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+    fn sub(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl SubAssign for Vec3 {
+    fn sub_assign(&mut self, rhs: Vec3) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Vec3;
+    fn mul(self, rhs: f32) -> Vec3 {
+        Vec3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/*==================== Vec4 ====================*/
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Vec4 {
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Vec4 { x, y, z, w }
+    }
+
+    pub fn dot(self, other: Vec4) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn truncate(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.x, self.y, self.z, self.w]
+    }
+}
+
+impl Add for Vec4 {
+    type Output = Vec4;
+    fn add(self, rhs: Vec4) -> Vec4 {
+        Vec4::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z, self.w + rhs.w)
+    }
+}
+
+impl Sub for Vec4 {
+    type Output = Vec4;
+    fn sub(self, rhs: Vec4) -> Vec4 {
+        Vec4::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z, self.w - rhs.w)
+    }
+}
+
+impl Mul<f32> for Vec4 {
+    type Output = Vec4;
+    fn mul(self, rhs: f32) -> Vec4 {
+        Vec4::new(self.x * rhs, self.y * rhs, self.z * rhs, self.w * rhs)
+    }
+}
+
+/*==================== Mat4 ====================*/
+// Column-major, same layout `uniform_matrix4fv_with_f32_array(.., false, ..)` expects:
+// element (row, col) lives at index col * 4 + row, so the translation is in [12], [13], [14].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    m: [f32; 16],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ],
+    };
+
+    pub const fn from_cols_array(m: [f32; 16]) -> Self {
+        Mat4 { m }
+    }
+
+    pub fn from_cols(c0: Vec4, c1: Vec4, c2: Vec4, c3: Vec4) -> Self {
+        let mut m = [0.0; 16];
+        m[0..4].copy_from_slice(&c0.to_array());
+        m[4..8].copy_from_slice(&c1.to_array());
+        m[8..12].copy_from_slice(&c2.to_array());
+        m[12..16].copy_from_slice(&c3.to_array());
+        Mat4 { m }
+    }
+
+    pub fn to_cols_array(&self) -> [f32; 16] {
+        self.m
+    }
+
+    // For handing straight to uniform_matrix4fv_with_f32_array
+    pub fn as_slice(&self) -> &[f32] {
+        &self.m
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f32 {
+        self.m[col * 4 + row]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: f32) {
+        self.m[col * 4 + row] = value;
+    }
+
+    pub fn col(&self, col: usize) -> Vec4 {
+        let c = &self.m[col * 4..col * 4 + 4];
+        Vec4::new(c[0], c[1], c[2], c[3])
+    }
+
+    pub fn row(&self, row: usize) -> Vec4 {
+        Vec4::new(self.m[row], self.m[4 + row], self.m[8 + row], self.m[12 + row])
+    }
+
+    /*==================== Constructors ====================*/
+    pub fn translation(t: Vec3) -> Self {
+        Mat4::from_cols_array([
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            t.x, t.y, t.z, 1.0
+        ])
+    }
+
+    pub fn scale(s: Vec3) -> Self {
+        Mat4::from_cols_array([
+            s.x, 0.0, 0.0, 0.0,
+            0.0, s.y, 0.0, 0.0,
+            0.0, 0.0, s.z, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    pub fn rotation_x(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Mat4::from_cols_array([
+            1.0, 0.0, 0.0, 0.0,
+            0.0, c, s, 0.0,
+            0.0, -s, c, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    pub fn rotation_y(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Mat4::from_cols_array([
+            c, 0.0, -s, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            s, 0.0, c, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    pub fn rotation_z(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Mat4::from_cols_array([
+            c, s, 0.0, 0.0,
+            -s, c, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    // Right-handed rotation of `angle` radians around `axis` (normalized here)
+    pub fn rotation_axis(axis: Vec3, angle: f32) -> Self {
+        let a = axis.normalize();
+        let (s, c) = angle.sin_cos();
+        let t = 1.0 - c;
+
+        Mat4::from_cols_array([
+            t * a.x * a.x + c, t * a.x * a.y + s * a.z, t * a.x * a.z - s * a.y, 0.0,
+            t * a.x * a.y - s * a.z, t * a.y * a.y + c, t * a.y * a.z + s * a.x, 0.0,
+            t * a.x * a.z + s * a.y, t * a.y * a.z - s * a.x, t * a.z * a.z + c, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    // Same matrix the demos' get_projection produced: `angle` is the field of view in degrees,
+    // `a` the canvas aspect ratio.
+    pub fn perspective(angle: f32, a: f32, z_min: f32, z_max: f32) -> Self {
+        let ang = (angle * 0.5).to_radians().tan();
+
+        Mat4::from_cols_array([
+            0.5/ang, 0.0, 0.0, 0.0,
+            0.0, 0.5*a/ang, 0.0, 0.0,
+            0.0, 0.0, -(z_max + z_min)/(z_max - z_min), -1.0,
+            0.0, 0.0, (-2.0*z_max*z_min)/(z_max-z_min), 0.0
+        ])
+    }
+
+    // Maps the box left..right, bottom..top, -z_min..-z_max to clip space
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, z_min: f32, z_max: f32) -> Self {
+        Mat4::from_cols_array([
+            2.0/(right-left), 0.0, 0.0, 0.0,
+            0.0, 2.0/(top-bottom), 0.0, 0.0,
+            0.0, 0.0, -2.0/(z_max-z_min), 0.0,
+            -(right+left)/(right-left), -(top+bottom)/(top-bottom), -(z_max+z_min)/(z_max-z_min), 1.0
+        ])
+    }
+
+    // Centered orthographic box `size` units tall. `a` is used the same way as in perspective(),
+    // so switching between the two keeps the same horizontal/vertical proportions.
+    pub fn orthographic_size(size: f32, a: f32, z_min: f32, z_max: f32) -> Self {
+        let half_height = size * 0.5;
+        let half_width = half_height * a;
+        Mat4::orthographic(-half_width, half_width, -half_height, half_height, z_min, z_max)
+    }
+
+    // Element-wise blend, t = 0 gives self and t = 1 gives other
+    pub fn lerp(&self, other: &Mat4, t: f32) -> Mat4 {
+        let mut out = [0.0; 16];
+        for (i, v) in out.iter_mut().enumerate() {
+            *v = self.m[i] + (other.m[i] - self.m[i]) * t;
+        }
+        Mat4 { m: out }
+    }
+
+    // Right-handed view matrix for a camera at `eye` looking towards `target`
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let f = (target - eye).normalize();
+        let s = f.cross(up).normalize();
+        let u = s.cross(f);
+
+        Mat4::from_cols_array([
+            s.x, u.x, -f.x, 0.0,
+            s.y, u.y, -f.y, 0.0,
+            s.z, u.z, -f.z, 0.0,
+            -s.dot(eye), -u.dot(eye), f.dot(eye), 1.0
+        ])
+    }
+
+    /*==================== Rotation ====================*/
+    // Incremental rotations used by the animation loops. Each one is `R * self`,
+    // which is what the old rotate_x/y/z helpers computed in place.
+    pub fn rotate_x(&mut self, angle: f32) {
+        *self = Mat4::rotation_x(angle) * *self;
+    }
+
+    pub fn rotate_y(&mut self, angle: f32) {
+        *self = Mat4::rotation_y(angle) * *self;
+    }
+
+    pub fn rotate_z(&mut self, angle: f32) {
+        *self = Mat4::rotation_z(angle) * *self;
+    }
+
+    /*==================== Operations ====================*/
+    pub fn transpose(&self) -> Mat4 {
+        let mut out = [0.0; 16];
+        for row in 0..4 {
+            for col in 0..4 {
+                out[row * 4 + col] = self.m[col * 4 + row];
+            }
+        }
+        Mat4 { m: out }
+    }
+
+    pub fn determinant(&self) -> f32 {
+        determinant_from_terms(&self.cofactor_terms())
+    }
+
+    // None when the matrix is singular
+    pub fn inverse(&self) -> Option<Mat4> {
+        let a = &self.m;
+        let c = self.cofactor_terms();
+        let det = determinant_from_terms(&c);
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        Some(Mat4::from_cols_array([
+            (a[5] * c[11] - a[6] * c[10] + a[7] * c[9]) * inv_det,
+            (a[2] * c[10] - a[1] * c[11] - a[3] * c[9]) * inv_det,
+            (a[13] * c[5] - a[14] * c[4] + a[15] * c[3]) * inv_det,
+            (a[10] * c[4] - a[9] * c[5] - a[11] * c[3]) * inv_det,
+            (a[6] * c[8] - a[4] * c[11] - a[7] * c[7]) * inv_det,
+            (a[0] * c[11] - a[2] * c[8] + a[3] * c[7]) * inv_det,
+            (a[14] * c[2] - a[12] * c[5] - a[15] * c[1]) * inv_det,
+            (a[8] * c[5] - a[10] * c[2] + a[11] * c[1]) * inv_det,
+            (a[4] * c[10] - a[5] * c[8] + a[7] * c[6]) * inv_det,
+            (a[1] * c[8] - a[0] * c[10] - a[3] * c[6]) * inv_det,
+            (a[12] * c[4] - a[13] * c[2] + a[15] * c[0]) * inv_det,
+            (a[9] * c[2] - a[8] * c[4] - a[11] * c[0]) * inv_det,
+            (a[5] * c[7] - a[4] * c[9] - a[6] * c[6]) * inv_det,
+            (a[0] * c[9] - a[1] * c[7] + a[2] * c[6]) * inv_det,
+            (a[13] * c[1] - a[12] * c[3] - a[14] * c[0]) * inv_det,
+            (a[8] * c[3] - a[9] * c[1] + a[10] * c[0]) * inv_det,
+        ]))
+    }
+
+    // 2x2 sub-determinants shared by determinant() and inverse()
+    fn cofactor_terms(&self) -> [f32; 12] {
+        let a = &self.m;
+        [
+            a[0] * a[5] - a[1] * a[4],
+            a[0] * a[6] - a[2] * a[4],
+            a[0] * a[7] - a[3] * a[4],
+            a[1] * a[6] - a[2] * a[5],
+            a[1] * a[7] - a[3] * a[5],
+            a[2] * a[7] - a[3] * a[6],
+            a[8] * a[13] - a[9] * a[12],
+            a[8] * a[14] - a[10] * a[12],
+            a[8] * a[15] - a[11] * a[12],
+            a[9] * a[14] - a[10] * a[13],
+            a[9] * a[15] - a[11] * a[13],
+            a[10] * a[15] - a[11] * a[14],
+        ]
+    }
+
+    pub fn transform_point3(&self, p: Vec3) -> Vec3 {
+        let v = *self * p.extend(1.0);
+        if v.w != 0.0 && v.w != 1.0 {
+            v.truncate() * (1.0 / v.w)
+        } else {
+            v.truncate()
+        }
+    }
+
+    pub fn transform_vector3(&self, v: Vec3) -> Vec3 {
+        (*self * v.extend(0.0)).truncate()
+    }
+}
+
+fn determinant_from_terms(c: &[f32; 12]) -> f32 {
+    c[0] * c[11] - c[1] * c[10] + c[2] * c[9] + c[3] * c[8] - c[4] * c[7] + c[5] * c[6]
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut out = [0.0; 16];
+        for col in 0..4 {
+            for row in 0..4 {
+                out[col * 4 + row] = self.row(row).dot(rhs.col(col));
+            }
+        }
+        Mat4 { m: out }
+    }
+}
+
+impl MulAssign for Mat4 {
+    fn mul_assign(&mut self, rhs: Mat4) {
+        *self = *self * rhs;
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+    fn mul(self, v: Vec4) -> Vec4 {
+        Vec4::new(self.row(0).dot(v), self.row(1).dot(v), self.row(2).dot(v), self.row(3).dot(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The helpers cube_rotate/triangle_rotate shipped with, kept verbatim as the reference
+    fn legacy_rotate_z(matrix: &mut [f32; 16], angle: f32) {
+        let cos_angle = angle.cos();
+        let sin_angle = angle.sin();
+        let mv0 = matrix[0];
+        let mv4 = matrix[4];
+        let mv8 = matrix[8];
+
+        matrix[0] = cos_angle*matrix[0] - sin_angle*matrix[1];
+        matrix[4] = cos_angle*matrix[4] - sin_angle*matrix[5];
+        matrix[8] = cos_angle*matrix[8] - sin_angle*matrix[9];
+        matrix[1] = cos_angle*matrix[1] + sin_angle*mv0;
+        matrix[5] = cos_angle*matrix[5] + sin_angle*mv4;
+        matrix[9] = cos_angle*matrix[9] + sin_angle*mv8;
+    }
+
+    fn legacy_rotate_x(matrix: &mut [f32; 16], angle: f32) {
+        let cos_angle = angle.cos();
+        let sin_angle = angle.sin();
+        let mv1 = matrix[1];
+        let mv5 = matrix[5];
+        let mv9 = matrix[9];
+
+        matrix[1] = matrix[1]*cos_angle-matrix[2]*sin_angle;
+        matrix[5] = matrix[5]*cos_angle-matrix[6]*sin_angle;
+        matrix[9] = matrix[9]*cos_angle-matrix[10]*sin_angle;
+
+        matrix[2] = matrix[2]*cos_angle+mv1*sin_angle;
+        matrix[6] = matrix[6]*cos_angle+mv5*sin_angle;
+        matrix[10] = matrix[10]*cos_angle+mv9*sin_angle;
+    }
+
+    fn legacy_rotate_y(matrix: &mut [f32; 16], angle: f32) {
+        let cos_angle = angle.cos();
+        let sin_angle = angle.sin();
+        let mv0 = matrix[0];
+        let mv4 = matrix[4];
+        let mv8 = matrix[8];
+
+        matrix[0] = cos_angle*matrix[0]+sin_angle*matrix[2];
+        matrix[4] = cos_angle*matrix[4]+sin_angle*matrix[6];
+        matrix[8] = cos_angle*matrix[8]+sin_angle*matrix[10];
+
+        matrix[2] = cos_angle*matrix[2]-sin_angle*mv0;
+        matrix[6] = cos_angle*matrix[6]-sin_angle*mv4;
+        matrix[10] = cos_angle*matrix[10]-sin_angle*mv8;
+    }
+
+    fn legacy_get_projection(angle: f32, a: f32, z_min: f32, z_max: f32) -> [f32; 16] {
+        let ang = (angle * 0.5).to_radians().tan();
+
+        [
+            0.5/ang, 0.0, 0.0, 0.0,
+            0.0, 0.5*a/ang, 0.0, 0.0,
+            0.0, 0.0, -(z_max + z_min)/(z_max - z_min), -1.0,
+            0.0, 0.0, (-2.0*z_max*z_min)/(z_max-z_min), 0.0
+        ]
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (i, (x, y)) in a.iter().zip(b.iter()).enumerate() {
+            assert!((x - y).abs() < 1e-5, "element {}: {} != {}\n{:?}\n{:?}", i, x, y, a, b);
+        }
+    }
+
+    #[test]
+    fn rotate_matches_legacy_helpers() {
+        let mut legacy = Mat4::IDENTITY.to_cols_array();
+        let mut mat = Mat4::IDENTITY;
+
+        // Same per-frame sequence as cube_rotate, with a range of frame deltas
+        for frame in 0..200 {
+            let dt = 16.0 + (frame % 7) as f32;
+            legacy_rotate_z(&mut legacy, dt * 0.005);
+            legacy_rotate_y(&mut legacy, dt * 0.002);
+            legacy_rotate_x(&mut legacy, dt * 0.003);
+
+            mat.rotate_z(dt * 0.005);
+            mat.rotate_y(dt * 0.002);
+            mat.rotate_x(dt * 0.003);
+
+            assert_close(mat.as_slice(), &legacy);
+        }
+    }
+
+    #[test]
+    fn single_axis_rotations_match_legacy() {
+        for &angle in &[0.0, 0.3, -1.2, std::f32::consts::PI] {
+            let mut legacy = Mat4::IDENTITY.to_cols_array();
+            legacy_rotate_x(&mut legacy, angle);
+            assert_close(Mat4::rotation_x(angle).as_slice(), &legacy);
+
+            let mut legacy = Mat4::IDENTITY.to_cols_array();
+            legacy_rotate_y(&mut legacy, angle);
+            assert_close(Mat4::rotation_y(angle).as_slice(), &legacy);
+
+            let mut legacy = Mat4::IDENTITY.to_cols_array();
+            legacy_rotate_z(&mut legacy, angle);
+            assert_close(Mat4::rotation_z(angle).as_slice(), &legacy);
+        }
+    }
+
+    #[test]
+    fn perspective_matches_get_projection() {
+        let expected = legacy_get_projection(40.0, 640.0 / 480.0, 1.0, 100.0);
+        assert_close(Mat4::perspective(40.0, 640.0 / 480.0, 1.0, 100.0).as_slice(), &expected);
+    }
+
+    #[test]
+    fn orthographic_maps_box_to_clip_space() {
+        let m = Mat4::orthographic(-2.0, 4.0, -1.0, 3.0, 1.0, 11.0);
+        assert_close(&m.transform_point3(Vec3::new(-2.0, -1.0, -1.0)).to_array(), &[-1.0, -1.0, -1.0]);
+        assert_close(&m.transform_point3(Vec3::new(4.0, 3.0, -11.0)).to_array(), &[1.0, 1.0, 1.0]);
+        assert_close(&m.transform_point3(Vec3::new(1.0, 1.0, -6.0)).to_array(), &[0.0, 0.0, 0.0]);
+
+        let sized = Mat4::orthographic_size(4.0, 1.5, 1.0, 100.0);
+        assert_close(sized.as_slice(), Mat4::orthographic(-3.0, 3.0, -2.0, 2.0, 1.0, 100.0).as_slice());
+    }
+
+    #[test]
+    fn rotation_axis_matches_principal_axes() {
+        assert_close(Mat4::rotation_axis(Vec3::X, 0.7).as_slice(), Mat4::rotation_x(0.7).as_slice());
+        assert_close(Mat4::rotation_axis(Vec3::Y, 0.7).as_slice(), Mat4::rotation_y(0.7).as_slice());
+        assert_close(Mat4::rotation_axis(Vec3::Z * 3.0, 0.7).as_slice(), Mat4::rotation_z(0.7).as_slice());
+    }
+
+    #[test]
+    fn translation_and_scale_transform_points() {
+        let t = Mat4::translation(Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(t.transform_point3(Vec3::ZERO), Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(t.transform_vector3(Vec3::X), Vec3::X);
+
+        let s = Mat4::scale(Vec3::new(1.0, 1.5, 1.0));
+        assert_eq!(s.transform_point3(Vec3::new(-0.5, 0.5, 0.0)), Vec3::new(-0.5, 0.75, 0.0));
+
+        // Scale first, then translate
+        let m = t * s;
+        assert_eq!(m.transform_point3(Vec3::new(0.0, 2.0, 0.0)), Vec3::new(1.0, 5.0, 3.0));
+    }
+
+    #[test]
+    fn look_at_matches_the_old_zoom_translation() {
+        // The demos used identity with view_matrix[14] -= 6.0
+        let view = Mat4::look_at(Vec3::new(0.0, 0.0, 6.0), Vec3::ZERO, Vec3::Y);
+        assert_close(view.as_slice(), Mat4::translation(Vec3::new(0.0, 0.0, -6.0)).as_slice());
+
+        // The target ends up straight ahead, on the -Z axis
+        let eye = Vec3::new(3.0, 2.0, -4.0);
+        let target = Vec3::new(-1.0, 0.5, 2.0);
+        let view = Mat4::look_at(eye, target, Vec3::Y);
+        let p = view.transform_point3(target);
+        assert_close(&p.to_array(), &[0.0, 0.0, -(target - eye).length()]);
+        assert_close(&view.transform_point3(eye).to_array(), &[0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn transpose_swaps_rows_and_cols() {
+        let m = Mat4::from_cols_array([
+            1.0, 2.0, 3.0, 4.0,
+            5.0, 6.0, 7.0, 8.0,
+            9.0, 10.0, 11.0, 12.0,
+            13.0, 14.0, 15.0, 16.0
+        ]);
+        let t = m.transpose();
+        for row in 0..4 {
+            for col in 0..4 {
+                assert_eq!(t.get(row, col), m.get(col, row));
+            }
+        }
+        assert_eq!(t.transpose(), m);
+    }
+
+    #[test]
+    fn determinant_of_known_matrices() {
+        assert_eq!(Mat4::IDENTITY.determinant(), 1.0);
+        assert!((Mat4::scale(Vec3::new(2.0, 3.0, 4.0)).determinant() - 24.0).abs() < 1e-5);
+        assert!((Mat4::rotation_axis(Vec3::new(1.0, 1.0, 0.0), 1.1).determinant() - 1.0).abs() < 1e-5);
+
+        let singular = Mat4::scale(Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(singular.determinant(), 0.0);
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn inverse_round_trips() {
+        let m = Mat4::translation(Vec3::new(1.0, -2.0, 6.0))
+            * Mat4::rotation_axis(Vec3::new(0.3, 1.0, -0.5), 0.8)
+            * Mat4::scale(Vec3::new(2.0, 0.5, 1.5));
+        let inv = m.inverse().unwrap();
+
+        assert_close((m * inv).as_slice(), Mat4::IDENTITY.as_slice());
+        assert_close((inv * m).as_slice(), Mat4::IDENTITY.as_slice());
+
+        let p = Vec3::new(0.25, 4.0, -3.0);
+        let back = inv.transform_point3(m.transform_point3(p));
+        assert_close(&back.to_array(), &p.to_array());
+    }
+
+    #[test]
+    fn vector_products() {
+        assert_eq!(Vec3::X.cross(Vec3::Y), Vec3::Z);
+        assert_eq!(Vec3::Y.cross(Vec3::Z), Vec3::X);
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0).dot(Vec3::new(4.0, -5.0, 6.0)), 12.0);
+        assert_eq!(Vec3::new(3.0, 0.0, 4.0).length(), 5.0);
+        assert_eq!(Vec3::ZERO.normalize(), Vec3::ZERO);
+    }
+}
@@ -0,0 +1,623 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::buffer::{Buffer, BufferError, BufferTarget, Usage};
+use crate::math::{Vec3, Vec4};
+use crate::program::{ProgramError, ShaderProgram};
+
+type Gl = WebGlRenderingContext;
+
+// Attribute names the built-in shaders use
+pub const POSITION: &str = "position";
+pub const COLOR: &str = "color";
+pub const NORMAL: &str = "normal";
+pub const UV: &str = "uv";
+pub const BARYCENTRIC: &str = "barycentric";
+
+// One attribute inside an interleaved vertex, matched to shader attributes by name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub name: &'static str,
+    pub components: i32,
+    // FLOAT, UNSIGNED_BYTE, ...
+    pub gl_type: u32,
+    pub normalized: bool,
+    // Bytes from the start of the vertex
+    pub offset: i32,
+}
+
+impl VertexAttribute {
+    pub fn size_in_bytes(&self) -> i32 {
+        self.components * gl_type_size(self.gl_type)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+    stride: i32,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        VertexLayout::default()
+    }
+
+    // Appends a float attribute right after the previous one and grows the stride to fit
+    pub fn float(self, name: &'static str, components: i32) -> Self {
+        let offset = self.stride;
+        self.attribute(VertexAttribute {
+            name,
+            components,
+            gl_type: Gl::FLOAT,
+            normalized: false,
+            offset,
+        })
+    }
+
+    pub fn position(self, components: i32) -> Self {
+        self.float(POSITION, components)
+    }
+
+    pub fn color(self, components: i32) -> Self {
+        self.float(COLOR, components)
+    }
+
+    pub fn normal(self) -> Self {
+        self.float(NORMAL, 3)
+    }
+
+    pub fn uv(self) -> Self {
+        self.float(UV, 2)
+    }
+
+    // An attribute at an explicit offset; the stride grows if the attribute ends past it
+    pub fn attribute(mut self, attribute: VertexAttribute) -> Self {
+        self.stride = self.stride.max(attribute.offset + attribute.size_in_bytes());
+        self.attributes.push(attribute);
+        self
+    }
+
+    // For vertices with padding at the end
+    pub fn with_stride(mut self, stride: i32) -> Self {
+        self.stride = stride;
+        self
+    }
+
+    pub fn stride(&self) -> i32 {
+        self.stride
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    pub fn get(&self, name: &str) -> Option<&VertexAttribute> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+}
+
+// A #[repr(C)] vertex struct, normally implemented with #[derive(Vertex)]
+pub trait Vertex: Sized {
+    const ATTRIBUTES: &'static [VertexAttribute];
+
+    // Writes the fields at their offsets into `out`, which starts at this vertex
+    fn write(&self, out: &mut [u8]);
+
+    fn layout() -> VertexLayout {
+        Self::ATTRIBUTES
+            .iter()
+            .fold(VertexLayout::new(), |layout, attribute| layout.attribute(*attribute))
+            .with_stride(std::mem::size_of::<Self>() as i32)
+    }
+}
+
+// Field types a vertex struct can hold: scalars and 1-4 component arrays of them, Vec3 and Vec4
+pub trait VertexField {
+    const COMPONENTS: i32;
+    const GL_TYPE: u32;
+
+    fn write(&self, out: &mut [u8]);
+}
+
+macro_rules! vertex_field {
+    ($($ty:ty => $gl_type:expr),*) => {$(
+        impl VertexField for $ty {
+            const COMPONENTS: i32 = 1;
+            const GL_TYPE: u32 = $gl_type;
+
+            fn write(&self, out: &mut [u8]) {
+                let bytes = self.to_ne_bytes();
+                out[..bytes.len()].copy_from_slice(&bytes);
+            }
+        }
+        vertex_field!(@array $ty, $gl_type, 1 2 3 4);
+    )*};
+    (@array $ty:ty, $gl_type:expr, $($n:literal)*) => {$(
+        impl VertexField for [$ty; $n] {
+            const COMPONENTS: i32 = $n;
+            const GL_TYPE: u32 = $gl_type;
+
+            fn write(&self, out: &mut [u8]) {
+                let size = std::mem::size_of::<$ty>();
+                for (i, v) in self.iter().enumerate() {
+                    VertexField::write(v, &mut out[i * size..]);
+                }
+            }
+        }
+    )*};
+}
+
+vertex_field!(
+    f32 => Gl::FLOAT,
+    u8 => Gl::UNSIGNED_BYTE,
+    i8 => Gl::BYTE,
+    u16 => Gl::UNSIGNED_SHORT,
+    i16 => Gl::SHORT
+);
+
+impl VertexField for Vec3 {
+    const COMPONENTS: i32 = 3;
+    const GL_TYPE: u32 = Gl::FLOAT;
+
+    fn write(&self, out: &mut [u8]) {
+        self.to_array().write(out);
+    }
+}
+
+impl VertexField for Vec4 {
+    const COMPONENTS: i32 = 4;
+    const GL_TYPE: u32 = Gl::FLOAT;
+
+    fn write(&self, out: &mut [u8]) {
+        self.to_array().write(out);
+    }
+}
+
+pub fn gl_type_size(gl_type: u32) -> i32 {
+    match gl_type {
+        Gl::BYTE | Gl::UNSIGNED_BYTE => 1,
+        Gl::SHORT | Gl::UNSIGNED_SHORT => 2,
+        _ => 4,
+    }
+}
+
+// Zips per-attribute arrays into one interleaved array, e.g. positions and colors of 3 floats each.
+// Stops at the shortest stream.
+pub fn interleave(streams: &[(&[f32], usize)]) -> Vec<f32> {
+    let count = streams.iter().map(|(data, n)| data.len() / n).min().unwrap_or(0);
+    let per_vertex: usize = streams.iter().map(|(_, n)| n).sum();
+    let mut out = Vec::with_capacity(count * per_vertex);
+    for i in 0..count {
+        for (data, n) in streams {
+            out.extend_from_slice(&data[i * n..(i + 1) * n]);
+        }
+    }
+    out
+}
+
+// Index types a mesh can be built from
+pub trait MeshIndex: Copy {
+    fn to_u32(self) -> u32;
+}
+
+impl MeshIndex for u16 {
+    fn to_u32(self) -> u32 {
+        self as u32
+    }
+}
+
+impl MeshIndex for u32 {
+    fn to_u32(self) -> u32 {
+        self
+    }
+}
+
+// How a mesh's indices went to the GPU
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexFormat {
+    // No index buffer, the vertices are drawn in order
+    None,
+    U16,
+    // Through OES_element_index_uint
+    U32,
+    // Without the extension: several u16 meshes, each with a copy of the vertices it uses
+    Split,
+}
+
+// What a u16 index reaches
+const U16_VERTICES: usize = 1 << 16;
+
+// A piece of a mesh too big for u16 indices. `indices` point into `vertices`, which are
+// indices of the mesh's own vertices.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexChunk {
+    pub vertices: Vec<u32>,
+    pub indices: Vec<u16>,
+}
+
+// Cuts `indices` into chunks of at most 65536 distinct vertices. Cuts fall on multiples of 6
+// indices so no point, line or triangle is split; strips and fans can't be split this way.
+pub fn split_indices(indices: &[u32]) -> Vec<IndexChunk> {
+    const GROUP: usize = 6;
+    let mut chunks = Vec::new();
+    let mut chunk = IndexChunk::default();
+    let mut local: HashMap<u32, u16> = HashMap::new();
+    for group in indices.chunks(GROUP) {
+        if chunk.vertices.len() + GROUP > U16_VERTICES {
+            chunks.push(std::mem::take(&mut chunk));
+            local.clear();
+        }
+        for &index in group {
+            let next = chunk.vertices.len() as u16;
+            let mapped = *local.entry(index).or_insert_with(|| {
+                chunk.vertices.push(index);
+                next
+            });
+            chunk.indices.push(mapped);
+        }
+    }
+    if !chunk.indices.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+// One vertex buffer with the index buffer drawn from it
+struct MeshPart {
+    vertex_buffer: Buffer,
+    index_buffer: Option<Buffer>,
+    index_count: i32,
+    // UNSIGNED_SHORT or UNSIGNED_INT
+    index_type: u32,
+    // For split meshes, the mesh vertex behind each vertex in the buffer
+    source: Vec<u32>,
+}
+
+// One interleaved vertex buffer plus an optional index buffer. Indices that don't fit u16 are
+// drawn as u32 when the context has OES_element_index_uint, or split into u16 parts otherwise.
+pub struct Mesh {
+    gl: Gl,
+    layout: VertexLayout,
+    parts: Vec<MeshPart>,
+    format: IndexFormat,
+    vertex_count: i32,
+    index_count: i32,
+    mode: u32,
+    // Program attribute locations bind() pointed at the first part, for draw() to move to the others
+    bound: RefCell<Vec<(u32, VertexAttribute)>>,
+}
+
+impl Mesh {
+    // `vertices` holds whole vertices of layout.stride() bytes each; no indices draws the vertices in order
+    pub fn new<I: MeshIndex>(gl: &Gl, layout: VertexLayout, vertices: &[u8], indices: &[I]) -> Self {
+        Mesh::with_usage(gl, layout, vertices, indices, Usage::Static)
+    }
+
+    // For meshes whose vertices get rewritten with update_vertices(); `usage` applies to the vertex buffer
+    pub fn with_usage<I: MeshIndex>(gl: &Gl, layout: VertexLayout, vertices: &[u8], indices: &[I], usage: Usage) -> Self {
+        let stride = layout.stride().max(1) as usize;
+        let vertex_count = vertices.len() / stride;
+        let indices: Vec<u32> = indices.iter().map(|i| i.to_u32()).collect();
+        let max_index = indices.iter().copied().max().unwrap_or(0) as usize;
+
+        let format = if indices.is_empty() {
+            IndexFormat::None
+        } else if max_index < U16_VERTICES {
+            IndexFormat::U16
+        } else if gl.get_extension("OES_element_index_uint").ok().flatten().is_some() {
+            IndexFormat::U32
+        } else {
+            IndexFormat::Split
+        };
+
+        let part = |vertices: &[u8], index_buffer: Option<Buffer>, index_count: usize, index_type: u32, source: Vec<u32>| {
+            let vertex_buffer = Buffer::with_data(gl, BufferTarget::Array, vertices, usage);
+            gl.bind_buffer(Gl::ARRAY_BUFFER, None);
+            gl.bind_buffer(Gl::ELEMENT_ARRAY_BUFFER, None);
+            MeshPart {
+                vertex_buffer,
+                index_buffer,
+                index_count: index_count as i32,
+                index_type,
+                source,
+            }
+        };
+
+        let parts = match format {
+            IndexFormat::None => vec![part(vertices, None, 0, Gl::UNSIGNED_SHORT, Vec::new())],
+            IndexFormat::U16 => {
+                let short: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+                let buffer = Some(Buffer::with_data(gl, BufferTarget::ElementArray, &short, Usage::Static));
+                vec![part(vertices, buffer, short.len(), Gl::UNSIGNED_SHORT, Vec::new())]
+            }
+            IndexFormat::U32 => {
+                let buffer = Some(Buffer::with_data(gl, BufferTarget::ElementArray, &indices, Usage::Static));
+                vec![part(vertices, buffer, indices.len(), Gl::UNSIGNED_INT, Vec::new())]
+            }
+            IndexFormat::Split => split_indices(&indices)
+                .into_iter()
+                .map(|chunk| {
+                    let mut copied = Vec::with_capacity(chunk.vertices.len() * stride);
+                    for &v in &chunk.vertices {
+                        let start = v as usize * stride;
+                        // Indices past the vertices read zeros rather than panicking
+                        match vertices.get(start..start + stride) {
+                            Some(vertex) => copied.extend_from_slice(vertex),
+                            None => copied.resize(copied.len() + stride, 0),
+                        }
+                    }
+                    let buffer = Some(Buffer::with_data(gl, BufferTarget::ElementArray, &chunk.indices, Usage::Static));
+                    part(&copied, buffer, chunk.indices.len(), Gl::UNSIGNED_SHORT, chunk.vertices)
+                })
+                .collect(),
+        };
+
+        Mesh {
+            gl: gl.clone(),
+            layout,
+            parts,
+            format,
+            vertex_count: vertex_count as i32,
+            index_count: indices.len() as i32,
+            mode: Gl::TRIANGLES,
+            bound: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn from_f32<I: MeshIndex>(gl: &Gl, layout: VertexLayout, vertices: &[f32], indices: &[I]) -> Self {
+        Mesh::new(gl, layout, &f32_bytes(vertices), indices)
+    }
+
+    // Layout from the vertex type; padding between fields is uploaded as zeros
+    pub fn from_vertices<V: Vertex, I: MeshIndex>(gl: &Gl, vertices: &[V], indices: &[I]) -> Self {
+        Mesh::new(gl, V::layout(), &vertex_bytes(vertices), indices)
+    }
+
+    // Overwrites vertices starting at `first`, they must already fit in the buffer
+    pub fn update_vertices<V: Vertex>(&self, first: usize, vertices: &[V]) -> Result<(), BufferError> {
+        self.update_bytes(first, &vertex_bytes(vertices))
+    }
+
+    pub fn update_bytes(&self, first: usize, bytes: &[u8]) -> Result<(), BufferError> {
+        let stride = self.layout.stride().max(1) as usize;
+        if self.format != IndexFormat::Split {
+            return self.parts[0].vertex_buffer.update(first * stride, bytes);
+        }
+
+        // Split parts hold copies, so each copy of an updated vertex is rewritten
+        let count = bytes.len() / stride;
+        if first + count > self.vertex_count as usize {
+            return Err(BufferError {
+                offset: first * stride,
+                len: bytes.len(),
+                size: self.vertex_count as usize * stride,
+            });
+        }
+        for part in &self.parts {
+            for (local, &source) in part.source.iter().enumerate() {
+                if let Some(k) = (source as usize).checked_sub(first).filter(|&k| k < count) {
+                    part.vertex_buffer.update(local * stride, &bytes[k * stride..(k + 1) * stride])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn layout(&self) -> &VertexLayout {
+        &self.layout
+    }
+
+    pub fn index_format(&self) -> IndexFormat {
+        self.format
+    }
+
+    pub fn vertex_count(&self) -> i32 {
+        self.vertex_count
+    }
+
+    pub fn index_count(&self) -> i32 {
+        self.index_count
+    }
+
+    // TRIANGLES by default. Split meshes only draw right as TRIANGLES, LINES or POINTS.
+    pub fn set_mode(&mut self, mode: u32) {
+        self.mode = mode;
+    }
+
+    // Points every attribute the program uses at this mesh's buffer. Attributes the layout has
+    // but the program doesn't use are skipped; one the program needs but the layout lacks is an error.
+    pub fn bind(&self, program: &ShaderProgram) -> Result<(), ProgramError> {
+        let mut bound = Vec::with_capacity(program.attributes().len());
+        for (name, info) in program.attributes() {
+            let attribute = self
+                .layout
+                .get(name)
+                .ok_or_else(|| ProgramError::MissingVertexAttribute(name.clone()))?;
+            bound.push((info.location, *attribute));
+        }
+        self.point_attributes(&self.parts[0], &bound);
+        for (location, _) in &bound {
+            self.gl.enable_vertex_attrib_array(*location);
+        }
+        *self.bound.borrow_mut() = bound;
+        Ok(())
+    }
+
+    pub fn unbind(&self, program: &ShaderProgram) {
+        for info in program.attributes().values() {
+            self.gl.disable_vertex_attrib_array(info.location);
+        }
+    }
+
+    pub fn draw(&self) {
+        for (i, part) in self.parts.iter().enumerate() {
+            if i > 0 {
+                self.point_attributes(part, &self.bound.borrow());
+            }
+            match &part.index_buffer {
+                Some(index_buffer) => {
+                    index_buffer.bind();
+                    self.gl.draw_elements_with_i32(self.mode, part.index_count, part.index_type, 0);
+                }
+                None => self.gl.draw_arrays(self.mode, 0, self.vertex_count),
+            }
+        }
+        if self.parts.len() > 1 {
+            // Leave the attributes where bind() put them
+            self.point_attributes(&self.parts[0], &self.bound.borrow());
+        }
+    }
+
+    // `count` vertices from `first`, ignoring any indices; only meant for meshes built without them
+    pub fn draw_range(&self, first: i32, count: i32) {
+        self.gl.draw_arrays(self.mode, first, count);
+    }
+
+    pub fn delete(self) {
+        for part in self.parts {
+            part.vertex_buffer.delete();
+            if let Some(index_buffer) = part.index_buffer {
+                index_buffer.delete();
+            }
+        }
+    }
+
+    fn point_attributes(&self, part: &MeshPart, bound: &[(u32, VertexAttribute)]) {
+        part.vertex_buffer.bind();
+        for (location, attribute) in bound {
+            self.gl.vertex_attrib_pointer_with_i32(
+                *location,
+                attribute.components,
+                attribute.gl_type,
+                attribute.normalized,
+                self.layout.stride(),
+                attribute.offset,
+            );
+        }
+    }
+}
+
+pub fn vertex_bytes<V: Vertex>(vertices: &[V]) -> Vec<u8> {
+    let stride = std::mem::size_of::<V>();
+    let mut bytes = vec![0; std::mem::size_of_val(vertices)];
+    for (vertex, out) in vertices.iter().zip(bytes.chunks_mut(stride.max(1))) {
+        vertex.write(out);
+    }
+    bytes
+}
+
+fn f32_bytes(data: &[f32]) -> Vec<u8> {
+    data.iter().flat_map(|v| v.to_ne_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vertex;
+
+    #[test]
+    fn packed_layout_offsets() {
+        let layout = VertexLayout::new().position(3).color(3).normal().uv();
+        let offsets: Vec<i32> = layout.attributes().iter().map(|a| a.offset).collect();
+        assert_eq!(offsets, vec![0, 12, 24, 36]);
+        assert_eq!(layout.stride(), 44);
+        assert_eq!(layout.get(UV).unwrap().components, 2);
+        assert!(layout.get("tangent").is_none());
+    }
+
+    #[test]
+    fn explicit_offsets_and_stride() {
+        let layout = VertexLayout::new()
+            .position(3)
+            .attribute(VertexAttribute {
+                name: COLOR,
+                components: 4,
+                gl_type: Gl::UNSIGNED_BYTE,
+                normalized: true,
+                offset: 12,
+            })
+            .with_stride(20);
+        assert_eq!(layout.get(COLOR).unwrap().size_in_bytes(), 4);
+        assert_eq!(layout.stride(), 20);
+    }
+
+    #[derive(Clone, Copy, Vertex)]
+    #[repr(C)]
+    struct ColorVertex {
+        position: [f32; 3],
+        #[vertex(normalized)]
+        color: [u8; 4],
+        #[vertex(name = "uv")]
+        tex_coord: [f32; 2],
+        #[vertex(skip)]
+        _pad: u16,
+        normal: Vec3,
+    }
+
+    #[test]
+    fn derived_layout_matches_repr_c() {
+        let layout = ColorVertex::layout();
+        assert_eq!(layout.stride() as usize, std::mem::size_of::<ColorVertex>());
+        assert_eq!(layout.attributes(), &[
+            VertexAttribute { name: POSITION, components: 3, gl_type: Gl::FLOAT, normalized: false, offset: 0 },
+            VertexAttribute { name: COLOR, components: 4, gl_type: Gl::UNSIGNED_BYTE, normalized: true, offset: 12 },
+            VertexAttribute { name: UV, components: 2, gl_type: Gl::FLOAT, normalized: false, offset: 16 },
+            VertexAttribute { name: NORMAL, components: 3, gl_type: Gl::FLOAT, normalized: false, offset: 28 },
+        ]);
+    }
+
+    #[test]
+    fn vertex_bytes_follow_the_layout() {
+        let vertex = ColorVertex {
+            position: [1.0, 2.0, 3.0],
+            color: [255, 0, 128, 255],
+            tex_coord: [0.5, 0.25],
+            _pad: 0xffff,
+            normal: Vec3::Z,
+        };
+        let bytes = vertex_bytes(&[vertex, vertex]);
+        assert_eq!(bytes.len(), 2 * std::mem::size_of::<ColorVertex>());
+
+        let f32_at = |offset: usize| f32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!([f32_at(0), f32_at(4), f32_at(8)], [1.0, 2.0, 3.0]);
+        assert_eq!(&bytes[12..16], &[255, 0, 128, 255]);
+        assert_eq!([f32_at(16), f32_at(20)], [0.5, 0.25]);
+        // Skipped fields and padding stay zero
+        assert_eq!(&bytes[24..28], &[0, 0, 0, 0]);
+        assert_eq!([f32_at(28), f32_at(32), f32_at(36)], [0.0, 0.0, 1.0]);
+        assert_eq!(&bytes[40..44], &bytes[0..4]);
+    }
+
+    #[test]
+    fn split_chunks_stay_u16_addressable() {
+        // A triangle list over 100000 vertices, each vertex used by two neighbouring triangles
+        let count = 100_000u32;
+        let indices: Vec<u32> = (0..count - 2).flat_map(|i| [i, i + 1, i + 2]).collect();
+        let chunks = split_indices(&indices);
+        assert!(chunks.len() >= 2);
+        assert!(chunks.iter().all(|chunk| chunk.vertices.len() <= U16_VERTICES));
+        assert!(chunks.iter().all(|chunk| chunk.indices.len() % 6 == 0 || chunk == chunks.last().unwrap()));
+
+        // Mapping back through the chunks gives the original list
+        let rebuilt: Vec<u32> = chunks
+            .iter()
+            .flat_map(|chunk| chunk.indices.iter().map(|&i| chunk.vertices[i as usize]))
+            .collect();
+        assert_eq!(rebuilt, indices);
+        assert!(split_indices(&[]).is_empty());
+    }
+
+    #[test]
+    fn interleaves_streams() {
+        let positions = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        let colors = [0.5, 0.5, 0.5, 0.25, 0.25, 0.25];
+        assert_eq!(
+            interleave(&[(&positions, 3), (&colors, 3)]),
+            vec![0.0, 0.0, 0.0, 0.5, 0.5, 0.5, 1.0, 1.0, 1.0, 0.25, 0.25, 0.25]
+        );
+        assert!(interleave(&[]).is_empty());
+    }
+}
@@ -0,0 +1,437 @@
+// Cleanup for a Geometry before it goes into a Mesh: welding duplicate vertices, normals with a
+// crease angle, tangents for normal maps and triangle order for the post-transform vertex cache.
+use std::collections::{HashMap, VecDeque};
+
+use crate::geometry::Geometry;
+use crate::math::{Vec3, Vec4};
+
+// Merges vertices whose position, normal and uv are each within `epsilon` of an earlier vertex's.
+// Triangles that collapse to an edge or a point are dropped. Returns how many vertices went away.
+// An epsilon of 0 merges exact duplicates only.
+pub fn weld(geometry: &mut Geometry, epsilon: f32) -> usize {
+    let mut welded = Geometry::new();
+    let remap = if epsilon > 0.0 {
+        weld_within(geometry, &mut welded, epsilon)
+    } else {
+        weld_exact(geometry, &mut welded)
+    };
+
+    for triangle in geometry.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| remap[triangle[k] as usize]);
+        if a != b && b != c && c != a {
+            welded.push_triangle(a, b, c);
+        }
+    }
+    let removed = geometry.vertex_count() - welded.vertex_count();
+    *geometry = welded;
+    removed
+}
+
+// Vertices equal bit for bit, with -0 the same as 0
+fn weld_exact(geometry: &Geometry, welded: &mut Geometry) -> Vec<u32> {
+    let bits = |c: f32| (c + 0.0).to_bits();
+    let mut seen: HashMap<[u32; 8], u32> = HashMap::new();
+    (0..geometry.vertex_count())
+        .map(|i| {
+            let (p, n, uv) = (geometry.positions[i], geometry.normals[i], geometry.uvs[i]);
+            let key = [p.x, p.y, p.z, n.x, n.y, n.z, uv[0], uv[1]].map(bits);
+            *seen.entry(key).or_insert_with(|| welded.push_vertex(p, n, uv))
+        })
+        .collect()
+}
+
+fn weld_within(geometry: &Geometry, welded: &mut Geometry, epsilon: f32) -> Vec<u32> {
+    let key = |p: Vec3| [p.x, p.y, p.z].map(|c| (c / epsilon).floor() as i64);
+    let close = |a: Vec3, b: Vec3| (a - b).length() <= epsilon;
+
+    // Vertices of `welded` by the grid cell of their position; a match can be in a neighbouring cell
+    let mut grid: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let mut remap = Vec::with_capacity(geometry.vertex_count());
+    for i in 0..geometry.vertex_count() {
+        let (position, normal, uv) = (geometry.positions[i], geometry.normals[i], geometry.uvs[i]);
+        let [x, y, z] = key(position);
+        let mut found = None;
+        'search: for dx in -1..=1i64 {
+            for dy in -1..=1i64 {
+                for dz in -1..=1i64 {
+                    // Cells saturate for tiny epsilons against big coordinates
+                    let cell = [x.saturating_add(dx), y.saturating_add(dy), z.saturating_add(dz)];
+                    for &j in grid.get(&cell).into_iter().flatten() {
+                        let j_uv = welded.uvs[j as usize];
+                        if close(welded.positions[j as usize], position)
+                            && close(welded.normals[j as usize], normal)
+                            && (j_uv[0] - uv[0]).abs() <= epsilon
+                            && (j_uv[1] - uv[1]).abs() <= epsilon
+                        {
+                            found = Some(j);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+        let index = found.unwrap_or_else(|| {
+            let index = welded.push_vertex(position, normal, uv);
+            grid.entry([x, y, z]).or_default().push(index);
+            index
+        });
+        remap.push(index);
+    }
+    remap
+}
+
+// Normals averaged over the faces around each position, but only across edges where the faces
+// meet at less than `crease_angle` (radians). Vertices on a crease are split, one per side, so
+// 0 gives flat shading and PI smooths everything. Faces are weighted by their angle at the
+// vertex, so how a face happens to be split into triangles doesn't tilt the result.
+pub fn smooth_normals(geometry: &mut Geometry, crease_angle: f32) {
+    let units: Vec<Vec3> = geometry
+        .indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|k| geometry.positions[triangle[k] as usize]);
+            (b - a).cross(c - a).normalize()
+        })
+        .collect();
+
+    // (face, angle of the face at the corner) around each position, whatever normal or uv the corners had
+    let position_key = |i: u32| geometry.positions[i as usize].to_array().map(f32::to_bits);
+    let mut around: HashMap<[u32; 3], Vec<(usize, f32)>> = HashMap::new();
+    for (face, triangle) in geometry.indices.chunks_exact(3).enumerate() {
+        for k in 0..3 {
+            let [p, next, prev] = [k, (k + 1) % 3, (k + 2) % 3].map(|k| geometry.positions[triangle[k] as usize]);
+            let (e1, e2) = ((next - p).normalize(), (prev - p).normalize());
+            let angle = e1.dot(e2).clamp(-1.0, 1.0).acos();
+            around.entry(position_key(triangle[k])).or_default().push((face, angle));
+        }
+    }
+
+    // A little slack so a face always counts as within 0 of itself
+    let min_cos = crease_angle.cos() - 1e-5;
+    let mut out = Geometry::new();
+    // One vertex per source vertex and normal it ends up with
+    let mut vertices: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    for (face, triangle) in geometry.indices.chunks_exact(3).enumerate() {
+        let [a, b, c] = [0, 1, 2].map(|k| {
+            let i = triangle[k];
+            let normal = around[&position_key(i)]
+                .iter()
+                .filter(|&&(other, _)| other == face || units[other].dot(units[face]) >= min_cos)
+                .fold(Vec3::ZERO, |sum, &(other, angle)| sum + units[other] * angle)
+                .normalize();
+            *vertices
+                .entry((i, normal.to_array().map(f32::to_bits)))
+                .or_insert_with(|| out.push_vertex(geometry.positions[i as usize], normal, geometry.uvs[i as usize]))
+        });
+        out.push_triangle(a, b, c);
+    }
+    *geometry = out;
+}
+
+// Every triangle gets its own normal
+pub fn flat_normals(geometry: &mut Geometry) {
+    smooth_normals(geometry, 0.0);
+}
+
+// Per-vertex tangent along +u in xyz, with the handedness of the uv mapping in w:
+// bitangent = cross(normal, tangent) * w. Vertices whose uvs don't span an area get any
+// direction perpendicular to the normal.
+pub fn compute_tangents(geometry: &Geometry) -> Vec<Vec4> {
+    let mut tangents = vec![Vec3::ZERO; geometry.vertex_count()];
+    let mut bitangents = vec![Vec3::ZERO; geometry.vertex_count()];
+    for triangle in geometry.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| triangle[k] as usize);
+        let (e1, e2) = (geometry.positions[b] - geometry.positions[a], geometry.positions[c] - geometry.positions[a]);
+        let [u0, v0] = geometry.uvs[a];
+        let ([du1, dv1], [du2, dv2]) = ([geometry.uvs[b][0] - u0, geometry.uvs[b][1] - v0], [geometry.uvs[c][0] - u0, geometry.uvs[c][1] - v0]);
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() < 1e-12 {
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (e1 * dv2 - e2 * dv1) * r;
+        let bitangent = (e2 * du1 - e1 * du2) * r;
+        for i in [a, b, c] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    geometry
+        .normals
+        .iter()
+        .zip(tangents.iter().zip(&bitangents))
+        .map(|(&normal, (&tangent, &bitangent))| {
+            // Gram-Schmidt against the normal
+            let mut t = (tangent - normal * normal.dot(tangent)).normalize();
+            if t.length() < 0.5 {
+                let axis = if normal.x.abs() < 0.9 { Vec3::X } else { Vec3::Y };
+                t = (axis - normal * normal.dot(axis)).normalize();
+            }
+            let w = if normal.cross(t).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+            t.extend(w)
+        })
+        .collect()
+}
+
+// Cache the stats simulate: a FIFO of this many vertices, about what GPUs have
+pub const FIFO_CACHE_SIZE: usize = 16;
+
+// Average cache miss ratio, before and after optimize_vertex_cache
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheStats {
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+// Vertices transformed per triangle with a FIFO cache of `cache_size` vertices: 3 with no reuse,
+// about 0.5 at best on a large regular grid
+pub fn acmr(indices: &[u32], cache_size: usize) -> f32 {
+    let triangles = indices.len() / 3;
+    if triangles == 0 {
+        return 0.0;
+    }
+    let mut cache = VecDeque::with_capacity(cache_size + 1);
+    let mut misses = 0;
+    for &index in indices {
+        if !cache.contains(&index) {
+            misses += 1;
+            cache.push_back(index);
+            if cache.len() > cache_size {
+                cache.pop_front();
+            }
+        }
+    }
+    misses as f32 / triangles as f32
+}
+
+// Reorders the triangles so neighbours are drawn close together. The triangles and their winding
+// stay the same, only their order changes.
+pub fn optimize_vertex_cache(geometry: &mut Geometry) -> CacheStats {
+    let acmr_before = acmr(&geometry.indices, FIFO_CACHE_SIZE);
+    geometry.indices = reorder_for_cache(&geometry.indices, geometry.vertex_count());
+    CacheStats {
+        acmr_before,
+        acmr_after: acmr(&geometry.indices, FIFO_CACHE_SIZE),
+    }
+}
+
+// Cache the optimizer models, an LRU a bit larger than the FIFO it's measured with
+const LRU_CACHE_SIZE: usize = 32;
+
+// Tom Forsyth's linear-speed vertex cache optimisation: vertices score higher the more recently
+// they were used and the fewer triangles they have left, and the best triangle around the
+// vertices in the cache goes next
+fn reorder_for_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    let triangle = |t: usize| [indices[3 * t], indices[3 * t + 1], indices[3 * t + 2]];
+
+    // Triangles not drawn yet around each vertex
+    let mut pending: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    for t in 0..triangle_count {
+        for v in triangle(t) {
+            pending[v as usize].push(t);
+        }
+    }
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut score: Vec<f32> = pending.iter().map(|around| vertex_score(None, around.len())).collect();
+
+    let mut drawn = vec![false; triangle_count];
+    let mut cache: Vec<u32> = Vec::with_capacity(LRU_CACHE_SIZE + 3);
+    let mut out = Vec::with_capacity(triangle_count * 3);
+    let mut best: Option<usize> = None;
+    // Where to look for a new start when nothing around the cache is left
+    let mut next_start = 0;
+    for _ in 0..triangle_count {
+        let t = best.unwrap_or_else(|| {
+            while drawn[next_start] {
+                next_start += 1;
+            }
+            next_start
+        });
+        drawn[t] = true;
+        let corners = triangle(t);
+        out.extend_from_slice(&corners);
+        for v in corners {
+            pending[v as usize].retain(|&other| other != t);
+        }
+
+        // The triangle's vertices move to the front, the rest shift back and some fall out
+        let mut moved: Vec<u32> = Vec::with_capacity(LRU_CACHE_SIZE + 3);
+        for &v in corners.iter().chain(&cache) {
+            if !moved.contains(&v) {
+                moved.push(v);
+            }
+        }
+        for (position, &v) in moved.iter().enumerate() {
+            let v = v as usize;
+            cache_position[v] = (position < LRU_CACHE_SIZE).then_some(position);
+            score[v] = vertex_score(cache_position[v], pending[v].len());
+        }
+        moved.truncate(LRU_CACHE_SIZE);
+
+        best = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for &v in &moved {
+            for &other in &pending[v as usize] {
+                let other_score: f32 = triangle(other).iter().map(|&c| score[c as usize]).sum();
+                if other_score > best_score {
+                    best = Some(other);
+                    best_score = other_score;
+                }
+            }
+        }
+        cache = moved;
+    }
+    out
+}
+
+fn vertex_score(cache_position: Option<usize>, pending: usize) -> f32 {
+    if pending == 0 {
+        return -1.0;
+    }
+    let cache = match cache_position {
+        None => 0.0,
+        // The triangle just drawn; a fixed score so it doesn't get picked again straight away
+        Some(position) if position < 3 => 0.75,
+        Some(position) => (1.0 - (position - 3) as f32 / (LRU_CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+    // Finishing off vertices with few triangles left keeps lone triangles from piling up
+    cache + 2.0 / (pending as f32).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| {
+                // Rotated to start at the smallest index, which keeps the winding
+                let k = (0..3).min_by_key(|&k| t[k]).unwrap();
+                [t[k], t[(k + 1) % 3], t[(k + 2) % 3]]
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn weld_merges_duplicates() {
+        // Two triangles of a quad, each with its own copy of the shared edge
+        let mut geometry = Geometry::new();
+        for p in [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 1.0 + 1e-7, 0.0)] {
+            geometry.push_vertex(p, Vec3::Z, [p.x, p.y]);
+        }
+        geometry.indices = vec![0, 1, 2, 3, 4, 5];
+        assert_eq!(weld(&mut geometry, 1e-5), 2);
+        assert_eq!(geometry.vertex_count(), 4);
+        assert_eq!(geometry.indices, vec![0, 1, 2, 1, 3, 2]);
+
+        // Different uvs keep the seam
+        let mut cube = primitives::cube(2.0, 1);
+        assert_eq!(weld(&mut cube, 1e-5), 0);
+
+        // A triangle squeezed onto an edge goes away
+        let mut sliver = Geometry::new();
+        for p in [Vec3::ZERO, Vec3::X, Vec3::new(1e-7, 0.0, 0.0)] {
+            sliver.push_vertex(p, Vec3::Z, [0.0, 0.0]);
+        }
+        sliver.indices = vec![0, 1, 2];
+        weld(&mut sliver, 1e-5);
+        assert_eq!(sliver.triangle_count(), 0);
+    }
+
+    #[test]
+    fn weld_exact_duplicates() {
+        // The same quad: only the bit-equal copy of (1, 0, 0) merges at 0, and -0 counts as 0
+        let mut geometry = Geometry::new();
+        for p in [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::new(-0.0, 1.0 + 1e-7, 0.0)] {
+            geometry.push_vertex(p, Vec3::Z, [p.x, p.y]);
+        }
+        geometry.indices = vec![0, 1, 2, 3, 4, 5];
+        assert_eq!(weld(&mut geometry, 0.0), 1);
+        assert_eq!(geometry.indices, vec![0, 1, 2, 1, 3, 4]);
+
+        // Huge coordinates with a tiny epsilon don't overflow the grid cells
+        let mut far = Geometry::new();
+        for x in [f32::MAX, f32::MAX, -f32::MAX] {
+            far.push_vertex(Vec3::new(x, 0.0, 0.0), Vec3::Z, [0.0, 0.0]);
+        }
+        assert_eq!(weld(&mut far, 1e-30), 1);
+    }
+
+    #[test]
+    fn crease_angle_splits_normals() {
+        // A cube down to its 8 corners
+        let mut cube = primitives::cube(2.0, 1);
+        cube.normals.iter_mut().for_each(|n| *n = Vec3::ZERO);
+        cube.uvs.iter_mut().for_each(|uv| *uv = [0.0, 0.0]);
+        weld(&mut cube, 1e-5);
+        assert_eq!(cube.vertex_count(), 8);
+
+        // Edges meet at 90 degrees, so 60 keeps every face flat
+        let mut creased = cube.clone();
+        smooth_normals(&mut creased, 60f32.to_radians());
+        assert_eq!(creased.vertex_count(), 24);
+        assert!(creased.normals.iter().all(|n| [n.x, n.y, n.z].iter().filter(|c| c.abs() > 0.999).count() == 1));
+
+        let mut flat = cube.clone();
+        flat_normals(&mut flat);
+        assert_eq!(flat.vertex_count(), 24);
+
+        let mut smooth = cube.clone();
+        smooth_normals(&mut smooth, std::f32::consts::PI);
+        assert_eq!(smooth.vertex_count(), 8);
+        for (p, n) in smooth.positions.iter().zip(&smooth.normals) {
+            assert!((*n - p.normalize()).length() < 1e-5);
+        }
+        assert_eq!(sorted_triangles(&smooth.indices).len(), 12);
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        let mut geometry = Geometry::new();
+        geometry.push_vertex(Vec3::ZERO, Vec3::Z, [0.0, 0.0]);
+        geometry.push_vertex(Vec3::X, Vec3::Z, [1.0, 0.0]);
+        geometry.push_vertex(Vec3::Y, Vec3::Z, [0.0, 1.0]);
+        geometry.push_triangle(0, 1, 2);
+        let tangents = compute_tangents(&geometry);
+        assert!(tangents.iter().all(|t| *t == Vec4::new(1.0, 0.0, 0.0, 1.0)));
+
+        // Mirrored u flips the handedness
+        geometry.uvs = vec![[1.0, 0.0], [0.0, 0.0], [1.0, 1.0]];
+        let tangents = compute_tangents(&geometry);
+        assert!(tangents.iter().all(|t| *t == Vec4::new(-1.0, 0.0, 0.0, -1.0)));
+
+        let sphere = primitives::sphere(1.0, 16, 8);
+        for (t, n) in compute_tangents(&sphere).iter().zip(&sphere.normals) {
+            let t = t.truncate();
+            assert!((t.length() - 1.0).abs() < 1e-4);
+            assert!(t.dot(*n).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn cache_order_lowers_acmr() {
+        let mut sphere = primitives::sphere(1.0, 64, 32);
+        // Scatter the triangles so the order starts out bad
+        let mut triangles: Vec<[u32; 3]> = sphere.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+        let n = triangles.len();
+        for i in 0..n {
+            triangles.swap(i, (i * 7919 + 13) % n);
+        }
+        sphere.indices = triangles.concat();
+        let before = sorted_triangles(&sphere.indices);
+
+        let stats = optimize_vertex_cache(&mut sphere);
+        assert!(stats.acmr_before > 2.0, "{:?}", stats);
+        assert!(stats.acmr_after < 1.0, "{:?}", stats);
+        assert_eq!(sorted_triangles(&sphere.indices), before);
+
+        assert_eq!(acmr(&[0, 1, 2, 2, 1, 3], 16), 2.0);
+        assert_eq!(acmr(&[], 16), 0.0);
+    }
+}
@@ -0,0 +1,384 @@
+// Wavefront OBJ and MTL, read from the bytes a page fetched. Covers v/vt/vn, faces of any size
+// (ear clipped into triangles, so concave ones work too), g/o groups, usemtl/mtllib and the material colors the shaders can show.
+// Everything else (curves, smoothing groups, line elements, ...) is skipped.
+use std::collections::HashMap;
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::geometry::Geometry;
+use crate::math::Vec3;
+use crate::triangulate::{project, triangulate};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjError {
+    // 1-based
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl From<ObjError> for JsValue {
+    fn from(err: ObjError) -> JsValue {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+// A `newmtl` block
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    // Kd
+    pub diffuse: [f32; 3],
+    // d, or 1 - Tr
+    pub opacity: f32,
+    // map_Kd, relative to the .mtl file
+    pub diffuse_map: Option<String>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> Self {
+        ObjMaterial {
+            name: name.to_string(),
+            diffuse: [0.8, 0.8, 0.8],
+            opacity: 1.0,
+            diffuse_map: None,
+        }
+    }
+}
+
+// The faces that share a group and a material
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjMesh {
+    // From the last `g` or `o`, empty before the first one
+    pub group: String,
+    pub material: Option<String>,
+    pub geometry: Geometry,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    // Files named by `mtllib`, for the page to fetch and hand to parse_mtl
+    pub material_libs: Vec<String>,
+}
+
+impl ObjModel {
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        self.meshes
+            .iter()
+            .filter_map(|mesh| mesh.geometry.bounds())
+            .reduce(|(min, max), (lo, hi)| {
+                (
+                    Vec3::new(min.x.min(lo.x), min.y.min(lo.y), min.z.min(lo.z)),
+                    Vec3::new(max.x.max(hi.x), max.y.max(hi.y), max.z.max(hi.z)),
+                )
+            })
+    }
+}
+
+// Position, uv and normal index of one face corner, 0-based
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct MeshBuilder {
+    mesh: ObjMesh,
+    // Each distinct corner becomes one vertex
+    vertices: HashMap<Corner, u32>,
+    // Vertices without a `vn`; they get the sum of the face normals around them
+    unlit: Vec<u32>,
+}
+
+pub fn parse_obj(bytes: &[u8]) -> Result<ObjModel, ObjError> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut material_libs = Vec::new();
+    let mut builders: Vec<MeshBuilder> = Vec::new();
+    let mut group = String::new();
+    let mut material: Option<String> = None;
+
+    for (number, line) in text.lines().enumerate() {
+        let line_number = number + 1;
+        let err = |message: String| ObjError { line: line_number, message };
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = floats(&rest, line_number)?;
+                positions.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                // A missing v is 0 per the spec
+                let u = floats::<1>(&rest, line_number)?[0];
+                let v = if rest.len() > 1 { floats::<2>(&rest, line_number)?[1] } else { 0.0 };
+                uvs.push([u, v]);
+            }
+            "vn" => {
+                let [x, y, z] = floats(&rest, line_number)?;
+                normals.push(Vec3::new(x, y, z).normalize());
+            }
+            "f" => {
+                if rest.len() < 3 {
+                    return Err(err(format!("face with {} vertices", rest.len())));
+                }
+                let corners = rest
+                    .iter()
+                    .map(|token| parse_corner(token, positions.len(), uvs.len(), normals.len()).map_err(&err))
+                    .collect::<Result<Vec<Corner>, ObjError>>()?;
+
+                let index = match builders.iter().position(|b| b.mesh.group == group && b.mesh.material == material) {
+                    Some(index) => index,
+                    None => {
+                        builders.push(MeshBuilder {
+                            mesh: ObjMesh {
+                                group: group.clone(),
+                                material: material.clone(),
+                                geometry: Geometry::new(),
+                            },
+                            vertices: HashMap::new(),
+                            unlit: Vec::new(),
+                        });
+                        builders.len() - 1
+                    }
+                };
+                add_face(&mut builders[index], &corners, &positions, &uvs, &normals);
+            }
+            "g" | "o" => group = rest.join(" "),
+            "usemtl" => material = Some(rest.join(" ")),
+            "mtllib" => material_libs.extend(rest.iter().map(|name| name.to_string())),
+            _ => {}
+        }
+    }
+
+    let meshes = builders
+        .into_iter()
+        .map(|mut builder| {
+            for &i in &builder.unlit {
+                let normal = &mut builder.mesh.geometry.normals[i as usize];
+                *normal = normal.normalize();
+            }
+            builder.mesh
+        })
+        .collect();
+    Ok(ObjModel { meshes, material_libs })
+}
+
+// `v`, `v/vt`, `v//vn` or `v/vt/vn`, with negative indices counting back from the latest
+fn parse_corner(token: &str, positions: usize, uvs: usize, normals: usize) -> Result<Corner, String> {
+    let mut parts = token.split('/');
+    let position = resolve_index(parts.next().unwrap_or(""), positions)?;
+    let uv = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, uvs)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, normals)?),
+        _ => None,
+    };
+    Ok((position, uv, normal))
+}
+
+fn resolve_index(part: &str, count: usize) -> Result<usize, String> {
+    let index: i64 = part.parse().map_err(|_| format!("bad index '{}'", part))?;
+    let resolved = if index > 0 { index - 1 } else { count as i64 + index };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index {} out of range, {} defined so far", index, count));
+    }
+    Ok(resolved as usize)
+}
+
+fn add_face(builder: &mut MeshBuilder, corners: &[Corner], positions: &[Vec3], uvs: &[[f32; 2]], normals: &[Vec3]) {
+    let mut indices = Vec::with_capacity(corners.len());
+    for corner in corners {
+        let index = match builder.vertices.get(corner) {
+            Some(&index) => index,
+            None => {
+                let geometry = &mut builder.mesh.geometry;
+                let (position, uv, normal) = *corner;
+                let uv = uv.map_or([0.0, 0.0], |i| uvs[i]);
+                let index = geometry.push_vertex(positions[position], normal.map_or(Vec3::ZERO, |i| normals[i]), uv);
+                if normal.is_none() {
+                    builder.unlit.push(index);
+                }
+                builder.vertices.insert(*corner, index);
+                index
+            }
+        };
+        indices.push(index);
+    }
+
+    // Newell's method, also right for polygons that aren't quite flat; the length is twice the area
+    let face_normal = corners.iter().zip(corners.iter().cycle().skip(1)).fold(Vec3::ZERO, |sum, (a, b)| {
+        let (a, b) = (positions[a.0], positions[b.0]);
+        sum + Vec3::new((a.y - b.y) * (a.z + b.z), (a.z - b.z) * (a.x + b.x), (a.x - b.x) * (a.y + b.y))
+    });
+    for (corner, &index) in corners.iter().zip(&indices) {
+        if corner.2.is_none() {
+            builder.mesh.geometry.normals[index as usize] += face_normal;
+        }
+    }
+
+    if indices.len() == 3 {
+        builder.mesh.geometry.push_triangle(indices[0], indices[1], indices[2]);
+        return;
+    }
+    // Flattened onto the plane the face is closest to; a convex face still comes out as a fan
+    let corner_positions: Vec<Vec3> = corners.iter().map(|corner| positions[corner.0]).collect();
+    for triangle in triangulate(&project(&corner_positions, face_normal), &[]).chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| indices[triangle[k] as usize]);
+        builder.mesh.geometry.push_triangle(a, b, c);
+    }
+}
+
+pub fn parse_mtl(bytes: &[u8]) -> Result<HashMap<String, ObjMaterial>, ObjError> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut materials = HashMap::new();
+    let mut current: Option<ObjMaterial> = None;
+
+    for (number, line) in text.lines().enumerate() {
+        let line_number = number + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if let Some(done) = current.replace(ObjMaterial::new(&rest.join(" "))) {
+                materials.insert(done.name.clone(), done);
+            }
+            continue;
+        }
+        let Some(material) = current.as_mut() else {
+            return Err(ObjError {
+                line: line_number,
+                message: format!("'{}' before any newmtl", keyword),
+            });
+        };
+        match keyword {
+            "Kd" => material.diffuse = floats(&rest, line_number)?,
+            "d" => material.opacity = floats::<1>(&rest, line_number)?[0],
+            "Tr" => material.opacity = 1.0 - floats::<1>(&rest, line_number)?[0],
+            // Options like `-s 2 2 1` come before the file name
+            "map_Kd" => material.diffuse_map = rest.last().map(|name| name.to_string()),
+            _ => {}
+        }
+    }
+    if let Some(done) = current {
+        materials.insert(done.name.clone(), done);
+    }
+    Ok(materials)
+}
+
+// The first N numbers after the keyword, more are allowed
+fn floats<const N: usize>(tokens: &[&str], line: usize) -> Result<[f32; N], ObjError> {
+    let mut out = [0.0; N];
+    for (i, value) in out.iter_mut().enumerate() {
+        let token = tokens.get(i).ok_or_else(|| ObjError {
+            line,
+            message: format!("expected {} numbers, found {}", N, tokens.len()),
+        })?;
+        *value = token.parse().map_err(|_| ObjError {
+            line,
+            message: format!("bad number '{}'", token),
+        })?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE_OBJ: &[u8] = include_bytes!("../fixtures/cube.obj");
+    const CUBE_MTL: &[u8] = include_bytes!("../fixtures/cube.mtl");
+    const SHAPES_OBJ: &[u8] = include_bytes!("../fixtures/shapes.obj");
+
+    #[test]
+    fn cube_splits_by_material() {
+        let model = parse_obj(CUBE_OBJ).unwrap();
+        assert_eq!(model.material_libs, vec!["cube.mtl"]);
+        let summary: Vec<(&str, Option<&str>, usize, usize)> = model
+            .meshes
+            .iter()
+            .map(|m| (m.group.as_str(), m.material.as_deref(), m.geometry.vertex_count(), m.geometry.triangle_count()))
+            .collect();
+        assert_eq!(summary, vec![("cube", Some("sides"), 16, 8), ("cube", Some("caps"), 8, 4)]);
+
+        let (min, max) = model.bounds().unwrap();
+        assert_eq!((min, max), (Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.5, 0.5, 0.5)));
+
+        // Every triangle agrees with the normals written in the file
+        for mesh in &model.meshes {
+            let geometry = &mesh.geometry;
+            for triangle in geometry.indices.chunks(3) {
+                let [a, b, c] = [0, 1, 2].map(|k| geometry.positions[triangle[k] as usize]);
+                let normal = geometry.normals[triangle[0] as usize];
+                assert!((b - a).cross(c - a).dot(normal) > 0.0);
+            }
+        }
+        assert_eq!(model.meshes[1].geometry.uvs[2], [1.0, 1.0]);
+    }
+
+    #[test]
+    fn materials() {
+        let materials = parse_mtl(CUBE_MTL).unwrap();
+        assert_eq!(materials.len(), 2);
+        let sides = &materials["sides"];
+        assert_eq!(sides.diffuse, [0.8, 0.2, 0.1]);
+        assert_eq!(sides.opacity, 1.0);
+        assert_eq!(sides.diffuse_map.as_deref(), Some("textures/sides.png"));
+        let caps = &materials["caps"];
+        assert_eq!(caps.opacity, 0.75);
+        assert!(caps.diffuse_map.is_none());
+    }
+
+    #[test]
+    fn polygons_groups_and_relative_indices() {
+        let model = parse_obj(SHAPES_OBJ).unwrap();
+        assert_eq!(model.meshes.len(), 2);
+        let pentagon = &model.meshes[0];
+        assert_eq!((pentagon.group.as_str(), pentagon.material.as_ref()), ("pentagon", None));
+        assert_eq!(pentagon.geometry.indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+        // No vn in the file, so the normals come from the faces
+        assert!(pentagon.geometry.normals.iter().all(|n| *n == Vec3::Z));
+
+        let triangle = &model.meshes[1].geometry;
+        assert_eq!(triangle.positions, vec![Vec3::new(0.0, 0.0, 1.0), Vec3::X + Vec3::Z, Vec3::Y + Vec3::Z]);
+        assert_eq!(triangle.uvs, vec![[0.0, 0.0]; 3]);
+    }
+
+    #[test]
+    fn concave_faces() {
+        // An arrowhead with the notch at the bottom, which a fan from the first corner gets wrong
+        let model = parse_obj(b"v 0 0 0\nv 2 1 0\nv 4 0 0\nv 2 3 0\nf 1 2 3 4\n").unwrap();
+        let geometry = &model.meshes[0].geometry;
+        assert_eq!(geometry.indices, vec![1, 2, 3, 1, 3, 0]);
+        for triangle in geometry.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| geometry.positions[triangle[k] as usize]);
+            assert!((b - a).cross(c - a).z > 0.0);
+        }
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let err = parse_obj(b"v 0 0 0\nv 1 0 0\n\nf 1 2 3\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 4: index 3 out of range, 2 defined so far");
+        assert_eq!(parse_obj(b"v 0 zero 0").unwrap_err().message, "bad number 'zero'");
+        assert_eq!(parse_obj(b"v 0 0 0\nf 1 1").unwrap_err().message, "face with 2 vertices");
+        assert_eq!(parse_obj(b"v 0 0 0\nf 0 1 1").unwrap_err().line, 2);
+        assert_eq!(parse_mtl(b"Kd 1 1 1").unwrap_err().message, "'Kd' before any newmtl");
+    }
+}
@@ -0,0 +1,295 @@
+// Level of detail for point clouds too big to draw whole every frame. Each octree node keeps a
+// thinned sample of its points (at most one per cell of a grid³ over its cube) and hands the
+// rest down to its children, so drawing a node and its ancestors shows its part of the cloud at
+// that node's density. Points are reordered so every node's own points are one run, to be drawn
+// with a single draw_arrays each.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::math::{Mat4, Vec3, Vec4};
+use crate::point_cloud::PointCloud;
+
+// Deeper than this, coincident points just stay where they are
+pub const MAX_DEPTH: u32 = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OctreeNode {
+    // The node's cube
+    pub min: Vec3,
+    pub size: f32,
+    pub depth: u32,
+    // The node's own points, as a run of Octree::order
+    pub first: u32,
+    pub count: u32,
+    pub children: [Option<u32>; 8],
+}
+
+impl OctreeNode {
+    pub fn center(&self) -> Vec3 {
+        self.min + Vec3::ONE * (self.size * 0.5)
+    }
+
+    // Of the cube's bounding sphere
+    pub fn radius(&self) -> f32 {
+        self.size * 0.5 * 3f32.sqrt()
+    }
+}
+
+pub struct Octree {
+    nodes: Vec<OctreeNode>,
+    order: Vec<u32>,
+    grid: u32,
+}
+
+impl Octree {
+    // Nodes with more than `leaf_points` points split. Node 0 is the root and nodes come
+    // depth first, which is also the order of their point runs.
+    pub fn new(cloud: &PointCloud, leaf_points: usize) -> Self {
+        let leaf_points = leaf_points.max(1);
+        // Half the leaf size per node leaves room for what the cells don't take
+        let grid = ((leaf_points as f32 / 2.0).cbrt().ceil() as u32).max(1);
+        let mut octree = Octree {
+            nodes: Vec::new(),
+            order: Vec::with_capacity(cloud.len()),
+            grid,
+        };
+        if let Some((min, max)) = cloud.bounds() {
+            let extent = max - min;
+            // A little over the largest extent so points on the far faces still land inside
+            let size = extent.x.max(extent.y).max(extent.z).max(f32::EPSILON) * 1.0001;
+            let indices = (0..cloud.len() as u32).collect();
+            octree.build(&cloud.positions, min, size, 0, indices, leaf_points);
+        }
+        octree
+    }
+
+    fn build(&mut self, positions: &[Vec3], min: Vec3, size: f32, depth: u32, indices: Vec<u32>, leaf_points: usize) -> u32 {
+        let index = self.nodes.len() as u32;
+        let first = self.order.len() as u32;
+        let half = size * 0.5;
+        let mut octants: [Vec<u32>; 8] = Default::default();
+
+        if indices.len() <= leaf_points || depth == MAX_DEPTH {
+            self.order.extend(&indices);
+        } else {
+            let grid = self.grid as usize;
+            let mut taken = vec![false; grid * grid * grid];
+            let cell = |v: f32, min: f32| (((v - min) / size * grid as f32) as usize).min(grid - 1);
+            for i in indices {
+                let p = positions[i as usize];
+                let at = (cell(p.z, min.z) * grid + cell(p.y, min.y)) * grid + cell(p.x, min.x);
+                if !taken[at] {
+                    taken[at] = true;
+                    self.order.push(i);
+                } else {
+                    let octant = (p.x >= min.x + half) as usize | ((p.y >= min.y + half) as usize) << 1 | ((p.z >= min.z + half) as usize) << 2;
+                    octants[octant].push(i);
+                }
+            }
+        }
+
+        self.nodes.push(OctreeNode {
+            min,
+            size,
+            depth,
+            first,
+            count: self.order.len() as u32 - first,
+            children: [None; 8],
+        });
+        for (octant, indices) in octants.into_iter().enumerate() {
+            if indices.is_empty() {
+                continue;
+            }
+            let offset = Vec3::new((octant & 1) as f32, ((octant >> 1) & 1) as f32, (octant >> 2) as f32) * half;
+            let child = self.build(positions, min + offset, half, depth + 1, indices, leaf_points);
+            self.nodes[index as usize].children[octant] = Some(child);
+        }
+        index
+    }
+
+    pub fn nodes(&self) -> &[OctreeNode] {
+        &self.nodes
+    }
+
+    // The cloud's point indices in node order; upload the points in this order
+    pub fn order(&self) -> &[u32] {
+        &self.order
+    }
+
+    // Sample cells along each side of a node
+    pub fn grid(&self) -> u32 {
+        self.grid
+    }
+
+    // The nodes to draw this frame, biggest on screen first, holding no more than `budget`
+    // points. `pixels_per_unit` is how many pixels one unit spans one unit in front of the eye,
+    // projection[1][1] times half the viewport height. Nodes outside the frustum of
+    // `view_projection` are left out, and so are children whose parent is already as dense as
+    // the pixels it covers.
+    pub fn select(&self, view_projection: &Mat4, eye: Vec3, pixels_per_unit: f32, budget: usize) -> Vec<u32> {
+        let planes = frustum_planes(view_projection);
+        let mut selected = Vec::new();
+        let mut points = 0;
+        let mut queue = BinaryHeap::new();
+        if let Some(root) = self.nodes.first() {
+            if in_frustum(&planes, root) {
+                queue.push(Candidate { pixels: pixel_size(root, eye, pixels_per_unit), node: 0 });
+            }
+        }
+
+        while let Some(Candidate { pixels, node: index }) = queue.pop() {
+            let node = &self.nodes[index as usize];
+            if points + node.count as usize > budget {
+                // Something smaller may still fit
+                continue;
+            }
+            points += node.count as usize;
+            selected.push(index);
+            if pixels <= self.grid as f32 {
+                continue;
+            }
+            for &child in node.children.iter().flatten() {
+                let child_node = &self.nodes[child as usize];
+                if in_frustum(&planes, child_node) {
+                    queue.push(Candidate { pixels: pixel_size(child_node, eye, pixels_per_unit), node: child });
+                }
+            }
+        }
+        selected
+    }
+}
+
+// Roughly how many pixels across the node's cube is; the eye inside it counts as huge
+fn pixel_size(node: &OctreeNode, eye: Vec3, pixels_per_unit: f32) -> f32 {
+    let distance = (node.center() - eye).length() - node.radius();
+    if distance <= 0.0 {
+        return f32::MAX;
+    }
+    node.size * pixels_per_unit / distance
+}
+
+struct Candidate {
+    pixels: f32,
+    node: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    // Biggest first, then the earlier node
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.pixels.total_cmp(&other.pixels).then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+// Left, right, bottom, top, near, far as (a, b, c, d) with a·x + b·y + c·z + d >= 0 inside
+fn frustum_planes(m: &Mat4) -> [Vec4; 6] {
+    let w = m.row(3);
+    [w + m.row(0), w - m.row(0), w + m.row(1), w - m.row(1), w + m.row(2), w - m.row(2)]
+}
+
+// Outside only if the cube's corner furthest along some plane's normal is behind it
+fn in_frustum(planes: &[Vec4; 6], node: &OctreeNode) -> bool {
+    planes.iter().all(|plane| {
+        let corner = |normal: f32, min: f32| if normal >= 0.0 { min + node.size } else { min };
+        let p = Vec3::new(corner(plane.x, node.min.x), corner(plane.y, node.min.y), corner(plane.z, node.min.z));
+        plane.truncate().dot(p) + plane.w >= 0.0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A noisy sphere shell, the same every run
+    fn cloud(points: usize) -> PointCloud {
+        let mut seed = 12345u32;
+        let mut random = move || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1u32 << 24) as f32
+        };
+        let positions = (0..points)
+            .map(|_| {
+                let direction = Vec3::new(random() - 0.5, random() - 0.5, random() - 0.5).normalize();
+                direction * (1.0 + 0.05 * random())
+            })
+            .collect();
+        PointCloud { positions, colors: Vec::new() }
+    }
+
+    fn camera(eye: Vec3, target: Vec3) -> (Mat4, f32) {
+        let projection = Mat4::perspective(60.0, 1.0, 0.1, 100.0);
+        (projection * Mat4::look_at(eye, target, Vec3::Y), projection.get(1, 1) * 300.0)
+    }
+
+    #[test]
+    fn every_point_in_one_node() {
+        let cloud = cloud(20000);
+        let octree = Octree::new(&cloud, 500);
+        assert_eq!(octree.grid(), 7);
+
+        let mut order = octree.order().to_vec();
+        order.sort_unstable();
+        assert!(order.iter().enumerate().all(|(i, &p)| p == i as u32));
+
+        let mut next = 0;
+        for node in octree.nodes() {
+            // Runs follow each other in node order
+            assert_eq!(node.first, next);
+            next += node.count;
+            assert!(node.count as usize <= 500 || node.depth == MAX_DEPTH);
+            for &i in &octree.order()[node.first as usize..(node.first + node.count) as usize] {
+                let p = cloud.positions[i as usize] - node.min;
+                assert!([p.x, p.y, p.z].iter().all(|&v| (0.0..=node.size).contains(&v)));
+            }
+            for &child in node.children.iter().flatten() {
+                let child = &octree.nodes()[child as usize];
+                assert_eq!((child.depth, child.size), (node.depth + 1, node.size * 0.5));
+            }
+        }
+        assert_eq!(next as usize, cloud.len());
+        assert!(octree.nodes().len() > 8);
+
+        assert!(Octree::new(&PointCloud::default(), 500).nodes().is_empty());
+    }
+
+    #[test]
+    fn selection_keeps_to_the_budget() {
+        let octree = Octree::new(&cloud(20000), 500);
+        let points = |nodes: &[u32]| nodes.iter().map(|&n| octree.nodes()[n as usize].count as usize).sum::<usize>();
+        let eye = Vec3::new(0.0, 0.0, 4.0);
+        let (view_projection, pixels_per_unit) = camera(eye, Vec3::ZERO);
+
+        let small = octree.select(&view_projection, eye, pixels_per_unit, 3000);
+        assert_eq!(small[0], 0);
+        assert!(points(&small) <= 3000);
+        let large = octree.select(&view_projection, eye, pixels_per_unit, 1_000_000);
+        assert!(points(&large) > points(&small));
+        // Further away the same budget isn't needed
+        let far_eye = Vec3::new(0.0, 0.0, 40.0);
+        let (far, far_pixels) = camera(far_eye, Vec3::ZERO);
+        assert!(points(&octree.select(&far, far_eye, far_pixels, 1_000_000)) < points(&large));
+
+        // From the middle of the shell looking down +X, nothing wholly behind the eye is drawn
+        let (inside, inside_pixels) = camera(Vec3::ZERO, Vec3::X);
+        let ahead = octree.select(&inside, Vec3::ZERO, inside_pixels, 1_000_000);
+        assert!(ahead.len() > 1);
+        assert!(ahead.iter().map(|&n| &octree.nodes()[n as usize]).all(|n| n.min.x + n.size >= 0.0));
+
+        // The cloud moved behind the camera
+        let away = view_projection * Mat4::translation(Vec3::new(0.0, 0.0, 20.0));
+        assert!(octree.select(&away, eye, pixels_per_unit, 1_000_000).is_empty());
+    }
+}
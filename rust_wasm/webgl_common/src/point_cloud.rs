@@ -0,0 +1,469 @@
+// Point clouds from the bytes a page fetched: PLY (ASCII and both binary byte orders, colors from
+// red/green/blue/alpha if the vertices have them) and plain XYZ text. Faces and any other
+// elements and properties are skipped. See octree.rs for drawing big ones.
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::math::Vec3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PointCloudError {
+    Ply(String),
+    // 1-based
+    Xyz { line: usize, message: String },
+}
+
+impl fmt::Display for PointCloudError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointCloudError::Ply(message) => write!(f, "PLY: {}", message),
+            PointCloudError::Xyz { line, message } => write!(f, "XYZ line {}: {}", line, message),
+        }
+    }
+}
+
+impl From<PointCloudError> for JsValue {
+    fn from(err: PointCloudError) -> JsValue {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PointCloud {
+    pub positions: Vec<Vec3>,
+    // One per position, or none at all when the file has no colors
+    pub colors: Vec<[u8; 4]>,
+}
+
+impl PointCloud {
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        let first = *self.positions.first()?;
+        Some(self.positions.iter().fold((first, first), |(min, max), p| {
+            (
+                Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            )
+        }))
+    }
+}
+
+/*==================== PLY ====================*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Scalar::F32 | Scalar::F64)
+    }
+
+    // `bytes` holds exactly size() bytes
+    fn read(self, bytes: &[u8], big_endian: bool) -> f64 {
+        macro_rules! read {
+            ($ty:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                (if big_endian { <$ty>::from_be_bytes(bytes) } else { <$ty>::from_le_bytes(bytes) }) as f64
+            }};
+        }
+        match self {
+            Scalar::I8 => read!(i8),
+            Scalar::U8 => read!(u8),
+            Scalar::I16 => read!(i16),
+            Scalar::U16 => read!(u16),
+            Scalar::I32 => read!(i32),
+            Scalar::U32 => read!(u32),
+            Scalar::F32 => read!(f32),
+            Scalar::F64 => read!(f64),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Property {
+    Scalar(String, Scalar),
+    // Count type, item type
+    List(String, Scalar, Scalar),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+fn ply_error(message: impl Into<String>) -> PointCloudError {
+    PointCloudError::Ply(message.into())
+}
+
+// The elements and where the data after end_header starts
+fn parse_ply_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, usize), PointCloudError> {
+    if !bytes.starts_with(b"ply") {
+        return Err(ply_error("not a PLY file"));
+    }
+    let end = bytes
+        .windows(b"end_header".len())
+        .position(|w| w == b"end_header")
+        .ok_or_else(|| ply_error("no end_header"))?;
+    let data_start = bytes[end..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |i| end + i + 1);
+    let header = String::from_utf8_lossy(&bytes[..end]);
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in header.lines().skip(1) {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", name, ..] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(ply_error(format!("unknown format '{}'", name))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| ply_error(format!("bad element count '{}'", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let scalar = |name: &str| Scalar::from_name(name).ok_or_else(|| ply_error(format!("unknown property type '{}'", name)));
+                let property = Property::List(name.to_string(), scalar(count)?, scalar(item)?);
+                elements.last_mut().ok_or_else(|| ply_error("property before any element"))?.properties.push(property);
+            }
+            ["property", ty, name] => {
+                let ty = Scalar::from_name(ty).ok_or_else(|| ply_error(format!("unknown property type '{}'", ty)))?;
+                elements.last_mut().ok_or_else(|| ply_error("property before any element"))?.properties.push(Property::Scalar(name.to_string(), ty));
+            }
+            // comment, obj_info and blank lines
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| ply_error("no format line"))?;
+    Ok((format, elements, data_start))
+}
+
+// Where x, y, z and the color channels are among the vertex properties, and whether the colors
+// are 0 to 1 floats rather than 0 to 255
+struct VertexColumns {
+    position: [usize; 3],
+    color: Option<([usize; 3], Option<usize>, bool)>,
+}
+
+impl VertexColumns {
+    fn new(element: &Element) -> Result<Self, PointCloudError> {
+        let find = |names: &[&str]| {
+            element.properties.iter().position(|p| match p {
+                Property::Scalar(name, _) => names.contains(&name.as_str()),
+                Property::List(..) => false,
+            })
+        };
+        let position = match (find(&["x"]), find(&["y"]), find(&["z"])) {
+            (Some(x), Some(y), Some(z)) => [x, y, z],
+            _ => return Err(ply_error("vertex element without x, y and z")),
+        };
+        let color = match (find(&["red", "diffuse_red"]), find(&["green", "diffuse_green"]), find(&["blue", "diffuse_blue"])) {
+            (Some(r), Some(g), Some(b)) => {
+                let float = matches!(&element.properties[r], Property::Scalar(_, ty) if ty.is_float());
+                Some(([r, g, b], find(&["alpha", "diffuse_alpha"]), float))
+            }
+            _ => None,
+        };
+        Ok(VertexColumns { position, color })
+    }
+
+    fn push(&self, values: &[f64], cloud: &mut PointCloud) {
+        let [x, y, z] = self.position;
+        cloud.positions.push(Vec3::new(values[x] as f32, values[y] as f32, values[z] as f32));
+        if let Some(([r, g, b], a, float)) = self.color {
+            let scale = if float { 255.0 } else { 1.0 };
+            let channel = |i: usize| (values[i] * scale).round().clamp(0.0, 255.0) as u8;
+            cloud.colors.push([channel(r), channel(g), channel(b), a.map_or(255, channel)]);
+        }
+    }
+}
+
+pub fn parse_ply(bytes: &[u8]) -> Result<PointCloud, PointCloudError> {
+    let (format, elements, data_start) = parse_ply_header(bytes)?;
+    let vertex_at = elements.iter().position(|e| e.name == "vertex").ok_or_else(|| ply_error("no vertex element"))?;
+    let columns = VertexColumns::new(&elements[vertex_at])?;
+    let data = &bytes[data_start..];
+    // The header's count isn't trusted further than the bytes left could hold
+    let record_bytes: usize = elements[vertex_at]
+        .properties
+        .iter()
+        .map(|p| match (format, p) {
+            (Format::Ascii, _) => 2,
+            (_, Property::Scalar(_, ty)) => ty.size(),
+            (_, Property::List(_, count, _)) => count.size(),
+        })
+        .sum();
+    let vertex_count = elements[vertex_at].count.min(data.len() / record_bytes.max(1));
+    let mut cloud = PointCloud {
+        positions: Vec::with_capacity(vertex_count),
+        colors: Vec::with_capacity(if columns.color.is_some() { vertex_count } else { 0 }),
+    };
+    let mut values = vec![0.0; elements[vertex_at].properties.len()];
+
+    match format {
+        Format::Ascii => {
+            let text = String::from_utf8_lossy(data);
+            let mut tokens = text.split_ascii_whitespace();
+            let mut number = |what: &str| -> Result<f64, PointCloudError> {
+                let token = tokens.next().ok_or_else(|| ply_error(format!("data ends in the {} element", what)))?;
+                token.parse().map_err(|_| ply_error(format!("bad number '{}'", token)))
+            };
+            // Only the elements up to the vertices have to be read. Ones without properties take no
+            // data however many records they claim, so they are skipped rather than counted through.
+            for element in elements[..=vertex_at].iter().filter(|e| !e.properties.is_empty()) {
+                let is_vertex = element.name == "vertex";
+                for _ in 0..element.count {
+                    for (i, property) in element.properties.iter().enumerate() {
+                        match property {
+                            Property::Scalar(..) => {
+                                let value = number(&element.name)?;
+                                if is_vertex {
+                                    values[i] = value;
+                                }
+                            }
+                            Property::List(..) => {
+                                for _ in 0..number(&element.name)? as usize {
+                                    number(&element.name)?;
+                                }
+                            }
+                        }
+                    }
+                    if is_vertex {
+                        columns.push(&values, &mut cloud);
+                    }
+                }
+            }
+        }
+        Format::LittleEndian | Format::BigEndian => {
+            let big_endian = format == Format::BigEndian;
+            let mut offset: usize = 0;
+            let mut take = |size: usize, what: &str| -> Result<&[u8], PointCloudError> {
+                let end = offset.checked_add(size).ok_or_else(|| ply_error(format!("list too long in the {} element", what)))?;
+                let slice = data.get(offset..end).ok_or_else(|| ply_error(format!("data ends in the {} element", what)))?;
+                offset = end;
+                Ok(slice)
+            };
+            for element in elements[..=vertex_at].iter().filter(|e| !e.properties.is_empty()) {
+                let is_vertex = element.name == "vertex";
+                for _ in 0..element.count {
+                    for (i, property) in element.properties.iter().enumerate() {
+                        match *property {
+                            Property::Scalar(_, ty) => {
+                                let value = ty.read(take(ty.size(), &element.name)?, big_endian);
+                                if is_vertex {
+                                    values[i] = value;
+                                }
+                            }
+                            Property::List(_, count, item) => {
+                                let count = count.read(take(count.size(), &element.name)?, big_endian) as usize;
+                                let size = count.checked_mul(item.size()).ok_or_else(|| ply_error(format!("list too long in the {} element", element.name)))?;
+                                take(size, &element.name)?;
+                            }
+                        }
+                    }
+                    if is_vertex {
+                        columns.push(&values, &mut cloud);
+                    }
+                }
+            }
+        }
+    }
+    Ok(cloud)
+}
+
+/*==================== XYZ ====================*/
+// One point per line, "x y z", "x y z r g b" or "x y z intensity r g b", split by spaces, tabs or
+// commas; # starts a comment. Colors are 0 to 255 unless none of them is above 1.
+pub fn parse_xyz(bytes: &[u8]) -> Result<PointCloud, PointCloudError> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut cloud = PointCloud::default();
+    let mut colors: Vec<[f32; 3]> = Vec::new();
+    let mut numbers = Vec::with_capacity(7);
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        numbers.clear();
+        for token in line.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+            numbers.push(token.parse::<f32>().map_err(|_| PointCloudError::Xyz {
+                line: number + 1,
+                message: format!("bad number '{}'", token),
+            })?);
+        }
+        if numbers.is_empty() {
+            continue;
+        }
+        if numbers.len() < 3 {
+            return Err(PointCloudError::Xyz {
+                line: number + 1,
+                message: format!("expected x, y and z, found {} numbers", numbers.len()),
+            });
+        }
+        cloud.positions.push(Vec3::new(numbers[0], numbers[1], numbers[2]));
+        let rgb = match numbers.len() {
+            6 => Some(&numbers[3..6]),
+            7 => Some(&numbers[4..7]),
+            _ => None,
+        };
+        // Colors only count if every line has them
+        match rgb {
+            Some(rgb) if colors.len() + 1 == cloud.positions.len() => colors.push([rgb[0], rgb[1], rgb[2]]),
+            _ => colors.clear(),
+        }
+    }
+
+    if colors.len() == cloud.positions.len() && !colors.is_empty() {
+        let scale = if colors.iter().flatten().any(|&c| c > 1.0) { 1.0 } else { 255.0 };
+        cloud.colors = colors
+            .iter()
+            .map(|rgb| {
+                let [r, g, b] = rgb.map(|c| (c * scale).round().clamp(0.0, 255.0) as u8);
+                [r, g, b, 255]
+            })
+            .collect();
+    }
+    Ok(cloud)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_END: &str = "end_header\n";
+
+    fn binary_ply(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian { "binary_big_endian" } else { "binary_little_endian" };
+        let mut bytes = format!(
+            "ply\nformat {} 1.0\ncomment made by hand\nelement vertex 2\nproperty float x\nproperty float y\nproperty double z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\nelement face 1\nproperty list uchar int vertex_indices\n{}",
+            format, HEADER_END
+        )
+        .into_bytes();
+        for (x, y, z, rgb) in [(1.0f32, 2.0f32, 3.0f64, [255u8, 0, 10]), (-1.0, 0.5, 0.25, [1, 2, 3])] {
+            if big_endian {
+                bytes.extend(x.to_be_bytes().iter().chain(&y.to_be_bytes()).chain(&z.to_be_bytes()));
+            } else {
+                bytes.extend(x.to_le_bytes().iter().chain(&y.to_le_bytes()).chain(&z.to_le_bytes()));
+            }
+            bytes.extend_from_slice(&rgb);
+        }
+        bytes.push(3);
+        bytes.extend([0u8; 12]);
+        bytes
+    }
+
+    #[test]
+    fn ascii_ply() {
+        // Faces before the vertices have to be read past, list and all
+        let ply = b"ply\r\nformat ascii 1.0\r\nelement face 1\r\nproperty list uchar int vertex_indices\r\nelement vertex 3\r\nproperty float x\r\nproperty float y\r\nproperty float z\r\nproperty float nx\r\nend_header\r\n3 0 1 2\r\n0 0 0 1\r\n1 0 0 1\r\n0 1.5 -2 1\r\n";
+        let cloud = parse_ply(ply).unwrap();
+        assert_eq!(cloud.positions, vec![Vec3::ZERO, Vec3::X, Vec3::new(0.0, 1.5, -2.0)]);
+        assert!(cloud.colors.is_empty());
+        assert_eq!(cloud.bounds(), Some((Vec3::new(0.0, 0.0, -2.0), Vec3::new(1.0, 1.5, 0.0))));
+
+        // Float colors with alpha
+        let colored = b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nproperty float red\nproperty float green\nproperty float blue\nproperty float alpha\nend_header\n1 2 3 1 0.5 0 0.25\n";
+        assert_eq!(parse_ply(colored).unwrap().colors, vec![[255, 128, 0, 64]]);
+    }
+
+    #[test]
+    fn binary_ply_both_byte_orders() {
+        for big_endian in [false, true] {
+            let cloud = parse_ply(&binary_ply(big_endian)).unwrap();
+            assert_eq!(cloud.positions, vec![Vec3::new(1.0, 2.0, 3.0), Vec3::new(-1.0, 0.5, 0.25)]);
+            assert_eq!(cloud.colors, vec![[255, 0, 10, 255], [1, 2, 3, 255]]);
+        }
+    }
+
+    #[test]
+    fn ply_errors() {
+        assert_eq!(parse_ply(b"solid cube").unwrap_err(), ply_error("not a PLY file"));
+        assert_eq!(parse_ply(b"ply\nformat ascii 1.0\n").unwrap_err().to_string(), "PLY: no end_header");
+        assert_eq!(
+            parse_ply(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty half y\nend_header\n").unwrap_err(),
+            ply_error("unknown property type 'half'")
+        );
+        assert_eq!(
+            parse_ply(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\n1\n").unwrap_err(),
+            ply_error("vertex element without x, y and z")
+        );
+        // Cut off in the middle of the second vertex
+        let mut truncated = binary_ply(false);
+        let header = truncated.windows(HEADER_END.len()).position(|w| w == HEADER_END.as_bytes()).unwrap() + HEADER_END.len();
+        truncated.truncate(header + 15 + 4);
+        assert_eq!(parse_ply(&truncated).unwrap_err(), ply_error("data ends in the vertex element"));
+        // A count far past what the bytes hold is an error, not a huge allocation
+        for format in ["ascii", "binary_little_endian"] {
+            let huge = format!("ply\nformat {} 1.0\nelement vertex 4000000000\nproperty float x\nproperty float y\nproperty float z\nend_header\n", format);
+            assert_eq!(parse_ply(huge.as_bytes()).unwrap_err(), ply_error("data ends in the vertex element"));
+            // And so is one without properties, which isn't counted through record by record
+            let empty = format!("ply\nformat {} 1.0\nelement pad 18446744073709551615\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nend_header\n", format);
+            assert_eq!(parse_ply(empty.as_bytes()).unwrap_err(), ply_error("data ends in the vertex element"));
+        }
+    }
+
+    #[test]
+    fn xyz() {
+        let cloud = parse_xyz(b"# scan\n0 0 0 255 0 0\n1,2,3,0,128,255\n\n4\t5\t6 0.7 10 20 30\n").unwrap();
+        assert_eq!(cloud.positions, vec![Vec3::ZERO, Vec3::new(1.0, 2.0, 3.0), Vec3::new(4.0, 5.0, 6.0)]);
+        // The last line has an intensity column before its color
+        assert_eq!(cloud.colors, vec![[255, 0, 0, 255], [0, 128, 255, 255], [10, 20, 30, 255]]);
+
+        // 0 to 1 colors, and no colors when some lines lack them
+        assert_eq!(parse_xyz(b"0 0 0 1 0.5 0\n").unwrap().colors, vec![[255, 128, 0, 255]]);
+        assert!(parse_xyz(b"0 0 0 1 1 1\n1 1 1\n").unwrap().colors.is_empty());
+
+        let err = parse_xyz(b"0 0 0\n1 1\n").unwrap_err();
+        assert_eq!(err.to_string(), "XYZ line 2: expected x, y and z, found 2 numbers");
+        assert_eq!(parse_xyz(b"0 0 zero").unwrap_err(), PointCloudError::Xyz { line: 1, message: "bad number 'zero'".to_string() });
+    }
+}
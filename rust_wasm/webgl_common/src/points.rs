@@ -0,0 +1,197 @@
+// Point sprites: every point its own size and color, drawn square, round or soft-edged by
+// glsl/point.frag from gl_PointCoord. Sizes are in CSS pixels and are held to what the GL can
+// draw, which can be as little as 1 pixel.
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::WebGlRenderingContext;
+
+use crate::mesh::Mesh;
+use crate::program::{ProgramError, ShaderProgram};
+use crate::Vertex;
+
+type Gl = WebGlRenderingContext;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PointShape {
+    Square,
+    #[default]
+    Round,
+    // Fades out from the middle
+    Soft,
+}
+
+impl PointShape {
+    // "square", "round" or "soft"
+    pub fn from_name(name: &str) -> Option<PointShape> {
+        match name {
+            "square" => Some(PointShape::Square),
+            "round" => Some(PointShape::Round),
+            "soft" => Some(PointShape::Soft),
+            _ => None,
+        }
+    }
+
+    // u_shape in the shader
+    fn gl_value(self) -> i32 {
+        self as i32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[repr(C)]
+pub struct PointVertex {
+    pub position: [f32; 3],
+    // CSS pixels
+    pub size: f32,
+    #[vertex(normalized)]
+    pub color: [u8; 4],
+}
+
+// An attribute array from JS that is neither one value per point nor one for all of them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PointCountError {
+    pub attribute: &'static str,
+    pub found: usize,
+    pub points: usize,
+}
+
+impl fmt::Display for PointCountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} values for {} points", self.found, self.attribute, self.points)
+    }
+}
+
+impl From<PointCountError> for JsValue {
+    fn from(err: PointCountError) -> JsValue {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+// Flat arrays as JS has them: x, y, z per point, and a size and r, g, b, a (0 to 1) either per
+// point or once for all of them
+pub fn point_vertices(positions: &[f32], sizes: &[f32], colors: &[f32]) -> Result<Vec<PointVertex>, PointCountError> {
+    let points = positions.len() / 3;
+    if sizes.len() != points && sizes.len() != 1 {
+        return Err(PointCountError { attribute: "size", found: sizes.len(), points });
+    }
+    if colors.len() != 4 * points && colors.len() != 4 {
+        return Err(PointCountError { attribute: "color", found: colors.len() / 4, points });
+    }
+    let color = |i: usize| {
+        let rgba = if colors.len() == 4 { colors } else { &colors[4 * i..4 * i + 4] };
+        std::array::from_fn(|k| (rgba[k].clamp(0.0, 1.0) * 255.0).round() as u8)
+    };
+    Ok(positions
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(i, p)| PointVertex {
+            position: [p[0], p[1], p[2]],
+            size: if sizes.len() == 1 { sizes[0] } else { sizes[i] },
+            color: color(i),
+        })
+        .collect())
+}
+
+// ALIASED_POINT_SIZE_RANGE, in device pixels; (1, 1) if the GL won't say
+pub fn point_size_range(gl: &Gl) -> [f32; 2] {
+    gl.get_parameter(Gl::ALIASED_POINT_SIZE_RANGE)
+        .ok()
+        .and_then(|range| range.dyn_into::<js_sys::Float32Array>().ok())
+        .filter(|range| range.length() == 2)
+        .map_or([1.0, 1.0], |range| [range.get_index(0), range.get_index(1)])
+}
+
+// Points uploaded once and drawn with a program from builtin::point_programs
+pub struct PointSprites {
+    mesh: Mesh,
+    size_range: [f32; 2],
+}
+
+impl PointSprites {
+    pub fn new(gl: &Gl, points: &[PointVertex]) -> Self {
+        let mut mesh = Mesh::from_vertices::<_, u32>(gl, points, &[]);
+        mesh.set_mode(Gl::POINTS);
+        PointSprites {
+            mesh,
+            size_range: point_size_range(gl),
+        }
+    }
+
+    // The clamp sizes are held to, in device pixels
+    pub fn size_range(&self) -> [f32; 2] {
+        self.size_range
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    // `pixel_ratio` turns the CSS pixel sizes into device pixels; the matrices the program's
+    // variant needs are up to the caller
+    pub fn draw(&self, program: &ShaderProgram, shape: PointShape, pixel_ratio: f32) -> Result<(), ProgramError> {
+        self.bind(program, shape, pixel_ratio)?;
+        self.mesh.draw();
+        self.mesh.unbind(program);
+        Ok(())
+    }
+
+    // Only the given (first, count) runs of points, as an octree picks them
+    pub fn draw_ranges(&self, program: &ShaderProgram, shape: PointShape, pixel_ratio: f32, ranges: &[(u32, u32)]) -> Result<(), ProgramError> {
+        self.bind(program, shape, pixel_ratio)?;
+        for &(first, count) in ranges {
+            self.mesh.draw_range(first as i32, count as i32);
+        }
+        self.mesh.unbind(program);
+        Ok(())
+    }
+
+    fn bind(&self, program: &ShaderProgram, shape: PointShape, pixel_ratio: f32) -> Result<(), ProgramError> {
+        program.use_program();
+        program.set_i32("u_shape", shape.gl_value())?;
+        program.set_f32("u_pointScale", pixel_ratio)?;
+        program.set_vec2("u_sizeRange", self.size_range[0], self.size_range[1])?;
+        self.mesh.bind(program)
+    }
+
+    pub fn delete(self) {
+        self.mesh.delete();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_point_and_shared_values() {
+        let positions = [0.0, 0.0, 0.0, 1.0, 2.0, 3.0];
+        let points = point_vertices(&positions, &[4.0, 8.0], &[1.0, 0.0, 0.0, 1.0, 0.0, 0.5, 2.0, -1.0]).unwrap();
+        assert_eq!(
+            points,
+            vec![
+                PointVertex { position: [0.0, 0.0, 0.0], size: 4.0, color: [255, 0, 0, 255] },
+                // Colors are clamped to 0..1 before they become bytes
+                PointVertex { position: [1.0, 2.0, 3.0], size: 8.0, color: [0, 128, 255, 0] },
+            ]
+        );
+
+        let shared = point_vertices(&positions, &[5.0], &[0.0, 0.0, 0.0, 1.0]).unwrap();
+        assert!(shared.iter().all(|p| p.size == 5.0 && p.color == [0, 0, 0, 255]));
+
+        let err = point_vertices(&positions, &[1.0, 2.0, 3.0], &[0.0; 4]).unwrap_err();
+        assert_eq!(err.to_string(), "3 size values for 2 points");
+        assert_eq!(point_vertices(&positions, &[1.0], &[0.0; 12]).unwrap_err().attribute, "color");
+    }
+
+    #[test]
+    fn layout() {
+        let layout = PointVertex::layout();
+        assert_eq!(layout.stride(), 20);
+        let color = layout.get("color").unwrap();
+        assert_eq!((color.offset, color.gl_type, color.normalized), (16, Gl::UNSIGNED_BYTE, true));
+        assert_eq!(PointShape::from_name("soft"), Some(PointShape::Soft));
+        assert_eq!(PointShape::Soft.gl_value(), 2);
+    }
+}
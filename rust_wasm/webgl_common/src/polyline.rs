@@ -0,0 +1,279 @@
+// Lines wider than the 1 pixel LINES gets on most WebGL implementations. The CPU only lays out a
+// quad per segment, per join and per point for round joins and caps; glsl/polyline.vert moves
+// their corners out to the width in pixels and the fragment shader fades the last pixel.
+use web_sys::WebGlRenderingContext;
+
+use crate::math::{Vec3, Vec4};
+use crate::mesh::Mesh;
+use crate::program::{ProgramError, ShaderProgram};
+use crate::Vertex;
+
+type Gl = WebGlRenderingContext;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Join {
+    #[default]
+    Miter,
+    Bevel,
+    Round,
+}
+
+impl Join {
+    // "miter", "bevel" or "round"
+    pub fn from_name(name: &str) -> Option<Join> {
+        match name {
+            "miter" => Some(Join::Miter),
+            "bevel" => Some(Join::Bevel),
+            "round" => Some(Join::Round),
+            _ => None,
+        }
+    }
+
+    // u_join in the shader
+    fn gl_value(self) -> i32 {
+        self as i32
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cap {
+    // Ends at the end point
+    #[default]
+    Butt,
+    // Half the width past the end point
+    Square,
+    Round,
+}
+
+impl Cap {
+    // "butt", "square" or "round"
+    pub fn from_name(name: &str) -> Option<Cap> {
+        match name {
+            "butt" => Some(Cap::Butt),
+            "square" => Some(Cap::Square),
+            "round" => Some(Cap::Round),
+            _ => None,
+        }
+    }
+
+    // u_cap in the shader
+    fn gl_value(self) -> i32 {
+        self as i32
+    }
+}
+
+// Uniforms, so they can change without rebuilding the line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PolylineStyle {
+    // In pixels of the drawing buffer
+    pub width: f32,
+    pub color: Vec4,
+    pub join: Join,
+    pub cap: Cap,
+    // Miter length over line width past which a miter join is drawn as a bevel
+    pub miter_limit: f32,
+}
+
+impl Default for PolylineStyle {
+    fn default() -> Self {
+        PolylineStyle {
+            width: 1.0,
+            color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            join: Join::Miter,
+            cap: Cap::Butt,
+            miter_limit: 4.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+#[repr(C)]
+pub struct PolylineVertex {
+    // The point the quad hangs off and its neighbours on the line, the point itself at the ends
+    pub prev: [f32; 3],
+    pub point: [f32; 3],
+    pub next: [f32; 3],
+    // Piece, two corner values and flags, read by polyline.vert
+    pub corner: [f32; 4],
+}
+
+// Pieces, corner.x in the shader
+const SEGMENT: f32 = 0.0;
+const JOIN: f32 = 1.0;
+const DISC: f32 = 2.0;
+
+// Segment flags for the first and the last segment of an open line
+const START: f32 = 1.0;
+const END: f32 = 2.0;
+
+const QUAD: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+// The quads for a line through `points`; a closed line also runs from the last point back to the
+// first and has joins all round instead of caps. Repeated points are skipped.
+pub fn polyline_vertices(points: &[Vec3], closed: bool) -> (Vec<PolylineVertex>, Vec<u32>) {
+    let mut points = points.to_vec();
+    points.dedup();
+    if closed && points.len() > 2 && points.first() == points.last() {
+        points.pop();
+    }
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let n = points.len();
+    if n < 2 {
+        return (vertices, indices);
+    }
+    let closed = closed && n > 2;
+    let at = |i: isize| {
+        if closed {
+            Some(points[i.rem_euclid(n as isize) as usize])
+        } else {
+            usize::try_from(i).ok().and_then(|i| points.get(i).copied())
+        }
+    };
+    let mut quad = |prev: Vec3, point: Vec3, next: Vec3, corners: [[f32; 4]; 4]| {
+        let base = vertices.len() as u32;
+        for corner in corners {
+            vertices.push(PolylineVertex {
+                prev: prev.to_array(),
+                point: point.to_array(),
+                next: next.to_array(),
+                corner,
+            });
+        }
+        indices.extend(QUAD.iter().map(|i| base + i));
+    };
+
+    let segments = if closed { n } else { n - 1 };
+    for (i, &point) in points.iter().enumerate().take(segments) {
+        let next = at(i as isize + 1).unwrap();
+        let prev = at(i as isize - 1).unwrap_or(point);
+        let mut flags = 0.0;
+        if !closed && i == 0 {
+            flags += START;
+        }
+        if !closed && i + 1 == segments {
+            flags += END;
+        }
+        quad(prev, point, next, [[SEGMENT, 0.0, -1.0, flags], [SEGMENT, 1.0, -1.0, flags], [SEGMENT, 1.0, 1.0, flags], [SEGMENT, 0.0, 1.0, flags]]);
+    }
+
+    for (i, &point) in points.iter().enumerate() {
+        let (prev, next) = (at(i as isize - 1), at(i as isize + 1));
+        // Disc flag 1 makes it a cap rather than a round join
+        let cap = if prev.is_some() && next.is_some() { 0.0 } else { 1.0 };
+        let (prev, next) = (prev.unwrap_or(point), next.unwrap_or(point));
+        if cap == 0.0 {
+            quad(prev, point, next, [[JOIN, 0.0, 0.0, 0.0], [JOIN, 1.0, 0.0, 0.0], [JOIN, 2.0, 0.0, 0.0], [JOIN, 3.0, 0.0, 0.0]]);
+        }
+        quad(prev, point, next, [[DISC, -1.0, -1.0, cap], [DISC, 1.0, -1.0, cap], [DISC, 1.0, 1.0, cap], [DISC, -1.0, 1.0, cap]]);
+    }
+    (vertices, indices)
+}
+
+// A line uploaded once and drawn with a program from builtin::polyline_programs
+pub struct Polyline {
+    mesh: Mesh,
+}
+
+impl Polyline {
+    pub fn new(gl: &Gl, points: &[Vec3], closed: bool) -> Self {
+        let (vertices, indices) = polyline_vertices(points, closed);
+        Polyline {
+            mesh: Mesh::from_vertices(gl, &vertices, &indices),
+        }
+    }
+
+    // Separate lines in one mesh, e.g. the subpaths of an SVG path; each is (points, closed)
+    pub fn from_lines(gl: &Gl, lines: &[(Vec<Vec3>, bool)]) -> Self {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (points, closed) in lines {
+            let (line_vertices, line_indices) = polyline_vertices(points, *closed);
+            let base = vertices.len() as u32;
+            indices.extend(line_indices.iter().map(|i| base + i));
+            vertices.extend(line_vertices);
+        }
+        Polyline {
+            mesh: Mesh::from_vertices(gl, &vertices, &indices),
+        }
+    }
+
+    // Sets the style and draws; the matrices the program's variant needs are up to the caller.
+    // `resolution` is the drawing buffer size, e.g. (surface.width(), surface.height()).
+    pub fn draw(&self, program: &ShaderProgram, style: &PolylineStyle, resolution: (u32, u32)) -> Result<(), ProgramError> {
+        program.use_program();
+        program.set_vec2("u_resolution", resolution.0 as f32, resolution.1 as f32)?;
+        program.set_f32("u_width", style.width)?;
+        program.set_f32("u_miterLimit", style.miter_limit)?;
+        program.set_i32("u_join", style.join.gl_value())?;
+        program.set_i32("u_cap", style.cap.gl_value())?;
+        program.set_vec4("u_color", style.color)?;
+        self.mesh.bind(program)?;
+        self.mesh.draw();
+        self.mesh.unbind(program);
+        Ok(())
+    }
+
+    pub fn delete(self) {
+        self.mesh.delete();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(vertices: &[PolylineVertex], piece: f32) -> Vec<&PolylineVertex> {
+        // One entry per quad
+        vertices.iter().step_by(4).filter(|v| v.corner[0] == piece).collect()
+    }
+
+    #[test]
+    fn open_line_quads() {
+        let points = [Vec3::ZERO, Vec3::X, Vec3::X, Vec3::new(1.0, 1.0, 0.0)];
+        let (vertices, indices) = polyline_vertices(&points, false);
+        // The repeated point is dropped: 2 segments, 1 join, 3 discs
+        assert_eq!(vertices.len(), 6 * 4);
+        assert_eq!(indices.len(), 6 * 6);
+        assert_eq!(&indices[6..12], &[4, 5, 6, 4, 6, 7]);
+
+        let segments = pieces(&vertices, SEGMENT);
+        assert_eq!(segments.iter().map(|v| v.corner[3]).collect::<Vec<_>>(), vec![START, END]);
+        // The first segment starts without a point before it
+        assert_eq!(segments[0].prev, segments[0].point);
+        assert_eq!(segments[1].prev, [0.0, 0.0, 0.0]);
+
+        let joins = pieces(&vertices, JOIN);
+        assert_eq!(joins.len(), 1);
+        assert_eq!((joins[0].prev, joins[0].point, joins[0].next), ([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]));
+
+        let caps: Vec<f32> = pieces(&vertices, DISC).iter().map(|v| v.corner[3]).collect();
+        assert_eq!(caps, vec![1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn closed_line_has_no_caps() {
+        let square = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y, Vec3::ZERO];
+        let (vertices, _) = polyline_vertices(&square, true);
+        assert_eq!(pieces(&vertices, SEGMENT).len(), 4);
+        assert_eq!(pieces(&vertices, JOIN).len(), 4);
+        assert!(pieces(&vertices, SEGMENT).iter().all(|v| v.corner[3] == 0.0));
+        assert!(pieces(&vertices, DISC).iter().all(|v| v.corner[3] == 0.0));
+        // The last segment closes the loop
+        assert_eq!(pieces(&vertices, SEGMENT)[3].next, [0.0, 0.0, 0.0]);
+
+        // Too short to close, or to draw at all
+        assert_eq!(pieces(&polyline_vertices(&square[..2], true).0, SEGMENT).len(), 1);
+        assert!(polyline_vertices(&[Vec3::X, Vec3::X], false).0.is_empty());
+    }
+
+    #[test]
+    fn style_names() {
+        assert_eq!(Join::from_name("bevel"), Some(Join::Bevel));
+        assert_eq!(Cap::from_name("square"), Some(Cap::Square));
+        assert_eq!(Cap::from_name("miter"), None);
+        assert_eq!((Join::Round.gl_value(), Cap::Round.gl_value()), (2, 2));
+        assert_eq!(PolylineVertex::layout().stride(), 52);
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+// Feature switches a shader source can be built with. Each set flag becomes a `#define`
+// in front of the source, so one file covers every permutation with #ifdef blocks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Variant(u32);
+
+impl Variant {
+    pub const NONE: Variant = Variant(0);
+    // Per-vertex `color` attribute passed on to the fragment shader
+    pub const VERTEX_COLOR: Variant = Variant(1);
+    // Pmatrix * Vmatrix * Mmatrix
+    pub const USE_MVP: Variant = Variant(1 << 1);
+    // A single u_xformMatrix
+    pub const USE_TRANSFORM: Variant = Variant(1 << 2);
+    // gl_PointSize from the u_pointSize uniform
+    pub const POINT_SIZE: Variant = Variant(1 << 3);
+    // Diffuse light from the `normal` attribute
+    pub const NORMAL_SHADING: Variant = Variant(1 << 4);
+    // Color multiplied by u_texture at the `uv` attribute
+    pub const TEXTURE: Variant = Variant(1 << 5);
+    // Edges drawn over the fill from the `barycentric` attribute, see wireframe.rs
+    pub const WIREFRAME: Variant = Variant(1 << 6);
+
+    const NAMES: [(Variant, &'static str); 7] = [
+        (Variant::VERTEX_COLOR, "VERTEX_COLOR"),
+        (Variant::USE_MVP, "USE_MVP"),
+        (Variant::USE_TRANSFORM, "USE_TRANSFORM"),
+        (Variant::POINT_SIZE, "POINT_SIZE"),
+        (Variant::NORMAL_SHADING, "NORMAL_SHADING"),
+        (Variant::TEXTURE, "TEXTURE"),
+        (Variant::WIREFRAME, "WIREFRAME"),
+    ];
+
+    pub fn contains(self, other: Variant) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // Names of the set flags, in a fixed order
+    pub fn defines(self) -> Vec<&'static str> {
+        Variant::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl BitOr for Variant {
+    type Output = Variant;
+
+    fn bitor(self, rhs: Variant) -> Variant {
+        Variant(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Variant {
+    fn bitor_assign(&mut self, rhs: Variant) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PreprocessError {
+    UnknownSnippet(String),
+    // The include chain that leads back to itself, e.g. ["a", "b", "a"]
+    IncludeCycle(Vec<String>),
+    MalformedInclude(String),
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessError::UnknownSnippet(name) => write!(f, "#include of unknown snippet '{}'", name),
+            PreprocessError::IncludeCycle(chain) => write!(f, "#include cycle: {}", chain.join(" -> ")),
+            PreprocessError::MalformedInclude(line) => write!(f, "malformed #include: {}", line),
+        }
+    }
+}
+
+// Resolves `#include "name"` against registered snippets and puts `#define`s in front of
+// the source. Everything else (#ifdef, #if defined(..), macros) is left to the GLSL compiler.
+#[derive(Clone, Debug, Default)]
+pub struct Preprocessor {
+    snippets: HashMap<String, String>,
+    defines: Vec<(String, String)>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Preprocessor::default()
+    }
+
+    pub fn add_snippet(&mut self, name: &str, source: &str) {
+        self.snippets.insert(name.to_string(), source.to_string());
+    }
+
+    // Injected into every source this preprocessor handles; an empty value gives a plain `#define NAME`
+    pub fn define(&mut self, name: &str, value: &str) {
+        match self.defines.iter_mut().find(|(n, _)| n == name) {
+            Some(define) => define.1 = value.to_string(),
+            None => self.defines.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn process(&self, source: &str, variant: Variant) -> Result<String, PreprocessError> {
+        self.process_mapped("", source, variant).map(|(code, _)| code)
+    }
+
+    // Like process(), plus where every line of the result came from: `name` for the lines of
+    // `source` and the snippet name for included ones
+    pub fn process_mapped(&self, name: &str, source: &str, variant: Variant) -> Result<(String, SourceMap), PreprocessError> {
+        let mut map = SourceMap::default();
+        let mut body = Vec::new();
+        self.expand(name, source, &mut Vec::new(), &mut body, &mut map)?;
+
+        let mut header = Vec::new();
+        for name in variant.defines() {
+            header.push(format!("#define {}", name));
+        }
+        for (name, value) in &self.defines {
+            if value.is_empty() {
+                header.push(format!("#define {}", name));
+            } else {
+                header.push(format!("#define {} {}", name, value));
+            }
+        }
+
+        // #version has to stay the first line, the defines go right after it
+        let version = body.iter().position(|(line, _): &(&str, _)| !line.trim().is_empty()).filter(|&i| body[i].0.trim_start().starts_with("#version"));
+        let split = version.map_or(0, |i| i + 1);
+        let mut code = String::new();
+        let lines = body[..split]
+            .iter()
+            .map(|&(line, at)| (line, Some(at)))
+            .chain(header.iter().map(|line| (line.as_str(), None)))
+            .chain(body[split..].iter().map(|&(line, at)| (line, Some(at))));
+        for (line, at) in lines {
+            code.push_str(line);
+            code.push('\n');
+            map.lines.push(at);
+        }
+        Ok((code, map))
+    }
+
+    fn expand<'a>(
+        &'a self,
+        name: &str,
+        source: &'a str,
+        stack: &mut Vec<String>,
+        out: &mut Vec<(&'a str, (usize, usize))>,
+        map: &mut SourceMap,
+    ) -> Result<(), PreprocessError> {
+        let file = map.file(name, source);
+        for (number, line) in source.lines().enumerate() {
+            let name = match line.trim().strip_prefix("#include") {
+                Some(rest) => include_name(rest).ok_or_else(|| PreprocessError::MalformedInclude(line.trim().to_string()))?,
+                None => {
+                    out.push((line, (file, number + 1)));
+                    continue;
+                }
+            };
+
+            if stack.iter().any(|n| n == name) {
+                let mut chain = stack.clone();
+                chain.push(name.to_string());
+                return Err(PreprocessError::IncludeCycle(chain));
+            }
+            let snippet = self.snippets.get(name).ok_or_else(|| PreprocessError::UnknownSnippet(name.to_string()))?;
+
+            stack.push(name.to_string());
+            self.expand(name, snippet, stack, out, map)?;
+            stack.pop();
+        }
+        Ok(())
+    }
+}
+
+// Where each line of a processed source came from, so a driver's line numbers can be taken back
+// to the file and line that were written
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    // Name and text of the source and of each snippet it included
+    files: Vec<(String, String)>,
+    // Per processed line, the file and 1-based line in it; None for the injected #defines
+    lines: Vec<Option<(usize, usize)>>,
+}
+
+impl SourceMap {
+    fn file(&mut self, name: &str, source: &str) -> usize {
+        match self.files.iter().position(|(n, _)| n == name) {
+            Some(index) => index,
+            None => {
+                self.files.push((name.to_string(), source.to_string()));
+                self.files.len() - 1
+            }
+        }
+    }
+
+    // File name, its text and the line there for a 1-based line of the processed source
+    pub fn locate(&self, line: usize) -> Option<(&str, &str, usize)> {
+        let (file, line) = (*self.lines.get(line.checked_sub(1)?)?)?;
+        let (name, text) = &self.files[file];
+        Some((name, text, line))
+    }
+}
+
+// `"name"` or `<name>`
+fn include_name(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    let name = rest
+        .strip_prefix('"')
+        .and_then(|r| r.strip_suffix('"'))
+        .or_else(|| rest.strip_prefix('<').and_then(|r| r.strip_suffix('>')))?;
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_expand_recursively() {
+        let mut pre = Preprocessor::new();
+        pre.add_snippet("inner", "float inner() { return 1.0; }");
+        pre.add_snippet("outer", "#include <inner>\nfloat outer() { return inner(); }");
+
+        let out = pre.process("  #include \"outer\"\nvoid main(void) {}", Variant::NONE).unwrap();
+        assert_eq!(out, "float inner() { return 1.0; }\nfloat outer() { return inner(); }\nvoid main(void) {}\n");
+    }
+
+    #[test]
+    fn include_errors() {
+        let mut pre = Preprocessor::new();
+        assert_eq!(pre.process("#include \"missing\"", Variant::NONE), Err(PreprocessError::UnknownSnippet("missing".to_string())));
+        assert!(matches!(pre.process("#include missing", Variant::NONE), Err(PreprocessError::MalformedInclude(_))));
+
+        pre.add_snippet("a", "#include \"b\"");
+        pre.add_snippet("b", "#include \"a\"");
+        let err = pre.process("#include \"a\"", Variant::NONE).unwrap_err();
+        assert_eq!(err.to_string(), "#include cycle: a -> b -> a");
+    }
+
+    #[test]
+    fn the_same_snippet_can_be_included_twice() {
+        let mut pre = Preprocessor::new();
+        pre.add_snippet("x", "x");
+        assert_eq!(pre.process("#include \"x\"\n#include \"x\"", Variant::NONE).unwrap(), "x\nx\n");
+    }
+
+    #[test]
+    fn defines_go_in_front_but_after_version() {
+        let mut pre = Preprocessor::new();
+        pre.define("FLAT_COLOR", "vec4(1.0)");
+        pre.define("DEBUG", "");
+        pre.define("FLAT_COLOR", "vec4(0.5)");
+
+        let variant = Variant::USE_MVP | Variant::VERTEX_COLOR;
+        let out = pre.process("void main(void) {}", variant).unwrap();
+        assert_eq!(out, "#define VERTEX_COLOR\n#define USE_MVP\n#define FLAT_COLOR vec4(0.5)\n#define DEBUG\nvoid main(void) {}\n");
+
+        let out = pre.process("\n#version 100\nvoid main(void) {}", Variant::POINT_SIZE).unwrap();
+        assert_eq!(out, "\n#version 100\n#define POINT_SIZE\n#define FLAT_COLOR vec4(0.5)\n#define DEBUG\nvoid main(void) {}\n");
+    }
+
+    #[test]
+    fn lines_map_back_to_their_files() {
+        let mut pre = Preprocessor::new();
+        pre.add_snippet("helpers", "float one() {\n    return 1.0;\n}");
+        pre.define("DEBUG", "");
+        let source = "#version 100\n#include \"helpers\"\nvoid main(void) {\n    bad;\n}";
+        let (code, map) = pre.process_mapped("main.frag", source, Variant::USE_MVP).unwrap();
+
+        let lines: Vec<&str> = code.lines().collect();
+        assert_eq!(lines[..3], ["#version 100", "#define USE_MVP", "#define DEBUG"]);
+        assert_eq!(map.locate(1).map(|(name, _, line)| (name, line)), Some(("main.frag", 1)));
+        // The defines come from no file
+        assert_eq!(map.locate(2), None);
+        assert_eq!(map.locate(5).map(|(name, _, line)| (name, line)), Some(("helpers", 2)));
+        let bad = lines.iter().position(|l| l.contains("bad")).unwrap() + 1;
+        let (name, text, line) = map.locate(bad).unwrap();
+        assert_eq!((name, line), ("main.frag", 4));
+        assert_eq!(text.lines().nth(line - 1), Some("    bad;"));
+        assert_eq!(map.locate(0), None);
+        assert_eq!(map.locate(99), None);
+    }
+
+    #[test]
+    fn variant_flags() {
+        let mut variant = Variant::NONE;
+        assert!(variant.defines().is_empty());
+        variant |= Variant::POINT_SIZE;
+        assert!(variant.contains(Variant::POINT_SIZE));
+        assert!(!variant.contains(Variant::USE_MVP));
+        assert_eq!((variant | Variant::USE_TRANSFORM).defines(), vec!["USE_TRANSFORM", "POINT_SIZE"]);
+    }
+}
@@ -0,0 +1,293 @@
+// Generators for common shapes, centered on the origin with Y up. Triangles are counter-clockwise
+// seen from outside, and the uv seams have their vertices doubled. Segment counts below the
+// minimum a shape needs are raised to it.
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use crate::geometry::Geometry;
+use crate::math::Vec3;
+
+// (columns + 1) x (rows + 1) vertices from `vertex(i, j)`, joined into quads. The surface has to
+// face the way the i direction crossed with the j direction points. Zero-area triangles, where
+// a row is squeezed into a pole or apex, are left out.
+fn grid(geometry: &mut Geometry, columns: u32, rows: u32, mut vertex: impl FnMut(u32, u32) -> (Vec3, Vec3, [f32; 2])) {
+    let base = geometry.vertex_count() as u32;
+    for j in 0..=rows {
+        for i in 0..=columns {
+            let (position, normal, uv) = vertex(i, j);
+            geometry.push_vertex(position, normal, uv);
+        }
+    }
+    let index = |i: u32, j: u32| base + j * (columns + 1) + i;
+    for j in 0..rows {
+        for i in 0..columns {
+            let (a, b, c, d) = (index(i, j), index(i + 1, j), index(i + 1, j + 1), index(i, j + 1));
+            for [a, b, c] in [[a, b, c], [a, c, d]] {
+                if !is_sliver(geometry, a, b, c) {
+                    geometry.push_triangle(a, b, c);
+                }
+            }
+        }
+    }
+}
+
+// Tiny next to its longest edge, so it still counts when sin(PI) leaves the pole a float step off
+fn is_sliver(geometry: &Geometry, a: u32, b: u32, c: u32) -> bool {
+    let [a, b, c] = [a, b, c].map(|i| geometry.positions[i as usize]);
+    let longest = (b - a).length().max((c - b).length()).max((a - c).length());
+    (b - a).cross(c - a).length() <= longest * longest * 1e-5
+}
+
+// Around the Y axis; i = 0 and i = segments land on the same seam
+fn around(i: u32, segments: u32) -> (f32, f32) {
+    let angle = i as f32 / segments as f32 * TAU;
+    (angle.cos(), -angle.sin())
+}
+
+// A flat disc at height `y` facing up or down, as a fan around a center vertex
+fn disc(geometry: &mut Geometry, radius: f32, y: f32, segments: u32, up: bool) {
+    let normal = if up { Vec3::Y } else { -Vec3::Y };
+    let center = geometry.push_vertex(Vec3::new(0.0, y, 0.0), normal, [0.5, 0.5]);
+    for i in 0..=segments {
+        let (x, z) = around(i, segments);
+        geometry.push_vertex(Vec3::new(x * radius, y, z * radius), normal, [0.5 + x * 0.5, 0.5 - z * 0.5]);
+    }
+    for i in 0..segments {
+        let (a, b) = (center + 1 + i, center + 2 + i);
+        if up {
+            geometry.push_triangle(center, a, b);
+        } else {
+            geometry.push_triangle(center, b, a);
+        }
+    }
+}
+
+// A size x size x size box, each face split into segments x segments quads.
+// Faces come in the order -Z, +Z, -X, +X, -Y, +Y with (segments + 1)^2 vertices each.
+pub fn cube(size: f32, segments: u32) -> Geometry {
+    let segments = segments.max(1);
+    let half = size * 0.5;
+    // Normal, then the u and v directions across the face
+    let faces = [
+        (-Vec3::Z, -Vec3::X, Vec3::Y),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (-Vec3::X, Vec3::Z, Vec3::Y),
+        (Vec3::X, -Vec3::Z, Vec3::Y),
+        (-Vec3::Y, Vec3::X, Vec3::Z),
+        (Vec3::Y, Vec3::X, -Vec3::Z),
+    ];
+    let mut geometry = Geometry::new();
+    for (normal, u_dir, v_dir) in faces {
+        grid(&mut geometry, segments, segments, |i, j| {
+            let (u, v) = (i as f32 / segments as f32, j as f32 / segments as f32);
+            let position = (normal + u_dir * (u * 2.0 - 1.0) + v_dir * (v * 2.0 - 1.0)) * half;
+            (position, normal, [u, v])
+        });
+    }
+    geometry
+}
+
+// In the XZ plane facing +Y, v running towards -Z
+pub fn plane(width: f32, depth: f32, x_segments: u32, z_segments: u32) -> Geometry {
+    let (x_segments, z_segments) = (x_segments.max(1), z_segments.max(1));
+    let mut geometry = Geometry::new();
+    grid(&mut geometry, x_segments, z_segments, |i, j| {
+        let (u, v) = (i as f32 / x_segments as f32, j as f32 / z_segments as f32);
+        let position = Vec3::new((u - 0.5) * width, 0.0, (0.5 - v) * depth);
+        (position, Vec3::Y, [u, v])
+    });
+    geometry
+}
+
+// A UV sphere with `segments` around and `rings` from pole to pole
+pub fn sphere(radius: f32, segments: u32, rings: u32) -> Geometry {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let mut geometry = Geometry::new();
+    grid(&mut geometry, segments, rings, |i, j| {
+        let (x, z) = around(i, segments);
+        // From the south pole up
+        let theta = j as f32 / rings as f32 * PI;
+        let normal = Vec3::new(x * theta.sin(), -theta.cos(), z * theta.sin());
+        (normal * radius, normal, [i as f32 / segments as f32, j as f32 / rings as f32])
+    });
+    geometry
+}
+
+// Side from `bottom_radius` at -height/2 to `top_radius` at +height/2, with caps where the radius isn't 0
+fn frustum(bottom_radius: f32, top_radius: f32, height: f32, segments: u32) -> Geometry {
+    let segments = segments.max(3);
+    let half = height * 0.5;
+    let mut geometry = Geometry::new();
+    grid(&mut geometry, segments, 1, |i, j| {
+        let (x, z) = around(i, segments);
+        let (radius, y) = if j == 0 { (bottom_radius, -half) } else { (top_radius, half) };
+        // Tilted by the slope of the side
+        let normal = Vec3::new(x * height, bottom_radius - top_radius, z * height).normalize();
+        (Vec3::new(x * radius, y, z * radius), normal, [i as f32 / segments as f32, j as f32])
+    });
+    if bottom_radius > 0.0 {
+        disc(&mut geometry, bottom_radius, -half, segments, false);
+    }
+    if top_radius > 0.0 {
+        disc(&mut geometry, top_radius, half, segments, true);
+    }
+    geometry
+}
+
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> Geometry {
+    frustum(radius, radius, height, segments)
+}
+
+// Base at -height/2, apex at +height/2
+pub fn cone(radius: f32, height: f32, segments: u32) -> Geometry {
+    frustum(radius, 0.0, height, segments)
+}
+
+// A ring of `radius` around Y with a tube of `tube_radius`
+pub fn torus(radius: f32, tube_radius: f32, segments: u32, tube_segments: u32) -> Geometry {
+    let (segments, tube_segments) = (segments.max(3), tube_segments.max(3));
+    let mut geometry = Geometry::new();
+    grid(&mut geometry, segments, tube_segments, |i, j| {
+        let (x, z) = around(i, segments);
+        // From the outer equator over the top and back
+        let angle = j as f32 / tube_segments as f32 * TAU;
+        let normal = Vec3::new(x * angle.cos(), angle.sin(), z * angle.cos());
+        let center = Vec3::new(x * radius, 0.0, z * radius);
+        let uv = [i as f32 / segments as f32, j as f32 / tube_segments as f32];
+        (center + normal * tube_radius, normal, uv)
+    });
+    geometry
+}
+
+// A cylinder of `height` with a hemisphere on each end, so it's height + 2 * radius tall.
+// `rings` is per hemisphere.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Geometry {
+    let (segments, rings) = (segments.max(3), rings.max(1));
+    let half = height * 0.5;
+    // v follows the length of the outline
+    let length = PI * radius + height;
+    let mut geometry = Geometry::new();
+    grid(&mut geometry, segments, 2 * rings + 1, |i, j| {
+        let (x, z) = around(i, segments);
+        // The bottom hemisphere ends and the top one starts at the equator, the row between is the cylinder
+        let (theta, y) = if j <= rings {
+            (j as f32 / rings as f32 * FRAC_PI_2, -half)
+        } else {
+            (FRAC_PI_2 + (j - rings - 1) as f32 / rings as f32 * FRAC_PI_2, half)
+        };
+        let normal = Vec3::new(x * theta.sin(), -theta.cos(), z * theta.sin());
+        let along = radius * theta + if j > rings { height } else { 0.0 };
+        let v = if length > 0.0 { along / length } else { 0.0 };
+        (Vec3::new(0.0, y, 0.0) + normal * radius, normal, [i as f32 / segments as f32, v])
+    });
+    geometry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face_normal(geometry: &Geometry, triangle: &[u32]) -> (Vec3, Vec3) {
+        let [a, b, c] = [0, 1, 2].map(|k| geometry.positions[triangle[k] as usize]);
+        ((b - a).cross(c - a), (a + b + c) * (1.0 / 3.0))
+    }
+
+    // Indices in range, unit normals, uvs in 0..1 and every triangle wound the way its vertex normals point
+    fn check(geometry: &Geometry) {
+        assert_eq!(geometry.indices.len() % 3, 0);
+        assert!(geometry.indices.iter().all(|&i| (i as usize) < geometry.vertex_count()));
+        assert!(geometry.normals.iter().all(|n| (n.length() - 1.0).abs() < 1e-4));
+        assert!(geometry.uvs.iter().flatten().all(|t| (-1e-6..=1.0 + 1e-6).contains(t)));
+        for triangle in geometry.indices.chunks(3) {
+            let (normal, _) = face_normal(geometry, triangle);
+            assert!(normal.length() > 0.0);
+            for &i in triangle {
+                assert!(normal.dot(geometry.normals[i as usize]) > 0.0, "triangle {:?} faces away from its normals", triangle);
+            }
+        }
+    }
+
+    // For shapes around the origin: every triangle faces away from it
+    fn check_outwards(geometry: &Geometry) {
+        for triangle in geometry.indices.chunks(3) {
+            let (normal, centroid) = face_normal(geometry, triangle);
+            assert!(normal.dot(centroid) > 0.0, "triangle {:?} faces inwards", triangle);
+        }
+    }
+
+    #[test]
+    fn cube_faces() {
+        let single = cube(2.0, 1);
+        check(&single);
+        check_outwards(&single);
+        assert_eq!((single.vertex_count(), single.triangle_count()), (24, 12));
+        // -Z face first
+        assert!(single.positions[..4].iter().all(|p| p.z == -1.0));
+        assert!(single.positions.iter().all(|p| p.x.abs() == 1.0 || p.y.abs() == 1.0 || p.z.abs() == 1.0));
+
+        let split = cube(2.0, 3);
+        check_outwards(&split);
+        assert_eq!((split.vertex_count(), split.triangle_count()), (6 * 16, 6 * 18));
+    }
+
+    #[test]
+    fn sphere_skips_the_pole_slivers() {
+        let ball = sphere(1.5, 16, 8);
+        check(&ball);
+        check_outwards(&ball);
+        assert_eq!(ball.vertex_count(), 17 * 9);
+        assert_eq!(ball.triangle_count(), 16 * 8 * 2 - 2 * 16);
+        assert!(ball.positions.iter().all(|p| (p.length() - 1.5).abs() < 1e-5));
+        // Raised to the minimum of 3 segments and 2 rings
+        assert_eq!(sphere(1.0, 0, 0).vertex_count(), 4 * 3);
+    }
+
+    #[test]
+    fn cylinder_and_cone() {
+        let cylinder = cylinder(1.0, 2.0, 12);
+        check(&cylinder);
+        check_outwards(&cylinder);
+        assert_eq!(cylinder.vertex_count(), 13 * 2 + 2 * 14);
+        assert_eq!(cylinder.triangle_count(), 24 + 24);
+
+        let cone = cone(1.0, 2.0, 12);
+        check(&cone);
+        check_outwards(&cone);
+        // One side triangle per segment and the base
+        assert_eq!(cone.triangle_count(), 12 + 12);
+        assert!(cone.positions.iter().all(|p| p.y >= -1.0 && p.y <= 1.0));
+    }
+
+    #[test]
+    fn torus_surface() {
+        let torus = torus(2.0, 0.5, 12, 8);
+        check(&torus);
+        assert_eq!(torus.vertex_count(), 13 * 9);
+        assert_eq!(torus.triangle_count(), 12 * 8 * 2);
+        for (position, normal) in torus.positions.iter().zip(&torus.normals) {
+            let ring = Vec3::new(position.x, 0.0, position.z).normalize() * 2.0;
+            assert!(((*position - ring).length() - 0.5).abs() < 1e-5);
+            assert!((*position - ring).normalize().dot(*normal) > 0.999);
+        }
+    }
+
+    #[test]
+    fn capsule_outline() {
+        let capsule = capsule(0.5, 2.0, 12, 4);
+        check(&capsule);
+        check_outwards(&capsule);
+        assert_eq!(capsule.vertex_count(), 13 * 10);
+        let top = capsule.positions.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+        assert!((top - 1.5).abs() < 1e-5);
+        // Row `rings` is the bottom equator, a quarter circle up the outline
+        let v = capsule.uvs[13 * 4][1];
+        assert!((v - 0.25 * PI / (0.5 * PI + 2.0)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn plane_faces_up() {
+        let plane = plane(4.0, 2.0, 2, 3);
+        check(&plane);
+        assert_eq!((plane.vertex_count(), plane.triangle_count()), (12, 12));
+        assert!(plane.positions.iter().all(|p| p.x.abs() <= 2.0 && p.z.abs() <= 1.0 && p.y == 0.0));
+    }
+}
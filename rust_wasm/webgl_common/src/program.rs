@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlUniformLocation};
+
+use crate::math::{Mat4, Vec3, Vec4};
+use crate::preprocess::{PreprocessError, Preprocessor, Variant};
+use crate::shader::{build_program, ShaderError, ShaderStage};
+
+type Gl = WebGlRenderingContext;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttributeInfo {
+    pub location: u32,
+    // GL type enum, e.g. FLOAT_VEC3
+    pub gl_type: u32,
+    pub size: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct UniformInfo {
+    pub location: WebGlUniformLocation,
+    pub gl_type: u32,
+    // Array length, 1 for plain uniforms
+    pub size: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgramError {
+    Shader(ShaderError),
+    Preprocess(PreprocessError),
+    UnknownAttribute(String),
+    UnknownUniform(String),
+    // The program uses an attribute the mesh's vertex layout doesn't have
+    MissingVertexAttribute(String),
+    TypeMismatch { name: String, expected: u32, found: u32 },
+    // A reloaded program declares an attribute/uniform with a different type than the running one
+    InterfaceChanged { name: String, old: u32, new: u32 },
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramError::Shader(err) => err.fmt(f),
+            ProgramError::Preprocess(err) => err.fmt(f),
+            ProgramError::UnknownAttribute(name) => write!(f, "no active attribute named '{}'", name),
+            ProgramError::UnknownUniform(name) => write!(f, "no active uniform named '{}'", name),
+            ProgramError::MissingVertexAttribute(name) => write!(f, "the program reads '{}' but the vertex layout has no such attribute", name),
+            ProgramError::TypeMismatch { name, expected, found } => write!(
+                f,
+                "uniform '{}' is declared as {} but was set as {}",
+                name,
+                gl_type_name(*found),
+                gl_type_name(*expected)
+            ),
+            ProgramError::InterfaceChanged { name, old, new } => write!(
+                f,
+                "'{}' is {} in the new program but {} in the running one",
+                name,
+                gl_type_name(*new),
+                gl_type_name(*old)
+            ),
+        }
+    }
+}
+
+impl From<ShaderError> for ProgramError {
+    fn from(err: ShaderError) -> Self {
+        ProgramError::Shader(err)
+    }
+}
+
+impl From<PreprocessError> for ProgramError {
+    fn from(err: PreprocessError) -> Self {
+        ProgramError::Preprocess(err)
+    }
+}
+
+impl From<ProgramError> for JsValue {
+    fn from(err: ProgramError) -> JsValue {
+        match err {
+            ProgramError::Shader(err) => err.into(),
+            err => js_sys::Error::new(&err.to_string()).into(),
+        }
+    }
+}
+
+// A linked program plus everything the driver reports as active in it, so attributes and
+// uniforms are looked up by name without get_attrib_location / get_uniform_location calls.
+pub struct ShaderProgram {
+    gl: Gl,
+    program: WebGlProgram,
+    attributes: HashMap<String, AttributeInfo>,
+    uniforms: HashMap<String, UniformInfo>,
+}
+
+impl ShaderProgram {
+    pub fn new(gl: &Gl, vert_code: &str, frag_code: &str) -> Result<Self, ProgramError> {
+        let program = build_program(gl, vert_code, frag_code)?;
+        Ok(ShaderProgram::from_program(gl, program))
+    }
+
+    pub fn from_program(gl: &Gl, program: WebGlProgram) -> Self {
+        let mut attributes = HashMap::new();
+        let count = gl.get_program_parameter(&program, Gl::ACTIVE_ATTRIBUTES).as_f64().unwrap_or(0.0) as u32;
+        for index in 0..count {
+            if let Some(info) = gl.get_active_attrib(&program, index) {
+                let location = gl.get_attrib_location(&program, &info.name());
+                if location >= 0 {
+                    attributes.insert(info.name(), AttributeInfo {
+                        location: location as u32,
+                        gl_type: info.type_(),
+                        size: info.size(),
+                    });
+                }
+            }
+        }
+
+        let mut uniforms = HashMap::new();
+        let count = gl.get_program_parameter(&program, Gl::ACTIVE_UNIFORMS).as_f64().unwrap_or(0.0) as u32;
+        for index in 0..count {
+            if let Some(info) = gl.get_active_uniform(&program, index) {
+                let name = uniform_base_name(&info.name()).to_string();
+                if let Some(location) = gl.get_uniform_location(&program, &name) {
+                    uniforms.insert(name, UniformInfo {
+                        location,
+                        gl_type: info.type_(),
+                        size: info.size(),
+                    });
+                }
+            }
+        }
+
+        ShaderProgram {
+            gl: gl.clone(),
+            program,
+            attributes,
+            uniforms,
+        }
+    }
+
+    pub fn program(&self) -> &WebGlProgram {
+        &self.program
+    }
+
+    pub fn use_program(&self) {
+        self.gl.use_program(Some(&self.program));
+    }
+
+    pub fn attributes(&self) -> &HashMap<String, AttributeInfo> {
+        &self.attributes
+    }
+
+    pub fn uniforms(&self) -> &HashMap<String, UniformInfo> {
+        &self.uniforms
+    }
+
+    pub fn attribute(&self, name: &str) -> Result<&AttributeInfo, ProgramError> {
+        self.attributes.get(name).ok_or_else(|| ProgramError::UnknownAttribute(name.to_string()))
+    }
+
+    pub fn attrib_location(&self, name: &str) -> Result<u32, ProgramError> {
+        self.attribute(name).map(|a| a.location)
+    }
+
+    pub fn uniform(&self, name: &str) -> Result<&UniformInfo, ProgramError> {
+        self.uniforms.get(name).ok_or_else(|| ProgramError::UnknownUniform(name.to_string()))
+    }
+
+    fn typed_uniform(&self, name: &str, accepted: &[u32]) -> Result<&WebGlUniformLocation, ProgramError> {
+        let uniform = self.uniform(name)?;
+        check_type(name, uniform.gl_type, accepted)?;
+        Ok(&uniform.location)
+    }
+
+    /*==================== Uniform setters ====================*/
+    // These write to the program that is currently in use, call use_program() first.
+    pub fn set_f32(&self, name: &str, value: f32) -> Result<(), ProgramError> {
+        let location = self.typed_uniform(name, &[Gl::FLOAT])?;
+        self.gl.uniform1f(Some(location), value);
+        Ok(())
+    }
+
+    pub fn set_vec2(&self, name: &str, x: f32, y: f32) -> Result<(), ProgramError> {
+        let location = self.typed_uniform(name, &[Gl::FLOAT_VEC2])?;
+        self.gl.uniform2f(Some(location), x, y);
+        Ok(())
+    }
+
+    pub fn set_vec3(&self, name: &str, value: Vec3) -> Result<(), ProgramError> {
+        let location = self.typed_uniform(name, &[Gl::FLOAT_VEC3])?;
+        self.gl.uniform3f(Some(location), value.x, value.y, value.z);
+        Ok(())
+    }
+
+    pub fn set_vec4(&self, name: &str, value: Vec4) -> Result<(), ProgramError> {
+        let location = self.typed_uniform(name, &[Gl::FLOAT_VEC4])?;
+        self.gl.uniform4f(Some(location), value.x, value.y, value.z, value.w);
+        Ok(())
+    }
+
+    pub fn set_mat4(&self, name: &str, value: &Mat4) -> Result<(), ProgramError> {
+        let location = self.typed_uniform(name, &[Gl::FLOAT_MAT4])?;
+        self.gl.uniform_matrix4fv_with_f32_array(Some(location), false, value.as_slice());
+        Ok(())
+    }
+
+    // int, bool and sampler uniforms are all set through uniform1i
+    pub fn set_i32(&self, name: &str, value: i32) -> Result<(), ProgramError> {
+        let location = self.typed_uniform(name, &[Gl::INT, Gl::BOOL, Gl::SAMPLER_2D, Gl::SAMPLER_CUBE])?;
+        self.gl.uniform1i(Some(location), value);
+        Ok(())
+    }
+}
+
+// Preprocesses both sources for `variant` and builds them. Compile errors point at the line of
+// the named source or snippet that was written, not at the processed text the driver saw.
+pub fn build_variant(gl: &Gl, preprocessor: &Preprocessor, vert: (&str, &str), frag: (&str, &str), variant: Variant) -> Result<ShaderProgram, ProgramError> {
+    let (vert_code, vert_map) = preprocessor.process_mapped(vert.0, vert.1, variant)?;
+    let (frag_code, frag_map) = preprocessor.process_mapped(frag.0, frag.1, variant)?;
+    ShaderProgram::new(gl, &vert_code, &frag_code).map_err(|err| match err {
+        ProgramError::Shader(err) => ProgramError::Shader(match err.stage {
+            ShaderStage::Vertex => err.map_lines(&vert_map),
+            ShaderStage::Fragment => err.map_lines(&frag_map),
+            ShaderStage::Link => err,
+        }),
+        err => err,
+    })
+}
+
+// One vertex/fragment source pair built on demand for each variant it is asked for.
+// Programs are compiled once and shared, later requests for the same variant get the same one.
+pub struct ProgramCache {
+    gl: Gl,
+    preprocessor: Preprocessor,
+    // What errors call the two sources
+    vert_name: String,
+    frag_name: String,
+    vert_code: String,
+    frag_code: String,
+    programs: HashMap<Variant, Rc<ShaderProgram>>,
+}
+
+impl ProgramCache {
+    pub fn new(gl: &Gl, preprocessor: Preprocessor, vert_code: &str, frag_code: &str) -> Self {
+        ProgramCache {
+            gl: gl.clone(),
+            preprocessor,
+            vert_name: "vertex shader".to_string(),
+            frag_name: "fragment shader".to_string(),
+            vert_code: vert_code.to_string(),
+            frag_code: frag_code.to_string(),
+            programs: HashMap::new(),
+        }
+    }
+
+    // File names for errors, e.g. "basic.vert" and "basic.frag"
+    pub fn with_names(mut self, vert_name: &str, frag_name: &str) -> Self {
+        self.vert_name = vert_name.to_string();
+        self.frag_name = frag_name.to_string();
+        self
+    }
+
+    pub fn preprocessor(&self) -> &Preprocessor {
+        &self.preprocessor
+    }
+
+    pub fn names(&self) -> (&str, &str) {
+        (&self.vert_name, &self.frag_name)
+    }
+
+    pub fn get(&mut self, variant: Variant) -> Result<Rc<ShaderProgram>, ProgramError> {
+        if let Some(program) = self.programs.get(&variant) {
+            return Ok(program.clone());
+        }
+        let vert = (self.vert_name.as_str(), self.vert_code.as_str());
+        let frag = (self.frag_name.as_str(), self.frag_code.as_str());
+        let program = Rc::new(build_variant(&self.gl, &self.preprocessor, vert, frag, variant)?);
+        self.programs.insert(variant, program.clone());
+        Ok(program)
+    }
+
+    // Switches to new sources, rebuilding every variant built so far. Each new program is passed
+    // to `check` with its variant and the program it replaces. If anything fails the cache is left as it was,
+    // otherwise the replaced programs are returned for the caller to delete once they are unused.
+    pub fn reload<F>(&mut self, vert_code: &str, frag_code: &str, check: F) -> Result<Vec<Rc<ShaderProgram>>, ProgramError>
+    where
+        F: Fn(Variant, &ShaderProgram, &ShaderProgram) -> Result<(), ProgramError>,
+    {
+        let vert = (self.vert_name.as_str(), vert_code);
+        let frag = (self.frag_name.as_str(), frag_code);
+        let mut rebuilt = HashMap::new();
+        for (variant, old) in &self.programs {
+            let built = build_variant(&self.gl, &self.preprocessor, vert, frag, *variant).and_then(|program| match check(*variant, old, &program) {
+                Ok(()) => Ok(program),
+                Err(err) => {
+                    self.gl.delete_program(Some(program.program()));
+                    Err(err)
+                }
+            });
+            match built {
+                Ok(program) => {
+                    rebuilt.insert(*variant, Rc::new(program));
+                }
+                Err(err) => {
+                    for program in rebuilt.values() {
+                        self.gl.delete_program(Some(program.program()));
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        self.vert_code = vert_code.to_string();
+        self.frag_code = frag_code.to_string();
+        Ok(std::mem::replace(&mut self.programs, rebuilt).into_values().collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+}
+
+// Array uniforms are reported as "name[0]"
+fn uniform_base_name(name: &str) -> &str {
+    name.strip_suffix("[0]").unwrap_or(name)
+}
+
+fn check_type(name: &str, found: u32, accepted: &[u32]) -> Result<(), ProgramError> {
+    if accepted.contains(&found) {
+        Ok(())
+    } else {
+        Err(ProgramError::TypeMismatch {
+            name: name.to_string(),
+            expected: accepted[0],
+            found,
+        })
+    }
+}
+
+pub fn gl_type_name(gl_type: u32) -> &'static str {
+    match gl_type {
+        Gl::FLOAT => "float",
+        Gl::FLOAT_VEC2 => "vec2",
+        Gl::FLOAT_VEC3 => "vec3",
+        Gl::FLOAT_VEC4 => "vec4",
+        Gl::INT => "int",
+        Gl::INT_VEC2 => "ivec2",
+        Gl::INT_VEC3 => "ivec3",
+        Gl::INT_VEC4 => "ivec4",
+        Gl::BOOL => "bool",
+        Gl::BOOL_VEC2 => "bvec2",
+        Gl::BOOL_VEC3 => "bvec3",
+        Gl::BOOL_VEC4 => "bvec4",
+        Gl::FLOAT_MAT2 => "mat2",
+        Gl::FLOAT_MAT3 => "mat3",
+        Gl::FLOAT_MAT4 => "mat4",
+        Gl::SAMPLER_2D => "sampler2D",
+        Gl::SAMPLER_CUBE => "samplerCube",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn array_uniform_names_drop_the_index() {
+        assert_eq!(uniform_base_name("lights[0]"), "lights");
+        assert_eq!(uniform_base_name("Pmatrix"), "Pmatrix");
+    }
+
+    #[test]
+    fn type_mismatch_names_both_types() {
+        assert!(check_type("Pmatrix", Gl::FLOAT_MAT4, &[Gl::FLOAT_MAT4]).is_ok());
+        assert!(check_type("tex", Gl::SAMPLER_2D, &[Gl::INT, Gl::SAMPLER_2D]).is_ok());
+
+        let err = check_type("Pmatrix", Gl::FLOAT_MAT4, &[Gl::FLOAT_VEC3]).unwrap_err();
+        assert_eq!(err.to_string(), "uniform 'Pmatrix' is declared as mat4 but was set as vec3");
+
+        let err = ProgramError::InterfaceChanged { name: "color".to_string(), old: Gl::FLOAT_VEC3, new: Gl::FLOAT_VEC4 };
+        assert_eq!(err.to_string(), "'color' is vec4 in the new program but vec3 in the running one");
+    }
+}
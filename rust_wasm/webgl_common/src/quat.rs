@@ -0,0 +1,305 @@
+use std::ops::{Mul, MulAssign};
+
+use crate::math::{Mat4, Vec3};
+
+// Unit quaternion used as an orientation. Unlike multiplying rotations into a Mat4 every
+// frame, renormalizing four numbers keeps the rotation rigid no matter how long it runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Quat { x, y, z, w }
+    }
+
+    // `angle` radians around `axis`; the axis does not need to be normalized
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let a = axis.normalize();
+        let (s, c) = (angle * 0.5).sin_cos();
+        Quat::new(a.x * s, a.y * s, a.z * s, c)
+    }
+
+    pub fn from_rotation_x(angle: f32) -> Self {
+        Quat::from_axis_angle(Vec3::X, angle)
+    }
+
+    pub fn from_rotation_y(angle: f32) -> Self {
+        Quat::from_axis_angle(Vec3::Y, angle)
+    }
+
+    pub fn from_rotation_z(angle: f32) -> Self {
+        Quat::from_axis_angle(Vec3::Z, angle)
+    }
+
+    // Rotate around X, then Y, then Z (the same as rotation_z * rotation_y * rotation_x)
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        let (sx, cx) = (x * 0.5).sin_cos();
+        let (sy, cy) = (y * 0.5).sin_cos();
+        let (sz, cz) = (z * 0.5).sin_cos();
+
+        Quat::new(
+            sx * cy * cz - cx * sy * sz,
+            cx * sy * cz + sx * cy * sz,
+            cx * cy * sz - sx * sy * cz,
+            cx * cy * cz + sx * sy * sz,
+        )
+    }
+
+    // Inverse of from_euler. Y is kept in [-PI/2, PI/2]; at the poles X and Z are not unique.
+    pub fn to_euler(self) -> Vec3 {
+        let q = self.normalize();
+        let x = (2.0 * (q.w * q.x + q.y * q.z)).atan2(1.0 - 2.0 * (q.x * q.x + q.y * q.y));
+        let y = (2.0 * (q.w * q.y - q.z * q.x)).clamp(-1.0, 1.0).asin();
+        let z = (2.0 * (q.w * q.z + q.x * q.y)).atan2(1.0 - 2.0 * (q.y * q.y + q.z * q.z));
+        Vec3::new(x, y, z)
+    }
+
+    // Returns (axis, angle); the identity gives the X axis with a zero angle
+    pub fn to_axis_angle(self) -> (Vec3, f32) {
+        let q = self.normalize();
+        let s = (1.0 - q.w * q.w).max(0.0).sqrt();
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+        if s < 1e-6 {
+            (Vec3::X, angle)
+        } else {
+            (Vec3::new(q.x / s, q.y / s, q.z / s), angle)
+        }
+    }
+
+    pub fn dot(self, other: Quat) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Quat {
+        let len = self.length();
+        if len > 0.0 {
+            let inv = 1.0 / len;
+            Quat::new(self.x * inv, self.y * inv, self.z * inv, self.w * inv)
+        } else {
+            Quat::IDENTITY
+        }
+    }
+
+    // Inverse rotation of a unit quaternion
+    pub fn conjugate(self) -> Quat {
+        Quat::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    // Shortest-path spherical interpolation, t in [0, 1]
+    pub fn slerp(self, end: Quat, t: f32) -> Quat {
+        let mut end = end;
+        let mut cos_theta = self.dot(end);
+
+        // q and -q are the same rotation; go the short way around
+        if cos_theta < 0.0 {
+            end = Quat::new(-end.x, -end.y, -end.z, -end.w);
+            cos_theta = -cos_theta;
+        }
+
+        // Nearly parallel: sin(theta) is ~0, fall back to a normalized lerp
+        let (k0, k1) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+
+        Quat::new(
+            self.x * k0 + end.x * k1,
+            self.y * k0 + end.y * k1,
+            self.z * k0 + end.z * k1,
+            self.w * k0 + end.w * k1,
+        )
+        .normalize()
+    }
+
+    pub fn rotate_vec3(self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+
+    pub fn to_mat4(self) -> Mat4 {
+        let Quat { x, y, z, w } = self;
+        let (xx, yy, zz) = (x * x, y * y, z * z);
+        let (xy, xz, yz) = (x * y, x * z, y * z);
+        let (wx, wy, wz) = (w * x, w * y, w * z);
+
+        Mat4::from_cols_array([
+            1.0 - 2.0 * (yy + zz), 2.0 * (xy + wz), 2.0 * (xz - wy), 0.0,
+            2.0 * (xy - wz), 1.0 - 2.0 * (xx + zz), 2.0 * (yz + wx), 0.0,
+            2.0 * (xz + wy), 2.0 * (yz - wx), 1.0 - 2.0 * (xx + yy), 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ])
+    }
+
+    /*==================== Rotation ====================*/
+    // Same meaning as Mat4::rotate_x/y/z (the new rotation is applied after the current one),
+    // renormalized each time so accumulated error never builds up.
+    pub fn rotate_x(&mut self, angle: f32) {
+        *self = (Quat::from_rotation_x(angle) * *self).normalize();
+    }
+
+    pub fn rotate_y(&mut self, angle: f32) {
+        *self = (Quat::from_rotation_y(angle) * *self).normalize();
+    }
+
+    pub fn rotate_z(&mut self, angle: f32) {
+        *self = (Quat::from_rotation_z(angle) * *self).normalize();
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+impl MulAssign for Quat {
+    fn mul_assign(&mut self, rhs: Quat) {
+        *self = *self * rhs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &[f32], b: &[f32], eps: f32) {
+        for (i, (x, y)) in a.iter().zip(b.iter()).enumerate() {
+            assert!((x - y).abs() < eps, "element {}: {} != {}\n{:?}\n{:?}", i, x, y, a, b);
+        }
+    }
+
+    #[test]
+    fn axis_rotations_match_mat4() {
+        for &angle in &[0.0, 0.4, -2.0, 3.0] {
+            assert_close(Quat::from_rotation_x(angle).to_mat4().as_slice(), Mat4::rotation_x(angle).as_slice(), 1e-6);
+            assert_close(Quat::from_rotation_y(angle).to_mat4().as_slice(), Mat4::rotation_y(angle).as_slice(), 1e-6);
+            assert_close(Quat::from_rotation_z(angle).to_mat4().as_slice(), Mat4::rotation_z(angle).as_slice(), 1e-6);
+
+            let axis = Vec3::new(1.0, -2.0, 0.5);
+            assert_close(
+                Quat::from_axis_angle(axis, angle).to_mat4().as_slice(),
+                Mat4::rotation_axis(axis, angle).as_slice(),
+                1e-6,
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_rotation_matches_mat4() {
+        let mut q = Quat::IDENTITY;
+        let mut m = Mat4::IDENTITY;
+        for frame in 0..100 {
+            let dt = 16.0 + (frame % 5) as f32;
+            q.rotate_z(dt * 0.005);
+            q.rotate_y(dt * 0.002);
+            q.rotate_x(dt * 0.003);
+            m.rotate_z(dt * 0.005);
+            m.rotate_y(dt * 0.002);
+            m.rotate_x(dt * 0.003);
+        }
+        assert_close(q.to_mat4().as_slice(), m.as_slice(), 1e-4);
+    }
+
+    #[test]
+    fn long_running_rotation_stays_rigid() {
+        // About six hours of frames at 60fps
+        let mut q = Quat::IDENTITY;
+        for frame in 0..1_300_000 {
+            let dt = 16.0 + (frame % 3) as f32;
+            q.rotate_z(dt * 0.005);
+            q.rotate_y(dt * 0.002);
+            q.rotate_x(dt * 0.003);
+        }
+        assert!((q.length() - 1.0).abs() < 1e-5);
+
+        // The columns of the model matrix are still an orthonormal basis
+        let m = q.to_mat4();
+        let cols = [m.col(0).truncate(), m.col(1).truncate(), m.col(2).truncate()];
+        for (i, a) in cols.iter().enumerate() {
+            assert!((a.length() - 1.0).abs() < 1e-5);
+            for b in cols.iter().skip(i + 1) {
+                assert!(a.dot(*b).abs() < 1e-5);
+            }
+        }
+        assert!((m.determinant() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let q = Quat::from_euler(0.3, -0.7, 1.9);
+        let expected = Mat4::rotation_z(1.9) * Mat4::rotation_y(-0.7) * Mat4::rotation_x(0.3);
+        assert_close(q.to_mat4().as_slice(), expected.as_slice(), 1e-6);
+
+        let e = q.to_euler();
+        assert_close(&e.to_array(), &[0.3, -0.7, 1.9], 1e-5);
+    }
+
+    #[test]
+    fn axis_angle_round_trip() {
+        let axis = Vec3::new(0.0, 3.0, 4.0);
+        let (out_axis, angle) = Quat::from_axis_angle(axis, 1.25).to_axis_angle();
+        assert_close(&out_axis.to_array(), &[0.0, 0.6, 0.8], 1e-5);
+        assert!((angle - 1.25).abs() < 1e-5);
+
+        let (_, angle) = Quat::IDENTITY.to_axis_angle();
+        assert_eq!(angle, 0.0);
+    }
+
+    #[test]
+    fn slerp_interpolates_along_the_arc() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+
+        assert_close(&quat_array(a.slerp(b, 0.0)), &quat_array(a), 1e-6);
+        assert_close(&quat_array(a.slerp(b, 1.0)), &quat_array(b), 1e-6);
+
+        let mid = a.slerp(b, 0.5);
+        let expected = Quat::from_rotation_y(std::f32::consts::FRAC_PI_4);
+        assert_close(&quat_array(mid), &quat_array(expected), 1e-6);
+
+        // -b is the same rotation; slerp must still take the short path
+        let neg_b = Quat::new(-b.x, -b.y, -b.z, -b.w);
+        let mid = a.slerp(neg_b, 0.5);
+        assert_close(mid.to_mat4().as_slice(), expected.to_mat4().as_slice(), 1e-6);
+    }
+
+    #[test]
+    fn rotate_vec3_matches_matrix() {
+        let q = Quat::from_euler(0.1, 0.2, 0.3);
+        let v = Vec3::new(1.0, -2.0, 3.0);
+        let expected = q.to_mat4().transform_vector3(v);
+        assert_close(&q.rotate_vec3(v).to_array(), &expected.to_array(), 1e-5);
+        assert_close(&q.conjugate().rotate_vec3(q.rotate_vec3(v)).to_array(), &v.to_array(), 1e-5);
+    }
+
+    fn quat_array(q: Quat) -> [f32; 4] {
+        [q.x, q.y, q.z, q.w]
+    }
+}
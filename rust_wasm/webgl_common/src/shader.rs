@@ -0,0 +1,339 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlShader};
+
+use crate::preprocess::SourceMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Link,
+}
+
+impl ShaderStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Link => "link",
+        }
+    }
+
+    fn gl_type(&self) -> u32 {
+        match self {
+            ShaderStage::Fragment => WebGlRenderingContext::FRAGMENT_SHADER,
+            _ => WebGlRenderingContext::VERTEX_SHADER,
+        }
+    }
+}
+
+// One message from a driver info log, with the line it points at when there is one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    // Set once the line has been mapped back through a SourceMap
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub message: String,
+    pub snippet: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderError {
+    pub stage: ShaderStage,
+    pub log: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ShaderError {
+    pub fn new(stage: ShaderStage, log: &str, source: &str) -> Self {
+        ShaderError {
+            stage,
+            log: log.trim().to_string(),
+            diagnostics: parse_info_log(log, source),
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.diagnostics.iter().find_map(|d| d.line)
+    }
+
+    pub fn snippet(&self) -> Option<&str> {
+        self.diagnostics.iter().find_map(|d| d.snippet.as_deref())
+    }
+
+    // The file of the first diagnostic with a line, if it was mapped
+    pub fn file(&self) -> Option<&str> {
+        self.diagnostics.iter().find(|d| d.line.is_some()).and_then(|d| d.file.as_deref())
+    }
+
+    // Turns lines of the processed source the driver saw into lines of the files it was made
+    // from, with the snippets taken from those files. Lines of the injected #defines stay as
+    // they are.
+    pub fn map_lines(mut self, map: &SourceMap) -> Self {
+        for d in &mut self.diagnostics {
+            if let Some((name, text, line)) = d.line.and_then(|line| map.locate(line)) {
+                d.file = Some(name.to_string());
+                d.line = Some(line);
+                d.snippet = snippet(text, line);
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} shader error", self.stage.as_str())?;
+        if self.diagnostics.is_empty() {
+            return write!(f, ": {}", self.log);
+        }
+        for d in &self.diagnostics {
+            match (&d.file, d.line) {
+                (Some(file), Some(line)) => write!(f, "\n  {} line {}: {}", file, line, d.message)?,
+                (None, Some(line)) => write!(f, "\n  line {}: {}", line, d.message)?,
+                (_, None) => write!(f, "\n  {}", d.message)?,
+            }
+            if let Some(snippet) = &d.snippet {
+                write!(f, "\n{}", snippet)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Surfaces in JS as an Error whose message is the formatted report, with
+// `stage`, `file`, `line`, `snippet` and the raw `log` attached for pages that want them.
+impl From<ShaderError> for JsValue {
+    fn from(err: ShaderError) -> JsValue {
+        let js_err = js_sys::Error::new(&err.to_string());
+        let set = |key: &str, value: JsValue| {
+            js_sys::Reflect::set(&js_err, &JsValue::from_str(key), &value).unwrap();
+        };
+        set("stage", JsValue::from_str(err.stage.as_str()));
+        set("file", err.file().map(JsValue::from_str).unwrap_or(JsValue::NULL));
+        set("line", err.line().map(|l| JsValue::from_f64(l as f64)).unwrap_or(JsValue::NULL));
+        set("snippet", err.snippet().map(JsValue::from_str).unwrap_or(JsValue::NULL));
+        set("log", JsValue::from_str(&err.log));
+        js_err.into()
+    }
+}
+
+/*==================== Building ====================*/
+pub fn compile_shader(gl: &WebGlRenderingContext, stage: ShaderStage, source: &str) -> Result<WebGlShader, ShaderError> {
+    let shader = gl
+        .create_shader(stage.gl_type())
+        .ok_or_else(|| ShaderError::new(stage, "unable to create shader object", source))?;
+    gl.shader_source(&shader, source);
+    gl.compile_shader(&shader);
+
+    let compiled = gl
+        .get_shader_parameter(&shader, WebGlRenderingContext::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false);
+    if compiled {
+        Ok(shader)
+    } else {
+        let log = gl.get_shader_info_log(&shader).unwrap_or_default();
+        gl.delete_shader(Some(&shader));
+        Err(ShaderError::new(stage, &log, source))
+    }
+}
+
+pub fn link_program(gl: &WebGlRenderingContext, vert_shader: &WebGlShader, frag_shader: &WebGlShader) -> Result<WebGlProgram, ShaderError> {
+    let program = gl
+        .create_program()
+        .ok_or_else(|| ShaderError::new(ShaderStage::Link, "unable to create program object", ""))?;
+    gl.attach_shader(&program, vert_shader);
+    gl.attach_shader(&program, frag_shader);
+    gl.link_program(&program);
+
+    let linked = gl
+        .get_program_parameter(&program, WebGlRenderingContext::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false);
+    if linked {
+        Ok(program)
+    } else {
+        let log = gl.get_program_info_log(&program).unwrap_or_default();
+        gl.delete_program(Some(&program));
+        Err(ShaderError::new(ShaderStage::Link, &log, ""))
+    }
+}
+
+// Compiles both stages and links them, stopping at the first stage that fails
+pub fn build_program(gl: &WebGlRenderingContext, vert_code: &str, frag_code: &str) -> Result<WebGlProgram, ShaderError> {
+    let vert_shader = compile_shader(gl, ShaderStage::Vertex, vert_code)?;
+    let frag_shader = match compile_shader(gl, ShaderStage::Fragment, frag_code) {
+        Ok(shader) => shader,
+        Err(err) => {
+            gl.delete_shader(Some(&vert_shader));
+            return Err(err);
+        }
+    };
+    let program = link_program(gl, &vert_shader, &frag_shader);
+
+    // The program keeps what it needs; the shader objects can go
+    gl.delete_shader(Some(&vert_shader));
+    gl.delete_shader(Some(&frag_shader));
+    program
+}
+
+/*==================== Info log ====================*/
+// Drivers disagree on the format, these are the ones WebGL implementations produce:
+//   ANGLE / Chrome, Firefox:  "ERROR: 0:12: 'foo' : undeclared identifier"
+//   Mesa:                     "0:12(5): error: `foo' undeclared"
+//   NVIDIA:                   "0(12) : error C1008: undefined variable \"foo\""
+// Line numbers count from 1 in the string handed to shader_source.
+pub fn parse_info_log(log: &str, source: &str) -> Vec<Diagnostic> {
+    log.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && *l != "\0")
+        .map(|l| {
+            let (line, message) = match parse_log_line(l) {
+                Some((line, message)) => (Some(line), message),
+                None => (None, l.to_string()),
+            };
+            Diagnostic {
+                file: None,
+                line,
+                message,
+                snippet: line.and_then(|line| snippet(source, line)),
+            }
+        })
+        .collect()
+}
+
+fn parse_log_line(l: &str) -> Option<(usize, String)> {
+    // ANGLE: "ERROR: 0:12: message" / "WARNING: 0:12: message"
+    for prefix in ["ERROR:", "WARNING:"] {
+        if let Some(rest) = l.strip_prefix(prefix) {
+            let mut parts = rest.trim_start().splitn(3, ':');
+            let _source_index = parts.next()?.trim().parse::<u32>().ok()?;
+            let line = parts.next()?.trim().parse::<usize>().ok()?;
+            let message = parts.next().unwrap_or("").trim();
+            return Some((line, format!("{} {}", prefix.trim_end_matches(':').to_lowercase(), message)));
+        }
+    }
+
+    // Mesa: "0:12(5): error: message"
+    if let Some((location, message)) = l.split_once("): ") {
+        if let Some((index_line, _column)) = location.split_once('(') {
+            if let Some((index, line)) = index_line.split_once(':') {
+                if index.trim().parse::<u32>().is_ok() {
+                    if let Ok(line) = line.trim().parse::<usize>() {
+                        return Some((line, message.trim().to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    // NVIDIA: "0(12) : error C0000: message"
+    if let Some((index, rest)) = l.split_once('(') {
+        if index.trim().parse::<u32>().is_ok() {
+            if let Some((line, message)) = rest.split_once(')') {
+                if let Ok(line) = line.trim().parse::<usize>() {
+                    return Some((line, message.trim_start_matches([' ', ':']).trim().to_string()));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// The failing line with one line of context on each side, marked with `>`
+pub fn snippet(source: &str, line: usize) -> Option<String> {
+    let lines: Vec<&str> = source.lines().collect();
+    if line == 0 || line > lines.len() {
+        return None;
+    }
+    let first = line.saturating_sub(1).max(1);
+    let last = (line + 1).min(lines.len());
+
+    let out: Vec<String> = (first..=last)
+        .map(|n| {
+            let marker = if n == line { '>' } else { ' ' };
+            format!("{} {:>4} | {}", marker, n, lines[n - 1].trim_end())
+        })
+        .collect();
+    Some(out.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERT: &str = "
+        attribute vec3 position;
+        uniform mat4 Pmatrix;
+        void main(void) {
+            gl_Position = Pmatrix*vec4(positon, 1.);
+        }
+    ";
+
+    #[test]
+    fn parses_angle_log() {
+        let log = "ERROR: 0:5: 'positon' : undeclared identifier \nERROR: 0:5: 'constructor' : not enough data provided for construction\n\0";
+        let diagnostics = parse_info_log(log, VERT);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].line, Some(5));
+        assert_eq!(diagnostics[0].message, "error 'positon' : undeclared identifier");
+        let snippet = diagnostics[0].snippet.as_ref().unwrap();
+        assert!(snippet.contains(">    5 |             gl_Position = Pmatrix*vec4(positon, 1.);"), "{}", snippet);
+        assert!(snippet.contains("     4 |         void main(void) {"), "{}", snippet);
+    }
+
+    #[test]
+    fn parses_mesa_and_nvidia_logs() {
+        let mesa = parse_info_log("0:5(39): error: `positon' undeclared", VERT);
+        assert_eq!(mesa[0].line, Some(5));
+        assert_eq!(mesa[0].message, "error: `positon' undeclared");
+
+        let nvidia = parse_info_log("0(5) : error C1008: undefined variable \"positon\"", VERT);
+        assert_eq!(nvidia[0].line, Some(5));
+        assert_eq!(nvidia[0].message, "error C1008: undefined variable \"positon\"");
+    }
+
+    #[test]
+    fn keeps_lines_without_location() {
+        let diagnostics = parse_info_log("Varyings with the same name but different type, or statically used varyings in fragment shader are not declared in vertex shader: vColor", "");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, None);
+        assert_eq!(diagnostics[0].snippet, None);
+    }
+
+    #[test]
+    fn out_of_range_line_has_no_snippet() {
+        assert_eq!(snippet(VERT, 0), None);
+        assert_eq!(snippet(VERT, 99), None);
+        assert_eq!(snippet("void main(void) {}", 1).unwrap(), ">    1 | void main(void) {}");
+    }
+
+    #[test]
+    fn error_report_names_stage_line_and_snippet() {
+        let err = ShaderError::new(ShaderStage::Vertex, "ERROR: 0:5: 'positon' : undeclared identifier", VERT);
+        assert_eq!(err.line(), Some(5));
+        let text = err.to_string();
+        assert!(text.starts_with("vertex shader error\n  line 5: error 'positon' : undeclared identifier"), "{}", text);
+        assert!(text.contains("vec4(positon, 1.)"));
+    }
+
+    #[test]
+    fn lines_map_back_through_the_preprocessor() {
+        let mut pre = crate::preprocess::Preprocessor::new();
+        pre.add_snippet("transform", "uniform mat4 Pmatrix;");
+        let source = "attribute vec3 position;\n#include \"transform\"\nvoid main(void) {\n    gl_Position = Pmatrix*vec4(positon, 1.);\n}";
+        let (code, map) = pre.process_mapped("basic.vert", source, crate::preprocess::Variant::USE_MVP).unwrap();
+        // The driver counts the #define and the included line
+        assert_eq!(code.lines().nth(4), Some("    gl_Position = Pmatrix*vec4(positon, 1.);"));
+
+        let err = ShaderError::new(ShaderStage::Vertex, "ERROR: 0:5: 'positon' : undeclared identifier", &code).map_lines(&map);
+        assert_eq!((err.file(), err.line()), (Some("basic.vert"), Some(4)));
+        assert!(err.snippet().unwrap().contains(">    4 |     gl_Position"), "{}", err.snippet().unwrap());
+        assert!(err.to_string().contains("\n  basic.vert line 4: error 'positon'"), "{}", err);
+    }
+}
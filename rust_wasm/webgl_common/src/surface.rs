@@ -0,0 +1,155 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{AddEventListenerOptions, HtmlCanvasElement, ResizeObserver, WebGlRenderingContext};
+
+// Keeps a canvas' backing store at its CSS size × devicePixelRatio so output stays sharp
+// on HiDPI screens and isn't stretched after the page layout changes.
+#[derive(Clone)]
+pub struct Surface {
+    canvas: HtmlCanvasElement,
+}
+
+impl Surface {
+    pub fn new(canvas: HtmlCanvasElement) -> Self {
+        // Without a CSS size the canvas is laid out at its width/height attributes, so growing
+        // the backing store for HiDPI would also grow it on the page. Pin the current layout
+        // size, but only in a direction that really follows the attribute; one sized by a
+        // stylesheet (width: 100% and the like) has to stay free to follow the window.
+        let style = canvas.style();
+        let width = canvas.width();
+        if follows_attribute(|w| canvas.set_width(w), width, || canvas.client_width()) {
+            style.set_property("width", &format!("{}px", canvas.client_width())).unwrap();
+        }
+        let height = canvas.height();
+        if follows_attribute(|h| canvas.set_height(h), height, || canvas.client_height()) {
+            style.set_property("height", &format!("{}px", canvas.client_height())).unwrap();
+        }
+
+        let surface = Surface { canvas };
+        surface.resize();
+        surface
+    }
+
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+
+    pub fn width(&self) -> u32 {
+        self.canvas.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.canvas.height()
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.canvas.width() as f32 / self.canvas.height() as f32
+    }
+
+    pub fn pixel_ratio(&self) -> f64 {
+        web_sys::window().map(|w| w.device_pixel_ratio()).unwrap_or(1.0)
+    }
+
+    // Size the backing store should have right now
+    pub fn display_size(&self) -> (u32, u32) {
+        backing_size(self.canvas.client_width(), self.canvas.client_height(), self.pixel_ratio())
+    }
+
+    // Resizes the backing store if the CSS size or devicePixelRatio changed.
+    // Cheap enough to call every frame; returns true when the size changed.
+    pub fn resize(&self) -> bool {
+        let (width, height) = self.display_size();
+        if self.canvas.width() == width && self.canvas.height() == height {
+            return false;
+        }
+        self.canvas.set_width(width);
+        self.canvas.set_height(height);
+        true
+    }
+
+    pub fn set_viewport(&self, gl: &WebGlRenderingContext) {
+        gl.viewport(0, 0, self.width() as i32, self.height() as i32);
+    }
+
+    // Calls `on_resize` (after resizing the backing store) whenever the canvas' layout size
+    // or devicePixelRatio changes. For demos that draw once instead of every frame. The observer
+    // lives for the rest of the page, like the requestAnimationFrame closures.
+    pub fn observe(&self, on_resize: impl FnMut(&Surface) + 'static) -> Result<ResizeObserver, JsValue> {
+        let surface = self.clone();
+        let on_resize = RefCell::new(on_resize);
+        let redraw: Rc<dyn Fn()> = Rc::new(move || {
+            surface.resize();
+            (on_resize.borrow_mut())(&surface);
+        });
+
+        let on_layout = redraw.clone();
+        let callback = Closure::wrap(Box::new(move |_entries: JsValue| on_layout()) as Box<dyn FnMut(JsValue)>);
+        let observer = ResizeObserver::new(callback.as_ref().unchecked_ref())?;
+        observer.observe(&self.canvas);
+        callback.forget();
+
+        watch_pixel_ratio(redraw)?;
+        Ok(observer)
+    }
+}
+
+// Calls `on_change` when devicePixelRatio moves off its current value, then watches the new one.
+// The ResizeObserver doesn't fire for these: browser zoom at a fixed CSS size or dragging the
+// window to another screen changes the device pixels behind the canvas, not its layout size.
+fn watch_pixel_ratio(on_change: Rc<dyn Fn()>) -> Result<(), JsValue> {
+    let Some(window) = web_sys::window() else {
+        return Ok(());
+    };
+    let query = format!("(resolution: {}dppx)", window.device_pixel_ratio());
+    let Some(media) = window.match_media(&query)? else {
+        return Ok(());
+    };
+    let callback = Closure::once_into_js(move || {
+        on_change();
+        // Only an invalid query throws, and this one was just accepted with another number
+        let _ = watch_pixel_ratio(on_change);
+    });
+    let mut options = AddEventListenerOptions::new();
+    options.once(true);
+    media.add_event_listener_with_callback_and_add_event_listener_options("change", callback.unchecked_ref(), &options)
+}
+
+// Nudges the attribute by a pixel and back, and sees whether the layout size moved with it.
+// Reading the client size forces a layout, which is fine once at start-up.
+fn follows_attribute(set: impl Fn(u32), value: u32, client: impl Fn() -> i32) -> bool {
+    let before = client();
+    set(value + 1);
+    let after = client();
+    set(value);
+    after != before
+}
+
+// CSS pixels × devicePixelRatio, never smaller than 1x1
+pub fn backing_size(css_width: i32, css_height: i32, pixel_ratio: f64) -> (u32, u32) {
+    let ratio = if pixel_ratio > 0.0 { pixel_ratio } else { 1.0 };
+    let width = (css_width.max(0) as f64 * ratio).round().max(1.0) as u32;
+    let height = (css_height.max(0) as f64 * ratio).round().max(1.0) as u32;
+    (width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backing_size_scales_by_pixel_ratio() {
+        assert_eq!(backing_size(570, 570, 1.0), (570, 570));
+        assert_eq!(backing_size(570, 300, 2.0), (1140, 600));
+        assert_eq!(backing_size(301, 201, 1.5), (452, 302));
+    }
+
+    #[test]
+    fn backing_size_never_collapses() {
+        assert_eq!(backing_size(0, 0, 2.0), (1, 1));
+        assert_eq!(backing_size(-5, 10, 1.0), (1, 10));
+        assert_eq!(backing_size(100, 100, 0.0), (100, 100));
+    }
+}
@@ -0,0 +1,701 @@
+// SVG path data, the `d` attribute: every command (M L H V C S Q T A Z, absolute and relative)
+// parsed into absolute form, flattened into polylines with curve.rs, and filled by the nonzero or
+// even-odd rule. Subpaths that don't cross are ear clipped with triangulate.rs, nested ones as
+// holes or islands; paths whose edges cross are cut into slabs and filled span by span.
+use std::f32::consts::TAU;
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::curve::{flatten_arc, flatten_cubic, flatten_quadratic};
+use crate::triangulate::{signed_area, triangulate, Point};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgError {
+    // Byte offset into the path data
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}: {}", self.position, self.message)
+    }
+}
+
+impl From<SvgError> for JsValue {
+    fn from(err: SvgError) -> JsValue {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+// A command with absolute coordinates; H and V become lines and S and T get their reflected
+// control point filled in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathCommand {
+    MoveTo(Point),
+    LineTo(Point),
+    Quadratic { control: Point, to: Point },
+    Cubic { c1: Point, c2: Point, to: Point },
+    // Rotation in degrees, as written
+    Arc { radii: [f32; 2], rotation: f32, large_arc: bool, sweep: bool, to: Point },
+    Close,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FillRule {
+    #[default]
+    NonZero,
+    EvenOdd,
+}
+
+impl FillRule {
+    // "nonzero" or "evenodd", as in the fill-rule attribute
+    pub fn from_name(name: &str) -> Option<FillRule> {
+        match name {
+            "nonzero" => Some(FillRule::NonZero),
+            "evenodd" => Some(FillRule::EvenOdd),
+            _ => None,
+        }
+    }
+
+    fn is_filled(self, winding: i32) -> bool {
+        match self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+// One run of the pen between move-tos
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Subpath {
+    pub points: Vec<Point>,
+    // Ended with Z; fills close every subpath either way
+    pub closed: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SvgPath {
+    pub commands: Vec<PathCommand>,
+}
+
+struct Lexer<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Lexer<'_> {
+    fn error(&self, message: impl Into<String>) -> SvgError {
+        SvgError {
+            position: self.position,
+            message: message.into(),
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self.position < self.bytes.len() && (self.bytes[self.position].is_ascii_whitespace() || self.bytes[self.position] == b',') {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_separators();
+        self.bytes.get(self.position).copied()
+    }
+
+    fn at_number(&mut self) -> bool {
+        matches!(self.peek(), Some(b'0'..=b'9' | b'.' | b'-' | b'+'))
+    }
+
+    // Numbers can run into each other where that's unambiguous, e.g. "0.5.5-1" is 0.5, .5 and -1
+    fn number(&mut self) -> Result<f32, SvgError> {
+        self.skip_separators();
+        let start = self.position;
+        let digits = |lexer: &mut Self| {
+            let from = lexer.position;
+            while lexer.bytes.get(lexer.position).is_some_and(u8::is_ascii_digit) {
+                lexer.position += 1;
+            }
+            lexer.position > from
+        };
+        if matches!(self.bytes.get(self.position), Some(b'-' | b'+')) {
+            self.position += 1;
+        }
+        let mut any = digits(self);
+        if self.bytes.get(self.position) == Some(&b'.') {
+            self.position += 1;
+            any |= digits(self);
+        }
+        if !any {
+            self.position = start;
+            return Err(self.error("expected a number"));
+        }
+        if matches!(self.bytes.get(self.position), Some(b'e' | b'E')) {
+            let mantissa_end = self.position;
+            self.position += 1;
+            if matches!(self.bytes.get(self.position), Some(b'-' | b'+')) {
+                self.position += 1;
+            }
+            if !digits(self) {
+                self.position = mantissa_end;
+            }
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+        text.parse().map_err(|_| SvgError {
+            position: start,
+            message: format!("bad number '{}'", text),
+        })
+    }
+
+    // Arc flags are a single 0 or 1 and need nothing after them, e.g. "a1 1 0 00 1 1"
+    fn flag(&mut self) -> Result<bool, SvgError> {
+        match self.peek() {
+            Some(b'0') => {
+                self.position += 1;
+                Ok(false)
+            }
+            Some(b'1') => {
+                self.position += 1;
+                Ok(true)
+            }
+            _ => Err(self.error("expected a flag, 0 or 1")),
+        }
+    }
+}
+
+impl SvgPath {
+    pub fn parse(d: &str) -> Result<SvgPath, SvgError> {
+        let mut lexer = Lexer { bytes: d.as_bytes(), position: 0 };
+        let mut commands = Vec::new();
+        let (mut pen, mut start) = ([0.0, 0.0], [0.0, 0.0]);
+        // The control point S or T reflects, if the command before was a C/S or a Q/T
+        let (mut last_cubic, mut last_quadratic): (Option<Point>, Option<Point>) = (None, None);
+        // Numbers without a letter repeat the last command, or line-to after a move-to
+        let mut repeat: Option<u8> = None;
+
+        while let Some(next) = lexer.peek() {
+            let letter = if next.is_ascii_alphabetic() {
+                lexer.position += 1;
+                next
+            } else {
+                match repeat {
+                    Some(letter) if lexer.at_number() => letter,
+                    Some(_) => return Err(lexer.error(format!("unexpected '{}'", next as char))),
+                    None => return Err(lexer.error("path data has to start with a move-to")),
+                }
+            };
+            if commands.is_empty() && !letter.eq_ignore_ascii_case(&b'M') {
+                return Err(SvgError {
+                    position: lexer.position - 1,
+                    message: "path data has to start with a move-to".to_string(),
+                });
+            }
+            let base = if letter.is_ascii_lowercase() { pen } else { [0.0, 0.0] };
+            let point = |lexer: &mut Lexer| -> Result<Point, SvgError> {
+                let x = lexer.number()?;
+                let y = lexer.number()?;
+                Ok([base[0] + x, base[1] + y])
+            };
+            let reflect = |control: Option<Point>| control.map_or(pen, |c| [2.0 * pen[0] - c[0], 2.0 * pen[1] - c[1]]);
+
+            let command = match letter.to_ascii_uppercase() {
+                b'M' => {
+                    let to = point(&mut lexer)?;
+                    start = to;
+                    PathCommand::MoveTo(to)
+                }
+                b'L' => PathCommand::LineTo(point(&mut lexer)?),
+                b'H' => PathCommand::LineTo([base[0] + lexer.number()?, pen[1]]),
+                b'V' => PathCommand::LineTo([pen[0], base[1] + lexer.number()?]),
+                b'C' => PathCommand::Cubic {
+                    c1: point(&mut lexer)?,
+                    c2: point(&mut lexer)?,
+                    to: point(&mut lexer)?,
+                },
+                b'S' => PathCommand::Cubic {
+                    c1: reflect(last_cubic),
+                    c2: point(&mut lexer)?,
+                    to: point(&mut lexer)?,
+                },
+                b'Q' => PathCommand::Quadratic {
+                    control: point(&mut lexer)?,
+                    to: point(&mut lexer)?,
+                },
+                b'T' => PathCommand::Quadratic {
+                    control: reflect(last_quadratic),
+                    to: point(&mut lexer)?,
+                },
+                b'A' => PathCommand::Arc {
+                    radii: [lexer.number()?, lexer.number()?],
+                    rotation: lexer.number()?,
+                    large_arc: lexer.flag()?,
+                    sweep: lexer.flag()?,
+                    to: point(&mut lexer)?,
+                },
+                b'Z' => PathCommand::Close,
+                _ => {
+                    return Err(SvgError {
+                        position: lexer.position - 1,
+                        message: format!("unknown command '{}'", letter as char),
+                    })
+                }
+            };
+
+            (last_cubic, last_quadratic) = match command {
+                PathCommand::Cubic { c2, .. } => (Some(c2), None),
+                PathCommand::Quadratic { control, .. } => (None, Some(control)),
+                _ => (None, None),
+            };
+            pen = match command {
+                PathCommand::MoveTo(to) | PathCommand::LineTo(to) => to,
+                PathCommand::Quadratic { to, .. } | PathCommand::Cubic { to, .. } | PathCommand::Arc { to, .. } => to,
+                PathCommand::Close => start,
+            };
+            repeat = match letter {
+                b'M' => Some(b'L'),
+                b'm' => Some(b'l'),
+                b'Z' | b'z' => None,
+                _ => Some(letter),
+            };
+            commands.push(command);
+
+            // Z takes no numbers, so numbers after it have nothing to repeat
+            if repeat.is_none() && lexer.at_number() {
+                return Err(lexer.error("numbers after a close-path"));
+            }
+        }
+        Ok(SvgPath { commands })
+    }
+
+    // Subpaths with fewer than two points, e.g. a lone move-to, are left out
+    pub fn flatten(&self, tolerance: f32) -> Vec<Subpath> {
+        let mut subpaths = Vec::new();
+        let mut current: Option<Subpath> = None;
+        let (mut pen, mut start) = ([0.0, 0.0], [0.0, 0.0]);
+        let mut finish = |subpath: Option<Subpath>| {
+            if let Some(subpath) = subpath.filter(|s| s.points.len() > 1) {
+                subpaths.push(subpath);
+            }
+        };
+
+        for command in &self.commands {
+            match *command {
+                PathCommand::MoveTo(to) => {
+                    finish(current.take());
+                    (pen, start) = (to, to);
+                    continue;
+                }
+                PathCommand::Close => {
+                    finish(current.take().map(|subpath| Subpath { closed: true, ..subpath }));
+                    // Drawing on without a move-to starts again from the subpath's start
+                    pen = start;
+                    continue;
+                }
+                _ => {}
+            }
+            let points = &mut current.get_or_insert_with(|| Subpath { points: vec![pen], closed: false }).points;
+            pen = match *command {
+                PathCommand::LineTo(to) => {
+                    points.push(to);
+                    to
+                }
+                PathCommand::Quadratic { control, to } => {
+                    flatten_quadratic(points, pen, control, to, tolerance);
+                    to
+                }
+                PathCommand::Cubic { c1, c2, to } => {
+                    flatten_cubic(points, pen, c1, c2, to, tolerance);
+                    to
+                }
+                PathCommand::Arc { radii, rotation, large_arc, sweep, to } => {
+                    flatten_endpoint_arc(points, pen, radii, rotation, large_arc, sweep, to, tolerance);
+                    to
+                }
+                PathCommand::MoveTo(_) | PathCommand::Close => unreachable!(),
+            };
+        }
+        finish(current);
+        subpaths
+    }
+
+    // Flattened finely enough to look smooth across a canvas, then scaled into clip space with y
+    // up, centred and keeping its aspect ratio; `margin` is left free on the longer side, from 0 to 1
+    pub fn fit_to_clip(&self, margin: f32) -> Vec<Subpath> {
+        // The control points are close enough to the path's size to pick a tolerance from
+        let mut extent = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        let mut grow = |p: Point| extent = (extent.0.min(p[0]), extent.1.min(p[1]), extent.2.max(p[0]), extent.3.max(p[1]));
+        for command in &self.commands {
+            match *command {
+                PathCommand::MoveTo(to) | PathCommand::LineTo(to) => grow(to),
+                PathCommand::Quadratic { control, to } => [control, to].into_iter().for_each(&mut grow),
+                PathCommand::Cubic { c1, c2, to } => [c1, c2, to].into_iter().for_each(&mut grow),
+                PathCommand::Arc { radii, to, .. } => {
+                    let r = radii[0].abs().max(radii[1].abs());
+                    [[to[0] - r, to[1] - r], [to[0] + r, to[1] + r]].into_iter().for_each(&mut grow);
+                }
+                PathCommand::Close => {}
+            }
+        }
+        let size = (extent.2 - extent.0).max(extent.3 - extent.1).max(0.0);
+        let mut subpaths = self.flatten(if size > 0.0 { size / 1000.0 } else { 1.0 });
+
+        let Some((min, max)) = bounds(&subpaths) else {
+            return subpaths;
+        };
+        let size = (max[0] - min[0]).max(max[1] - min[1]);
+        let scale = if size > 0.0 { 2.0 * (1.0 - margin) / size } else { 1.0 };
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+        for point in subpaths.iter_mut().flat_map(|s| s.points.iter_mut()) {
+            *point = [(point[0] - center[0]) * scale, (center[1] - point[1]) * scale];
+        }
+        subpaths
+    }
+}
+
+// The box around every point
+pub fn bounds(subpaths: &[Subpath]) -> Option<(Point, Point)> {
+    subpaths.iter().flat_map(|s| s.points.iter()).fold(None, |bounds, p| {
+        let (min, max) = bounds.unwrap_or((*p, *p));
+        Some(([min[0].min(p[0]), min[1].min(p[1])], [max[0].max(p[0]), max[1].max(p[1])]))
+    })
+}
+
+// SVG's arc from the pen to `to`, turned into a centre and angles as in the SVG implementation
+// notes (F.6.5); radii too small to reach are scaled up, and a zero radius is a straight line
+#[allow(clippy::too_many_arguments)]
+fn flatten_endpoint_arc(out: &mut Vec<Point>, from: Point, radii: [f32; 2], rotation: f32, large_arc: bool, sweep: bool, to: Point, tolerance: f32) {
+    if from == to {
+        return;
+    }
+    let (mut rx, mut ry) = (radii[0].abs(), radii[1].abs());
+    if rx == 0.0 || ry == 0.0 {
+        out.push(to);
+        return;
+    }
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (dx, dy) = ((from[0] - to[0]) / 2.0, (from[1] - to[1]) / 2.0);
+    let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let (rx2, ry2) = (rx * rx, ry * ry);
+    let numerator = rx2 * ry2 - rx2 * y1 * y1 - ry2 * x1 * x1;
+    let denominator = rx2 * y1 * y1 + ry2 * x1 * x1;
+    let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        coefficient = -coefficient;
+    }
+    let (cx1, cy1) = (coefficient * rx * y1 / ry, -coefficient * ry * x1 / rx);
+    let center = [cos * cx1 - sin * cy1 + (from[0] + to[0]) / 2.0, sin * cx1 + cos * cy1 + (from[1] + to[1]) / 2.0];
+
+    let start = ((y1 - cy1) / ry).atan2((x1 - cx1) / rx);
+    let end = ((-y1 - cy1) / ry).atan2((-x1 - cx1) / rx);
+    let mut delta = end - start;
+    if sweep && delta < 0.0 {
+        delta += TAU;
+    } else if !sweep && delta > 0.0 {
+        delta -= TAU;
+    }
+    flatten_arc(out, center, [rx, ry], rotation.to_radians(), start, delta, tolerance);
+    if let Some(last) = out.last_mut() {
+        *last = to;
+    }
+}
+
+// How many times the ring winds round p, counter-clockwise positive
+fn winding(ring: &[Point], p: Point) -> i32 {
+    let mut winding = 0;
+    for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+        let side = (b[0] - a[0]) * (p[1] - a[1]) - (p[0] - a[0]) * (b[1] - a[1]);
+        if a[1] <= p[1] && b[1] > p[1] && side > 0.0 {
+            winding += 1;
+        } else if a[1] > p[1] && b[1] <= p[1] && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+// Triangles covering what `rule` fills, over the returned points. Each subpath is a ring; one
+// whose inside is filled is triangulated with the rings directly inside it as holes, and those
+// rings get filled, or not, in their turn. If any edges cross, fill_slabs takes over.
+pub fn fill(subpaths: &[Subpath], rule: FillRule) -> (Vec<Point>, Vec<u32>) {
+    let edges: Vec<(Point, Point)> = subpaths
+        .iter()
+        .filter(|s| s.points.len() > 2)
+        .flat_map(|s| s.points.iter().zip(s.points.iter().cycle().skip(1)).map(|(&a, &b)| (a, b)))
+        .filter(|(a, b)| a != b)
+        .collect();
+    let crossings = crossing_heights(&edges);
+    if !crossings.is_empty() {
+        return fill_slabs(&edges, crossings, rule);
+    }
+
+    let rings: Vec<(&[Point], f32)> = subpaths
+        .iter()
+        .map(|s| (s.points.as_slice(), signed_area(&s.points)))
+        .filter(|(points, area)| points.len() > 2 && *area != 0.0)
+        .collect();
+
+    // The smallest ring around each ring's first point is the one it sits directly inside
+    let mut parents = Vec::with_capacity(rings.len());
+    let mut filled = Vec::with_capacity(rings.len());
+    for (i, (ring, area)) in rings.iter().enumerate() {
+        let p = ring[0];
+        let mut around = 0;
+        let mut parent: Option<usize> = None;
+        for (j, (other, other_area)) in rings.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            let w = winding(other, p);
+            around += w;
+            if w != 0 && parent.is_none_or(|k| other_area.abs() < rings[k].1.abs()) {
+                parent = Some(j);
+            }
+        }
+        parents.push(parent);
+        // Just inside the ring it winds once more, its own way
+        filled.push(rule.is_filled(around + area.signum() as i32));
+    }
+
+    let mut points = Vec::new();
+    let mut indices = Vec::new();
+    for (i, (ring, _)) in rings.iter().enumerate() {
+        if !filled[i] {
+            continue;
+        }
+        let base = points.len() as u32;
+        let mut polygon = ring.to_vec();
+        let mut hole_starts = Vec::new();
+        for (j, (hole, _)) in rings.iter().enumerate() {
+            if parents[j] == Some(i) {
+                hole_starts.push(polygon.len());
+                polygon.extend_from_slice(hole);
+            }
+        }
+        indices.extend(triangulate(&polygon, &hole_starts).into_iter().map(|index| base + index));
+        points.extend(polygon);
+    }
+    (points, indices)
+}
+
+// The y of every point where two edges cross, not counting edges that only touch
+fn crossing_heights(edges: &[(Point, Point)]) -> Vec<f32> {
+    let side = |a: Point, b: Point, p: Point| (b[0] - a[0]) * (p[1] - a[1]) - (p[0] - a[0]) * (b[1] - a[1]);
+    // Sorted by left end, so each edge is only checked against the ones overlapping it in x
+    let mut sorted: Vec<(f32, f32, usize)> = edges.iter().enumerate().map(|(i, (a, b))| (a[0].min(b[0]), a[0].max(b[0]), i)).collect();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut heights = Vec::new();
+    for (k, &(_, right, i)) in sorted.iter().enumerate() {
+        let (a, b) = edges[i];
+        for &(left, _, j) in &sorted[k + 1..] {
+            if left > right {
+                break;
+            }
+            let (c, d) = edges[j];
+            let (sc, sd) = (side(a, b, c), side(a, b, d));
+            let (sa, sb) = (side(c, d, a), side(c, d, b));
+            if sc * sd < 0.0 && sa * sb < 0.0 {
+                let t = sa / (sa - sb);
+                heights.push(a[1] + (b[1] - a[1]) * t);
+            }
+        }
+    }
+    heights
+}
+
+// Fills any edges, crossing or not: the path is cut into horizontal slabs at every vertex and
+// crossing, so no two edges cross inside a slab, and each slab is walked left to right keeping
+// the winding number; spans the rule fills become two triangles.
+fn fill_slabs(edges: &[(Point, Point)], mut heights: Vec<f32>, rule: FillRule) -> (Vec<Point>, Vec<u32>) {
+    // Upward edges wind +1, matching winding()
+    let edges: Vec<(Point, Point, i32)> = edges
+        .iter()
+        .filter(|(a, b)| a[1] != b[1])
+        .map(|&(a, b)| if a[1] < b[1] { (a, b, 1) } else { (b, a, -1) })
+        .collect();
+    heights.extend(edges.iter().flat_map(|(a, b, _)| [a[1], b[1]]));
+    heights.sort_by(f32::total_cmp);
+    heights.dedup();
+
+    let x_at = |(a, b): (Point, Point), y: f32| a[0] + (b[0] - a[0]) * (y - a[1]) / (b[1] - a[1]);
+    let mut points = Vec::new();
+    let mut indices = Vec::new();
+    let mut active = Vec::new();
+    for slab in heights.windows(2) {
+        let (y0, y1) = (slab[0], slab[1]);
+        let middle = (y0 + y1) / 2.0;
+        active.clear();
+        active.extend(edges.iter().filter(|(a, b, _)| a[1] < middle && b[1] > middle).map(|&(a, b, direction)| {
+            (x_at((a, b), middle), x_at((a, b), y0), x_at((a, b), y1), direction)
+        }));
+        active.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut winding = 0;
+        for pair in active.windows(2) {
+            // Right of an edge the ray to +x no longer meets it
+            winding -= pair[0].3;
+            if !rule.is_filled(winding) {
+                continue;
+            }
+            let base = points.len() as u32;
+            points.extend([[pair[0].1, y0], [pair[1].1, y0], [pair[1].2, y1], [pair[0].2, y1]]);
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+    (points, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PathCommand::*;
+
+    fn area(points: &[Point], indices: &[u32]) -> f32 {
+        indices
+            .chunks(3)
+            .map(|t| signed_area(&[points[t[0] as usize], points[t[1] as usize], points[t[2] as usize]]))
+            .sum()
+    }
+
+    #[test]
+    fn absolute_and_relative_commands() {
+        let path = SvgPath::parse("M10 20 L30 40 h10 v-5 H0 V0 l5,5 z m1 1 2 2").unwrap();
+        assert_eq!(
+            path.commands,
+            vec![
+                MoveTo([10.0, 20.0]),
+                LineTo([30.0, 40.0]),
+                LineTo([40.0, 40.0]),
+                LineTo([40.0, 35.0]),
+                LineTo([0.0, 35.0]),
+                LineTo([0.0, 0.0]),
+                LineTo([5.0, 5.0]),
+                Close,
+                // Relative to the start of the closed subpath, and the extra pair is a line
+                MoveTo([11.0, 21.0]),
+                LineTo([13.0, 23.0]),
+            ]
+        );
+
+        // Numbers packed as tightly as the grammar allows
+        let packed = SvgPath::parse("M0,0L.5.5-1e1-2e-1").unwrap();
+        assert_eq!(packed.commands[1..], [LineTo([0.5, 0.5]), LineTo([-10.0, -0.2])]);
+    }
+
+    #[test]
+    fn curves_and_reflections() {
+        let path = SvgPath::parse("M0 0 C0 1 1 1 1 0 S2 -1 2 0 q1 1 2 0 t2 0 a1 2 30 10 3 4").unwrap();
+        assert_eq!(
+            path.commands[1..],
+            [
+                Cubic { c1: [0.0, 1.0], c2: [1.0, 1.0], to: [1.0, 0.0] },
+                // c1 mirrors the last c2 about the pen
+                Cubic { c1: [1.0, -1.0], c2: [2.0, -1.0], to: [2.0, 0.0] },
+                Quadratic { control: [3.0, 1.0], to: [4.0, 0.0] },
+                Quadratic { control: [5.0, -1.0], to: [6.0, 0.0] },
+                Arc { radii: [1.0, 2.0], rotation: 30.0, large_arc: true, sweep: false, to: [9.0, 4.0] },
+            ]
+        );
+        // S straight after a move-to has no control to reflect
+        let smooth = SvgPath::parse("M1 1 S2 2 3 3").unwrap();
+        assert_eq!(smooth.commands[1], Cubic { c1: [1.0, 1.0], c2: [2.0, 2.0], to: [3.0, 3.0] });
+    }
+
+    #[test]
+    fn errors_point_at_the_data() {
+        assert_eq!(SvgPath::parse("L1 1").unwrap_err().message, "path data has to start with a move-to");
+        assert_eq!(SvgPath::parse("M1 1 X").unwrap_err().to_string(), "at 5: unknown command 'X'");
+        assert_eq!(SvgPath::parse("M1").unwrap_err().position, 2);
+        assert_eq!(SvgPath::parse("M0 0 a1 1 0 2 0 1 1").unwrap_err().message, "expected a flag, 0 or 1");
+        assert_eq!(SvgPath::parse("M0 0 L1 1 Z 2 2").unwrap_err().message, "numbers after a close-path");
+        assert_eq!(SvgPath::parse("  ").unwrap(), SvgPath::default());
+    }
+
+    #[test]
+    fn flattening() {
+        // A semicircle of radius 5 and then back along the diameter
+        let path = SvgPath::parse("M0 0 A5 5 0 0 1 10 0 Z L0 10").unwrap();
+        let subpaths = path.flatten(0.01);
+        assert_eq!(subpaths.len(), 2);
+        let arc = &subpaths[0];
+        assert!(arc.closed);
+        assert_eq!((arc.points[0], *arc.points.last().unwrap()), ([0.0, 0.0], [10.0, 0.0]));
+        assert!(arc.points.iter().all(|p| ((p[0] - 5.0).hypot(p[1]) - 5.0).abs() < 1e-3));
+        // Sweep 1 goes the positive-angle way, clockwise on screen with y down, so over the top
+        assert!(arc.points.iter().all(|p| p[1] <= 1e-3));
+        assert!(arc.points.iter().any(|p| p[1] < -4.9));
+        // After Z the pen is back at the subpath's start
+        assert_eq!(subpaths[1], Subpath { points: vec![[0.0, 0.0], [0.0, 10.0]], closed: false });
+
+        // Radii too small for the distance are scaled up to a half circle
+        let small = SvgPath::parse("M0 0 A1 1 0 0 0 4 0").unwrap().flatten(0.01);
+        assert!(small[0].points.iter().all(|p| ((p[0] - 2.0).hypot(p[1]) - 2.0).abs() < 1e-3));
+    }
+
+    #[test]
+    fn fill_rules() {
+        // A square, as triangles
+        let square = SvgPath::parse("M0 0 H10 V10 H0 Z").unwrap().flatten(0.1);
+        let (points, indices) = fill(&square, FillRule::NonZero);
+        assert_eq!(points, vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+
+        // A smaller square inside, drawn the same way round and the other
+        let same = SvgPath::parse("M0 0 H10 V10 H0 Z M2 2 H8 V8 H2 Z").unwrap().flatten(0.1);
+        let opposite = SvgPath::parse("M0 0 H10 V10 H0 Z M2 2 V8 H8 V2 Z").unwrap().flatten(0.1);
+        let filled = |subpaths: &[Subpath], rule| {
+            let (points, indices) = fill(subpaths, rule);
+            area(&points, &indices)
+        };
+        assert_eq!(filled(&same, FillRule::NonZero), 100.0);
+        assert_eq!(filled(&same, FillRule::EvenOdd), 64.0);
+        assert_eq!(filled(&opposite, FillRule::NonZero), 64.0);
+        assert_eq!(filled(&opposite, FillRule::EvenOdd), 64.0);
+
+        // A dot in the hole of a ring, like the letter "o" with a speck inside
+        let island = SvgPath::parse("M0 0 H10 V10 H0 Z M2 2 V8 H8 V2 Z M4 4 H6 V6 H4 Z").unwrap().flatten(0.1);
+        assert_eq!(filled(&island, FillRule::NonZero), 68.0);
+        assert_eq!(filled(&island, FillRule::EvenOdd), 68.0);
+
+        // A pentagram in one stroke: nonzero fills the middle pentagon, even-odd leaves it out
+        let star: String = (0..5)
+            .map(|i| {
+                let angle = std::f32::consts::FRAC_PI_2 + i as f32 * 2.0 * TAU / 5.0;
+                format!("{} {} {} ", if i == 0 { "M" } else { "L" }, 10.0 * angle.cos(), 10.0 * angle.sin())
+            })
+            .collect();
+        let star = SvgPath::parse(&format!("{}Z", star)).unwrap().flatten(0.1);
+        let inner = 10.0 * (TAU / 5.0).cos() / (TAU / 10.0).cos();
+        let pentagon = 2.5 * inner * inner * (TAU / 5.0).sin();
+        let (nonzero, even_odd) = (filled(&star, FillRule::NonZero), filled(&star, FillRule::EvenOdd));
+        assert!((nonzero - even_odd - pentagon).abs() < 1e-3, "{} {} {}", nonzero, even_odd, pentagon);
+        // Nonzero covers the star's outline: ten triangles from the centre, outer to inner corner
+        assert!((nonzero - 5.0 * 10.0 * inner * (TAU / 10.0).sin()).abs() < 1e-3);
+
+        // A bowtie is two triangles by either rule
+        let bowtie = SvgPath::parse("M0 0 L1 1 L1 0 L0 1 Z").unwrap().flatten(0.1);
+        assert!((filled(&bowtie, FillRule::NonZero) - 0.5).abs() < 1e-6);
+        assert!((filled(&bowtie, FillRule::EvenOdd) - 0.5).abs() < 1e-6);
+
+        // Open subpaths are filled as if closed
+        let (_, indices) = fill(&SvgPath::parse("M0 0 L4 0 L4 4").unwrap().flatten(0.1), FillRule::EvenOdd);
+        assert_eq!(indices.len(), 3);
+        assert_eq!(FillRule::from_name("evenodd"), Some(FillRule::EvenOdd));
+    }
+
+    #[test]
+    fn fits_into_clip_space() {
+        let subpaths = SvgPath::parse("M0 0 H20 V10 H0 Z").unwrap().fit_to_clip(0.1);
+        let close = |a: Point, b: Point| (a[0] - b[0]).abs() < 1e-6 && (a[1] - b[1]).abs() < 1e-6;
+        let (min, max) = bounds(&subpaths).unwrap();
+        assert!(close(min, [-0.9, -0.45]) && close(max, [0.9, 0.45]));
+        // y is flipped, so the top left corner ends up top left in clip space too
+        assert!(close(subpaths[0].points[0], [-0.9, 0.45]));
+    }
+}
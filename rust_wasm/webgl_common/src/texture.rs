@@ -0,0 +1,99 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{ImageBitmap, WebGlRenderingContext, WebGlTexture};
+
+type Gl = WebGlRenderingContext;
+
+// A 2D RGBA texture. WebGL1 only mipmaps and repeats power-of-two sizes, other sizes are
+// clamped to the edge and filtered linearly.
+pub struct Texture {
+    gl: Gl,
+    texture: WebGlTexture,
+    width: u32,
+    height: u32,
+}
+
+impl Texture {
+    // `pixels` holds width * height RGBA bytes, the first row ends up at v = 0
+    pub fn from_rgba(gl: &Gl, width: u32, height: u32, pixels: &[u8]) -> Result<Self, JsValue> {
+        let texture = Texture::create(gl, width, height);
+        gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            Gl::TEXTURE_2D,
+            0,
+            Gl::RGBA as i32,
+            width as i32,
+            height as i32,
+            0,
+            Gl::RGBA,
+            Gl::UNSIGNED_BYTE,
+            Some(pixels),
+        )?;
+        texture.set_sampling();
+        Ok(texture)
+    }
+
+    // From an image the browser decoded, e.g. with createImageBitmap
+    pub fn from_image_bitmap(gl: &Gl, image: &ImageBitmap) -> Result<Self, JsValue> {
+        let texture = Texture::create(gl, image.width(), image.height());
+        gl.tex_image_2d_with_u32_and_u32_and_image_bitmap(Gl::TEXTURE_2D, 0, Gl::RGBA as i32, Gl::RGBA, Gl::UNSIGNED_BYTE, image)?;
+        texture.set_sampling();
+        Ok(texture)
+    }
+
+    // 1x1 white, for drawing untextured meshes with a textured program
+    pub fn white(gl: &Gl) -> Result<Self, JsValue> {
+        Texture::from_rgba(gl, 1, 1, &[255, 255, 255, 255])
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn gl_texture(&self) -> &WebGlTexture {
+        &self.texture
+    }
+
+    // Binds to texture unit `unit`, the value the sampler uniform has to be set to
+    pub fn bind(&self, unit: u32) {
+        self.gl.active_texture(Gl::TEXTURE0 + unit);
+        self.gl.bind_texture(Gl::TEXTURE_2D, Some(&self.texture));
+    }
+
+    pub fn delete(self) {
+        self.gl.delete_texture(Some(&self.texture));
+    }
+
+    // Leaves the new texture bound to TEXTURE_2D for the upload
+    fn create(gl: &Gl, width: u32, height: u32) -> Self {
+        let texture = gl.create_texture().unwrap();
+        gl.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+        Texture {
+            gl: gl.clone(),
+            texture,
+            width,
+            height,
+        }
+    }
+
+    fn set_sampling(&self) {
+        let gl = &self.gl;
+        if is_power_of_two(self.width) && is_power_of_two(self.height) {
+            gl.generate_mipmap(Gl::TEXTURE_2D);
+            gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MIN_FILTER, Gl::LINEAR_MIPMAP_LINEAR as i32);
+            gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_S, Gl::REPEAT as i32);
+            gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_T, Gl::REPEAT as i32);
+        } else {
+            gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MIN_FILTER, Gl::LINEAR as i32);
+            gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_S, Gl::CLAMP_TO_EDGE as i32);
+            gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_T, Gl::CLAMP_TO_EDGE as i32);
+        }
+        gl.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MAG_FILTER, Gl::LINEAR as i32);
+    }
+}
+
+pub fn is_power_of_two(n: u32) -> bool {
+    n != 0 && n & (n - 1) == 0
+}
@@ -0,0 +1,376 @@
+// Filled polygons: a simple outline, concave or not, with any number of holes, cut into triangles
+// by ear clipping. Points are given earcut style, the outline followed by each hole in one list
+// with the index each hole starts at, and the triangles index straight into that list.
+use crate::math::Vec3;
+
+pub type Point = [f32; 2];
+
+// Twice the signed area of a, b, c; positive when they turn counter-clockwise
+fn cross(a: Point, b: Point, c: Point) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+// Positive for a counter-clockwise ring
+pub fn signed_area(points: &[Point]) -> f32 {
+    let sum: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a[0] * b[1] - b[0] * a[1])
+        .sum();
+    sum / 2.0
+}
+
+// On the edges counts as inside
+fn in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+    let (d1, d2, d3) = (cross(a, b, p), cross(b, c, p), cross(c, a, p));
+    let negative = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let positive = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(negative && positive)
+}
+
+// Counter-clockwise triangles over `points`. Holes start at each index in `hole_starts`, in
+// order; either winding works for the outline and the holes. Repeated points are skipped and
+// rings with no area are ignored. The holes must sit inside the outline without crossing it or
+// each other; anything less than simple still gets triangles, just not necessarily the right ones.
+pub fn triangulate(points: &[Point], hole_starts: &[usize]) -> Vec<u32> {
+    let mut ends: Vec<usize> = hole_starts.iter().map(|&start| start.min(points.len())).collect();
+    ends.push(points.len());
+    let mut rings = Vec::new();
+    let mut start = 0;
+    for &end in &ends {
+        rings.push(ring(points, start..end.max(start)));
+        start = end.max(start);
+    }
+
+    let mut outline = rings.remove(0);
+    let area = signed_area(&outline.iter().map(|&i| points[i as usize]).collect::<Vec<_>>());
+    if outline.len() < 3 || area == 0.0 {
+        return Vec::new();
+    }
+    if area < 0.0 {
+        outline.reverse();
+    }
+
+    // Holes go the other way round, so the outline with holes bridged in keeps the inside on its left
+    let mut holes: Vec<Vec<u32>> = rings
+        .into_iter()
+        .filter_map(|mut hole| {
+            let area = signed_area(&hole.iter().map(|&i| points[i as usize]).collect::<Vec<_>>());
+            if hole.len() < 3 || area == 0.0 {
+                return None;
+            }
+            if area > 0.0 {
+                hole.reverse();
+            }
+            Some(hole)
+        })
+        .collect();
+    // Rightmost first, so every bridge can reach the outline without crossing a hole still to come
+    let right = |hole: &Vec<u32>| hole.iter().map(|&i| points[i as usize][0]).fold(f32::MIN, f32::max);
+    holes.sort_by(|a, b| right(b).total_cmp(&right(a)));
+    for hole in holes {
+        outline = bridge_hole(points, outline, &hole);
+    }
+
+    clip_ears(points, &outline)
+}
+
+// The indices in `range` with repeated points, and a last point equal to the first, left out
+fn ring(points: &[Point], range: std::ops::Range<usize>) -> Vec<u32> {
+    let mut ring: Vec<u32> = Vec::with_capacity(range.len());
+    for i in range {
+        if ring.last().is_none_or(|&last| points[last as usize] != points[i]) {
+            ring.push(i as u32);
+        }
+    }
+    while ring.len() > 1 && points[ring[0] as usize] == points[ring[ring.len() - 1] as usize] {
+        ring.pop();
+    }
+    ring
+}
+
+// Whether `p` is on the inside of the corner at position k of the ring
+fn locally_inside(points: &[Point], ring: &[u32], k: usize, p: Point) -> bool {
+    let n = ring.len();
+    let at = |k: usize| points[ring[k % n] as usize];
+    let (prev, vertex, next) = (at(k + n - 1), at(k), at(k + 1));
+    if cross(prev, vertex, next) >= 0.0 {
+        cross(vertex, p, next) <= 0.0 && cross(vertex, prev, p) <= 0.0
+    } else {
+        cross(vertex, p, prev) > 0.0 || cross(vertex, next, p) > 0.0
+    }
+}
+
+// Cuts the outline open at a vertex the hole's rightmost point can see, and walks round the hole
+// and back through a pair of coincident edges
+fn bridge_hole(points: &[Point], outline: Vec<u32>, hole: &[u32]) -> Vec<u32> {
+    let p = |i: u32| points[i as usize];
+    let m_at = (0..hole.len()).max_by(|&a, &b| p(hole[a])[0].total_cmp(&p(hole[b])[0])).unwrap();
+    let m = p(hole[m_at]);
+    let n = outline.len();
+
+    // The nearest edge a ray to the right of m hits, coming from the inside
+    let mut hit: Option<(f32, usize)> = None;
+    for k in 0..n {
+        let (a, b) = (p(outline[k]), p(outline[(k + 1) % n]));
+        if a[1] <= m[1] && m[1] <= b[1] && a[1] != b[1] {
+            let x = a[0] + (m[1] - a[1]) * (b[0] - a[0]) / (b[1] - a[1]);
+            if x >= m[0] && hit.is_none_or(|(best, _)| x < best) {
+                let end = if a[0] > b[0] { k } else { (k + 1) % n };
+                hit = Some((x, end));
+            }
+        }
+    }
+    let Some((hit_x, end)) = hit else {
+        // Not inside the outline at all; the cut still keeps the ring in one piece
+        return splice(outline, 0, hole, m_at);
+    };
+
+    // The edge's end is visible unless the outline pokes into the triangle m, hit, end. Then the
+    // vertex in there closest in angle to the ray is, and it has to face m.
+    let hit_point = [hit_x, m[1]];
+    let end_point = p(outline[end]);
+    let mut best: Option<(f32, usize)> = None;
+    for k in 0..n {
+        let q = p(outline[k]);
+        if q[0] < m[0] || q[0] > hit_x || !in_triangle(q, m, hit_point, end_point) {
+            continue;
+        }
+        if !locally_inside(points, &outline, k, m) {
+            continue;
+        }
+        let tan = if q[0] > m[0] { (m[1] - q[1]).abs() / (q[0] - m[0]) } else { f32::INFINITY };
+        let closer = best.is_none_or(|(best_tan, best_k)| tan < best_tan || (tan == best_tan && q[0] > p(outline[best_k])[0]));
+        if closer {
+            best = Some((tan, k));
+        }
+    }
+    splice(outline, best.map_or(end, |(_, k)| k), hole, m_at)
+}
+
+fn splice(outline: Vec<u32>, at: usize, hole: &[u32], hole_at: usize) -> Vec<u32> {
+    let mut out = Vec::with_capacity(outline.len() + hole.len() + 2);
+    out.extend_from_slice(&outline[..=at]);
+    out.extend_from_slice(&hole[hole_at..]);
+    out.extend_from_slice(&hole[..=hole_at]);
+    out.push(outline[at]);
+    out.extend_from_slice(&outline[at + 1..]);
+    out
+}
+
+// Repeatedly cuts off a convex corner with nothing of the ring inside it. A convex ring comes out
+// as a fan from its first point.
+fn clip_ears(points: &[Point], ring: &[u32]) -> Vec<u32> {
+    let n = ring.len();
+    let p = |k: usize| points[ring[k] as usize];
+    let mut prev: Vec<usize> = (0..n).map(|k| (k + n - 1) % n).collect();
+    let mut next: Vec<usize> = (0..n).map(|k| (k + 1) % n).collect();
+    let mut triangles = Vec::with_capacity(3 * (n - 2));
+
+    let is_ear = |prev: &[usize], next: &[usize], b: usize| {
+        let (a, c) = (prev[b], next[b]);
+        let (pa, pb, pc) = (p(a), p(b), p(c));
+        if cross(pa, pb, pc) <= 0.0 {
+            return false;
+        }
+        // Only a reflex corner can be in the way; the copies bridges leave of a, b and c are not
+        let mut k = next[c];
+        while k != a {
+            let q = p(k);
+            if q != pa && q != pb && q != pc && in_triangle(q, pa, pb, pc) && cross(p(prev[k]), q, p(next[k])) <= 0.0 {
+                return false;
+            }
+            k = next[k];
+        }
+        true
+    };
+
+    let mut remaining = n;
+    let mut b = 1 % n;
+    let mut stalled = 0;
+    while remaining > 3 {
+        let (a, c) = (prev[b], next[b]);
+        let clip = if is_ear(&prev, &next, b) {
+            true
+        } else {
+            stalled += 1;
+            stalled > remaining
+        };
+        if !clip {
+            b = c;
+            continue;
+        }
+        if stalled > remaining {
+            // A whole lap without an ear: the ring isn't simple. Drop a corner with no area if
+            // there is one, otherwise cut this one regardless.
+            let mut k = b;
+            for _ in 0..remaining {
+                if cross(p(prev[k]), p(k), p(next[k])) == 0.0 {
+                    break;
+                }
+                k = next[k];
+            }
+            if cross(p(prev[k]), p(k), p(next[k])) == 0.0 {
+                next[prev[k]] = next[k];
+                prev[next[k]] = prev[k];
+                remaining -= 1;
+                b = next[k];
+                stalled = 0;
+                continue;
+            }
+        }
+        if cross(p(a), p(b), p(c)) > 0.0 {
+            triangles.extend_from_slice(&[ring[a], ring[b], ring[c]]);
+        }
+        next[a] = c;
+        prev[c] = a;
+        remaining -= 1;
+        b = c;
+        stalled = 0;
+    }
+    let (a, c) = (prev[b], next[b]);
+    if cross(p(a), p(b), p(c)) > 0.0 {
+        triangles.extend_from_slice(&[ring[a], ring[b], ring[c]]);
+    }
+    triangles
+}
+
+// A flat 3D polygon seen from the side `normal` points to, with the axis it is least spread along
+// dropped. Counter-clockwise about the normal stays counter-clockwise.
+pub fn project(points: &[Vec3], normal: Vec3) -> Vec<Point> {
+    let (x, y, z) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+    points
+        .iter()
+        .map(|v| {
+            if z >= x && z >= y {
+                if normal.z >= 0.0 { [v.x, v.y] } else { [v.y, v.x] }
+            } else if x >= y {
+                if normal.x >= 0.0 { [v.y, v.z] } else { [v.z, v.y] }
+            } else if normal.y >= 0.0 {
+                [v.z, v.x]
+            } else {
+                [v.x, v.z]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Triangles that are counter-clockwise, inside the polygon and add up to its area
+    fn check(points: &[Point], hole_starts: &[usize], triangle_count: usize) -> Vec<u32> {
+        let triangles = triangulate(points, hole_starts);
+        assert_eq!(triangles.len(), 3 * triangle_count, "{:?}", triangles);
+
+        let mut bounds = vec![0];
+        bounds.extend_from_slice(hole_starts);
+        bounds.push(points.len());
+        let rings: Vec<&[Point]> = bounds.windows(2).map(|w| &points[w[0]..w[1]]).collect();
+        let expected = rings[0..1].iter().map(|r| signed_area(r).abs()).sum::<f32>() - rings[1..].iter().map(|r| signed_area(r).abs()).sum::<f32>();
+        let mut area = 0.0;
+        for t in triangles.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| points[t[k] as usize]);
+            assert!(cross(a, b, c) > 0.0, "{:?} isn't counter-clockwise", t);
+            area += cross(a, b, c) / 2.0;
+
+            // Even-odd over all the rings
+            let centroid = [(a[0] + b[0] + c[0]) / 3.0, (a[1] + b[1] + c[1]) / 3.0];
+            let crossings = rings
+                .iter()
+                .flat_map(|r| r.iter().zip(r.iter().cycle().skip(1)))
+                .filter(|(p, q)| (p[1] > centroid[1]) != (q[1] > centroid[1]) && centroid[0] < p[0] + (centroid[1] - p[1]) * (q[0] - p[0]) / (q[1] - p[1]))
+                .count();
+            assert!(crossings % 2 == 1, "{:?} is outside", t);
+        }
+        assert!((area - expected).abs() < 1e-4, "area {} != {}", area, expected);
+        triangles
+    }
+
+    #[test]
+    fn convex_outline_is_a_fan() {
+        let square = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        assert_eq!(check(&square, &[], 2), vec![0, 1, 2, 0, 2, 3]);
+
+        // Clockwise comes out counter-clockwise too
+        let mut clockwise = square;
+        clockwise.reverse();
+        check(&clockwise, &[], 2);
+    }
+
+    #[test]
+    fn concave_outlines() {
+        let l_shape = [[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]];
+        check(&l_shape, &[], 4);
+
+        // The fan from the first point would cover the notch
+        let arrow = [[0.0, 0.0], [2.0, 1.0], [4.0, 0.0], [2.0, 3.0]];
+        check(&arrow, &[], 2);
+
+        let comb = [[0.0, 0.0], [5.0, 0.0], [5.0, 3.0], [4.0, 3.0], [4.0, 1.0], [3.0, 1.0], [3.0, 3.0], [2.0, 3.0], [2.0, 1.0], [1.0, 1.0], [1.0, 3.0], [0.0, 3.0]];
+        check(&comb, &[], 10);
+
+        let star: Vec<Point> = (0..10)
+            .map(|k| {
+                let angle = k as f32 * std::f32::consts::PI / 5.0;
+                let r = if k % 2 == 0 { 1.0 } else { 0.4 };
+                [r * angle.cos(), r * angle.sin()]
+            })
+            .collect();
+        check(&star, &[], 8);
+
+        // A spiral, with most corners reflex
+        let spiral = [[0.0, 0.0], [6.0, 0.0], [6.0, 6.0], [1.0, 6.0], [1.0, 2.0], [4.0, 2.0], [4.0, 4.0], [3.0, 4.0], [3.0, 3.0], [2.0, 3.0], [2.0, 5.0], [5.0, 5.0], [5.0, 1.0], [0.0, 1.0]];
+        check(&spiral, &[], 12);
+    }
+
+    #[test]
+    fn holes() {
+        let framed = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0], [1.0, 1.0], [3.0, 1.0], [3.0, 3.0], [1.0, 3.0]];
+        check(&framed, &[4], 8);
+
+        // Two holes side by side, the left one clockwise already, and level with each other so
+        // the left one's bridge has to find its way past the right one
+        let two = [
+            [0.0, 0.0], [7.0, 0.0], [7.0, 3.0], [0.0, 3.0],
+            [1.0, 1.0], [1.0, 2.0], [3.0, 2.0], [3.0, 1.0],
+            [4.0, 1.0], [6.0, 1.0], [6.0, 2.0], [4.0, 2.0],
+        ];
+        check(&two, &[4, 8], 14);
+
+        // A triangular hole in a concave outline, right next to the notch
+        let notched = [[0.0, 0.0], [6.0, 0.0], [6.0, 4.0], [3.0, 1.5], [0.0, 4.0], [4.0, 0.5], [5.0, 0.5], [5.0, 1.5]];
+        check(&notched, &[5], 8);
+
+        // A hole touching the outline at a corner; the bridge has no length, so one triangle fewer
+        let touching = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0], [2.0, 1.0], [4.0, 2.0], [2.0, 3.0]];
+        check(&touching, &[4], 6);
+    }
+
+    #[test]
+    fn degenerate_input() {
+        assert!(triangulate(&[[0.0, 0.0], [1.0, 1.0]], &[]).is_empty());
+        assert!(triangulate(&[[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]], &[]).is_empty());
+
+        // A repeated closing point, a doubled point and a point in the middle of an edge
+        let square = [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0], [0.0, 0.0]];
+        let triangles = check(&square, &[], 3);
+        assert!(!triangles.contains(&3) && !triangles.contains(&6));
+
+        // A hole with no area, and one past the end of the points, are left out
+        let points = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.5, 0.5], [0.6, 0.6]];
+        assert_eq!(triangulate(&points, &[4, 9]).len(), 6);
+    }
+
+    #[test]
+    fn projected_faces_keep_their_winding() {
+        let face = [Vec3::ZERO, Vec3::Y, Vec3::new(0.0, 1.0, 1.0), Vec3::Z];
+        // Counter-clockwise about +x
+        assert!(signed_area(&project(&face, Vec3::X)) > 0.0);
+        assert!(signed_area(&project(&face, -Vec3::X)) < 0.0);
+        let floor = [Vec3::ZERO, Vec3::Z, Vec3::new(1.0, 0.0, 1.0), Vec3::X];
+        assert!(signed_area(&project(&floor, Vec3::Y)) > 0.0);
+        assert_eq!(project(&[Vec3::new(1.0, 2.0, 3.0)], Vec3::Z), vec![[1.0, 2.0]]);
+    }
+}
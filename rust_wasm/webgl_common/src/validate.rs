@@ -0,0 +1,580 @@
+// Native check of the WebGL 1 shaders, so a broken source fails `cargo test` instead of a demo page.
+// Test-only: naga is a dev-dependency and never ends up in the wasm builds.
+//
+// naga's GLSL frontend only reads desktop GLSL 450, so the GLSL ES 1.00 source is rewritten first:
+// attribute/varying become located in/out, plain uniforms get wrapped in a block, samplers are split
+// into the texture and sampler pair naga wants, gl_FragColor becomes a declared output and
+// `#extension GL_OES_standard_derivatives` turns into its macro, derivatives being core in 450.
+// The rewrite keeps every line where it was, so reported line numbers and snippets point into the
+// source that was passed in.
+//
+// GLSL 450 converts int to float implicitly and GLSL ES 1.00 doesn't, so `float s = 1;` gets through
+// naga and fails in the browser. Int literals used where a float is expected are looked for on the
+// ES source itself, after naga is happy with it.
+use std::collections::HashMap;
+use std::error::Error;
+
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+use crate::shader::{snippet, Diagnostic, ShaderError, ShaderStage};
+
+const FRAG_COLOR: &str = "_gl_FragColor";
+
+// A top level `attribute`/`varying`/`uniform` declaration
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Declaration {
+    qualifier: &'static str,
+    gl_type: String,
+    name: String,
+    // Anything after the `;`, kept as it was
+    rest: String,
+}
+
+pub fn validate_shader(stage: ShaderStage, source: &str) -> Result<(), ShaderError> {
+    let translated = to_glsl450(stage, source);
+    let naga_stage = match stage {
+        ShaderStage::Fragment => naga::ShaderStage::Fragment,
+        _ => naga::ShaderStage::Vertex,
+    };
+
+    let module = Frontend::default()
+        .parse(&Options::from(naga_stage), &translated)
+        .map_err(|errors| {
+            let diagnostics = errors
+                .errors
+                .iter()
+                .map(|e| diagnostic(source, &translated, e.meta.to_range().map(|r| r.start), e.kind.to_string()))
+                .collect();
+            error(stage, diagnostics)
+        })?;
+
+    Validator::new(ValidationFlags::all(), Capabilities::default())
+        .validate(&module)
+        .map_err(|err| {
+            let offset = err.spans().next().and_then(|(span, _)| span.to_range()).map(|r| r.start);
+            // The top level error only names the function, the cause is further down the chain
+            let mut message = err.as_inner().to_string();
+            let mut cause = err.as_inner().source();
+            while let Some(inner) = cause {
+                message = format!("{}: {}", message, inner);
+                cause = inner.source();
+            }
+            error(stage, vec![diagnostic(source, &translated, offset, message)])
+        })?;
+
+    let diagnostics = implicit_conversions(source);
+    if !diagnostics.is_empty() {
+        return Err(error(stage, diagnostics));
+    }
+    Ok(())
+}
+
+// Both stages, then the part of linking that can be checked without a driver:
+// every varying the fragment shader reads has to come out of the vertex shader with the same type.
+pub fn validate_program(vert_code: &str, frag_code: &str) -> Result<(), ShaderError> {
+    validate_shader(ShaderStage::Vertex, vert_code)?;
+    validate_shader(ShaderStage::Fragment, frag_code)?;
+
+    let outputs = declarations(vert_code, "varying");
+    for (line, input) in declarations(frag_code, "varying") {
+        let message = match outputs.iter().find(|(_, output)| output.name == input.name) {
+            None => format!("varying '{}' is not declared in the vertex shader", input.name),
+            Some((_, output)) if output.gl_type != input.gl_type => format!(
+                "varying '{}' is {} in the vertex shader but {} in the fragment shader",
+                input.name, output.gl_type, input.gl_type
+            ),
+            Some(_) => continue,
+        };
+        return Err(error(ShaderStage::Link, vec![Diagnostic {
+            file: None,
+            line: Some(line),
+            message,
+            snippet: snippet(frag_code, line),
+        }]));
+    }
+    Ok(())
+}
+
+fn error(stage: ShaderStage, diagnostics: Vec<Diagnostic>) -> ShaderError {
+    let log = diagnostics.iter().map(|d| d.message.as_str()).collect::<Vec<_>>().join("\n");
+    ShaderError { stage, log, diagnostics }
+}
+
+// `offset` is a byte offset into the translated source, which has one extra line on top
+fn diagnostic(source: &str, translated: &str, offset: Option<usize>, message: String) -> Diagnostic {
+    let line = offset
+        .map(|offset| translated[..offset.min(translated.len())].matches('\n').count())
+        .filter(|&line| line > 0);
+    Diagnostic {
+        file: None,
+        line,
+        message,
+        snippet: line.and_then(|line| snippet(source, line)),
+    }
+}
+
+/*==================== GLSL ES 1.00 implicit conversions ====================*/
+const FLOAT_TYPES: [&str; 7] = ["float", "vec2", "vec3", "vec4", "mat2", "mat3", "mat4"];
+const OTHER_TYPES: [&str; 10] = ["int", "ivec2", "ivec3", "ivec4", "bool", "bvec2", "bvec3", "bvec4", "sampler2D", "samplerCube"];
+// Built-ins that only come in float versions in GLSL ES 1.00
+const FLOAT_FUNCTIONS: [&str; 35] = [
+    "radians", "degrees", "sin", "cos", "tan", "asin", "acos", "atan", "pow", "exp", "log", "exp2", "log2", "sqrt",
+    "inversesqrt", "abs", "sign", "floor", "ceil", "fract", "mod", "min", "max", "clamp", "mix", "step", "smoothstep",
+    "length", "distance", "dot", "cross", "normalize", "faceforward", "reflect", "refract",
+];
+// An int literal here is converted; `x = 1` only counts when the literal is the whole right side
+const ASSIGNMENTS: [&str; 5] = ["=", "+=", "-=", "*=", "/="];
+const OPERATORS: [&str; 10] = ["+", "-", "*", "/", "<", ">", "<=", ">=", "==", "!="];
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+// Int literals assigned to, combined with or passed as a float. Names are looked up without
+// scopes, so one declared both float and not is left alone.
+fn implicit_conversions(source: &str) -> Vec<Diagnostic> {
+    let tokens = tokenize(source);
+    let text = |i: usize| tokens.get(i).map_or("", |t| t.text);
+    let is_ident = |i: usize| text(i).starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+
+    let mut floats = HashMap::new();
+    // Parameter types of user functions, None once overloaded
+    let mut functions: HashMap<&str, Option<Vec<bool>>> = HashMap::new();
+    for i in 0..tokens.len() {
+        let (ty, name) = (text(i), text(i + 1));
+        let known = FLOAT_TYPES.contains(&ty) || OTHER_TYPES.contains(&ty);
+        if !is_ident(i) || !is_ident(i + 1) || (!known && ty != "void") {
+            continue;
+        }
+        if text(i + 2) != "(" {
+            floats.entry(name).and_modify(|f| *f = false).or_insert(FLOAT_TYPES.contains(&ty));
+            continue;
+        }
+        let mut params = Vec::new();
+        let mut j = i + 3;
+        while j < tokens.len() && text(j) != ")" {
+            let start = j;
+            while j < tokens.len() && text(j) != "," && text(j) != ")" {
+                j += 1;
+            }
+            if text(start) != "void" || j > start + 1 {
+                params.push((start..j).any(|k| FLOAT_TYPES.contains(&text(k))));
+            }
+            if text(j) == "," {
+                j += 1;
+            }
+        }
+        functions
+            .entry(name)
+            .and_modify(|p| {
+                if p.as_ref() != Some(&params) {
+                    *p = None;
+                }
+            })
+            .or_insert(Some(params));
+    }
+
+    // `name`, `name.x`, `name.xy.x` ending at `end` (or starting at it, going forward)
+    let float_before = |end: usize| {
+        let mut i = end;
+        while i >= 2 && text(i - 1) == "." && is_ident(i - 2) {
+            i -= 2;
+        }
+        is_ident(end) && floats.get(text(i)) == Some(&true)
+    };
+    let float_after = |start: usize| is_ident(start) && text(start + 1) != "(" && floats.get(text(start)) == Some(&true);
+    let is_operand_end = |i: usize| {
+        is_ident(i) || text(i).starts_with(|c: char| c.is_ascii_digit() || c == '.') || [")", "]"].contains(&text(i))
+    };
+
+    let mut diagnostics = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if !is_int_literal(token.text) || i == 0 {
+            continue;
+        }
+        // Step over a unary sign
+        let mut before = i - 1;
+        if ["-", "+"].contains(&text(before)) && before > 0 && !is_operand_end(before - 1) {
+            before -= 1;
+        }
+        let after = i + 1;
+        let alone = [";", ",", ")"].contains(&text(after));
+
+        let mut expects = None;
+        if before > 0 && (OPERATORS.contains(&text(before)) || (alone && ASSIGNMENTS.contains(&text(before)))) && float_before(before - 1) {
+            expects = Some(format!("'{}'", text(before - 1)));
+        } else if OPERATORS.contains(&text(after)) && float_after(after + 1) {
+            expects = Some(format!("'{}'", text(after + 1)));
+        } else if alone && ["(", ","].contains(&text(before)) {
+            // Which argument of which call
+            let (mut depth, mut argument, mut open) = (0, 0, before);
+            while open > 0 {
+                match text(open) {
+                    ")" => depth += 1,
+                    "(" if depth == 0 => break,
+                    "(" => depth -= 1,
+                    "," if depth == 0 => argument += 1,
+                    _ => {}
+                }
+                open -= 1;
+            }
+            let callee = if open > 0 && text(open) == "(" { text(open - 1) } else { "" };
+            let float_parameter = match functions.get(callee) {
+                Some(Some(params)) => params.get(argument).copied().unwrap_or(false),
+                _ => FLOAT_FUNCTIONS.contains(&callee),
+            };
+            if float_parameter {
+                expects = Some(callee.to_string());
+            }
+        }
+
+        if let Some(expects) = expects {
+            let suggestion = if token.text.starts_with("0x") { format!("float({})", token.text) } else { format!("{}.0", token.text) };
+            diagnostics.push(Diagnostic {
+                file: None,
+                line: Some(token.line),
+                message: format!(
+                    "int literal {} where {} expects a float; GLSL ES 1.00 has no implicit conversions, write {}",
+                    token.text, expects, suggestion
+                ),
+                snippet: snippet(source, token.line),
+            });
+        }
+    }
+    diagnostics
+}
+
+fn is_int_literal(text: &str) -> bool {
+    let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+    match hex {
+        Some(digits) => !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit()),
+        None => !text.is_empty() && text.chars().all(|c| c.is_ascii_digit()),
+    }
+}
+
+// Identifiers, numbers and operators with their line; comments and preprocessor lines are dropped
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    const TWO_CHAR: [&str; 12] = ["+=", "-=", "*=", "/=", "<=", ">=", "==", "!=", "&&", "||", "++", "--"];
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let (mut i, mut line, mut line_start) = (0, 1, true);
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c == b'\n' {
+            line += 1;
+            line_start = true;
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if (line_start && c == b'#') || source[i..].starts_with("//") {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        line_start = false;
+        if source[i..].starts_with("/*") {
+            let end = source[i + 2..].find("*/").map_or(bytes.len(), |e| i + 2 + e + 2);
+            line += source[i..end].matches('\n').count();
+            i = end;
+            continue;
+        }
+        if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+        } else if c.is_ascii_digit() || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit)) {
+            // 1, 1.5, .5, 1e-3, 0x1F
+            while i < bytes.len() {
+                let d = bytes[i];
+                let exponent_sign = (d == b'-' || d == b'+') && matches!(bytes[i - 1], b'e' | b'E') && !source[start..].starts_with("0x");
+                if d.is_ascii_alphanumeric() || d == b'.' || exponent_sign {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+        } else if TWO_CHAR.iter().any(|op| source[i..].starts_with(op)) {
+            i += 2;
+        } else {
+            i += source[i..].chars().next().map_or(1, char::len_utf8);
+        }
+        tokens.push(Token { text: &source[start..i], line });
+    }
+    tokens
+}
+
+/*==================== GLSL ES 1.00 -> 450 ====================*/
+fn to_glsl450(stage: ShaderStage, source: &str) -> String {
+    let mut out = String::from("#version 450\n");
+    let (mut attributes, mut varyings, mut bindings) = (0, 0, 0);
+    let mut frag_color_declared = false;
+    // Sampler uniforms and their type, sampler2D or samplerCube
+    let mut samplers: Vec<(String, String)> = Vec::new();
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        let mut line = if trimmed.starts_with("#version") {
+            String::new()
+        } else if trimmed.starts_with("#extension") && trimmed.contains("GL_OES_standard_derivatives") {
+            "#define GL_OES_standard_derivatives 1".to_string()
+        } else {
+            line.to_string()
+        };
+
+        if let Some(decl) = parse_declaration(&line) {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            line = match decl.qualifier {
+                "attribute" => {
+                    attributes += 1;
+                    format!("{}layout(location = {}) in {} {};{}", indent, attributes - 1, decl.gl_type, decl.name, decl.rest)
+                }
+                "varying" => {
+                    let direction = if stage == ShaderStage::Fragment { "in" } else { "out" };
+                    varyings += 1;
+                    format!("{}layout(location = {}) {} {} {};{}", indent, varyings - 1, direction, decl.gl_type, decl.name, decl.rest)
+                }
+                _ if decl.gl_type.starts_with("sampler") => {
+                    bindings += 2;
+                    samplers.push((decl.name.clone(), decl.gl_type.clone()));
+                    format!(
+                        "{}layout(binding = {}) uniform {} {}; layout(binding = {}) uniform sampler _{}_sampler;{}",
+                        indent,
+                        bindings - 2,
+                        decl.gl_type.replace("sampler", "texture"),
+                        decl.name,
+                        bindings - 1,
+                        decl.name,
+                        decl.rest
+                    )
+                }
+                _ => {
+                    bindings += 1;
+                    format!(
+                        "{}layout(binding = {}) uniform _{}_block {{ {} {}; }};{}",
+                        indent, bindings - 1, decl.name, decl.gl_type, decl.name, decl.rest
+                    )
+                }
+            };
+        } else {
+            // texture2D(u_texture, uv) -> texture(sampler2D(u_texture, _u_texture_sampler), uv)
+            for (name, gl_type) in &samplers {
+                line = replace_identifier(&line, name, &format!("{}({}, _{}_sampler)", gl_type, name, name));
+            }
+            line = replace_identifier(&line, "texture2D", "texture");
+            line = replace_identifier(&line, "textureCube", "texture");
+        }
+
+        if stage == ShaderStage::Fragment {
+            line = replace_identifier(&line, "gl_FragColor", FRAG_COLOR);
+            if !frag_color_declared && line.trim_start().starts_with("void main") {
+                line = format!("layout(location = 0) out vec4 {}; {}", FRAG_COLOR, line);
+                frag_color_declared = true;
+            }
+        }
+
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn declarations(source: &str, qualifier: &str) -> Vec<(usize, Declaration)> {
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| parse_declaration(line).map(|d| (i + 1, d)))
+        .filter(|(_, d)| d.qualifier == qualifier)
+        .collect()
+}
+
+// `uniform mat4 Pmatrix;`, `varying lowp vec3 vColor;`, ... one declaration per statement
+fn parse_declaration(line: &str) -> Option<Declaration> {
+    let trimmed = line.trim_start();
+    let (qualifier, rest) = ["attribute", "varying", "uniform"]
+        .iter()
+        .find_map(|q| trimmed.strip_prefix(q).filter(|r| r.starts_with(char::is_whitespace)).map(|r| (*q, r)))?;
+    let (statement, after) = rest.split_once(';')?;
+
+    let mut words: Vec<&str> = statement.split_whitespace().collect();
+    if matches!(words.first(), Some(&"lowp") | Some(&"mediump") | Some(&"highp")) {
+        words.remove(0);
+    }
+    // Blocks and multiple declarators are left alone
+    if words.len() != 2 || statement.contains(',') || statement.contains('{') {
+        return None;
+    }
+    Some(Declaration {
+        qualifier,
+        gl_type: words[0].to_string(),
+        name: words[1].to_string(),
+        rest: after.to_string(),
+    })
+}
+
+fn replace_identifier(line: &str, from: &str, to: &str) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(pos) = rest.find(from) {
+        let before = rest[..pos].chars().next_back();
+        let after = rest[pos + from.len()..].chars().next();
+        out.push_str(&rest[..pos]);
+        if !before.is_some_and(is_ident) && !after.is_some_and(is_ident) {
+            out.push_str(to);
+        } else {
+            out.push_str(from);
+        }
+        rest = &rest[pos + from.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERT: &str = "
+        attribute vec3 position;
+        uniform mat4 Pmatrix;
+        varying vec3 vColor;
+        void main(void) {
+            gl_Position = Pmatrix * vec4(position, 1.0);
+            vColor = position;
+        }
+    ";
+
+    const FRAG: &str = "
+        precision mediump float;
+        varying vec3 vColor;
+        void main(void) {
+            gl_FragColor = vec4(vColor, 1.0);
+        }
+    ";
+
+    #[test]
+    fn valid_program_passes() {
+        validate_program(VERT, FRAG).unwrap();
+    }
+
+    #[test]
+    fn translation_keeps_lines() {
+        let translated = to_glsl450(ShaderStage::Fragment, FRAG);
+        assert_eq!(translated.lines().count(), FRAG.lines().count() + 1);
+        assert!(translated.contains("layout(location = 0) in vec3 vColor;"));
+        assert!(translated.contains("_gl_FragColor = vec4(vColor, 1.0);"));
+
+        let translated = to_glsl450(ShaderStage::Vertex, VERT);
+        assert!(translated.contains("layout(binding = 0) uniform _Pmatrix_block { mat4 Pmatrix; };"));
+    }
+
+    #[test]
+    fn undeclared_identifier_points_at_its_line() {
+        let broken = VERT.replace("vec4(position, 1.0)", "vec4(positon, 1.0)");
+        let err = validate_shader(ShaderStage::Vertex, &broken).unwrap_err();
+        assert_eq!(err.stage, ShaderStage::Vertex);
+        assert_eq!(err.line(), Some(6));
+        assert!(err.snippet().unwrap().contains("> "), "{}", err);
+        assert!(err.to_string().contains("positon"), "{}", err);
+    }
+
+    #[test]
+    fn type_errors_are_reported() {
+        let broken = FRAG.replace("vec4(vColor, 1.0)", "vColor");
+        let err = validate_shader(ShaderStage::Fragment, &broken).unwrap_err();
+        assert_eq!(err.stage, ShaderStage::Fragment);
+    }
+
+    #[test]
+    fn varyings_have_to_match() {
+        let err = validate_program(VERT, &FRAG.replace("vColor", "vColour")).unwrap_err();
+        assert_eq!(err.stage, ShaderStage::Link);
+        assert_eq!(err.line(), Some(3));
+        assert!(err.to_string().contains("varying 'vColour' is not declared"), "{}", err);
+
+        let frag = FRAG.replace("varying vec3", "varying vec4").replace("vec4(vColor, 1.0)", "vColor");
+        let err = validate_program(VERT, &frag).unwrap_err();
+        assert!(err.to_string().contains("vec3 in the vertex shader but vec4"), "{}", err);
+    }
+
+    #[test]
+    fn samplers_are_split() {
+        let frag = "
+            precision mediump float;
+            uniform sampler2D u_texture;
+            varying vec2 vUv;
+            void main(void) {
+                gl_FragColor = texture2D(u_texture, vUv);
+            }
+        ";
+        let translated = to_glsl450(ShaderStage::Fragment, frag);
+        assert!(translated.contains("uniform texture2D u_texture; layout(binding = 1) uniform sampler _u_texture_sampler;"));
+        assert!(translated.contains("texture(sampler2D(u_texture, _u_texture_sampler), vUv)"));
+        validate_shader(ShaderStage::Fragment, frag).unwrap();
+    }
+
+    #[test]
+    fn int_literals_are_not_floats() {
+        // naga accepts all of these through the 450 translation
+        let broken = FRAG.replace("void main(void) {", "void main(void) {\n            float s = 1;");
+        let err = validate_shader(ShaderStage::Fragment, &broken).unwrap_err();
+        assert_eq!(err.line(), Some(5));
+        assert!(err.to_string().contains("int literal 1 where 's' expects a float"), "{}", err);
+        assert!(err.to_string().contains("write 1.0"), "{}", err);
+
+        let broken = FRAG.replace("vec4(vColor, 1.0)", "vec4(max(vColor, 0), 1.0)");
+        let err = validate_shader(ShaderStage::Fragment, &broken).unwrap_err();
+        assert!(err.to_string().contains("int literal 0 where max expects a float"), "{}", err);
+
+        let broken = VERT.replace("vColor = position;", "vColor = position * 2;");
+        let err = validate_shader(ShaderStage::Vertex, &broken).unwrap_err();
+        assert_eq!(err.line(), Some(7));
+        assert!(err.to_string().contains("'position'"), "{}", err);
+
+        let user_function = "
+            precision mediump float;
+            float half_of(float x) { return x * 0.5; }
+            void main(void) {
+                gl_FragColor = vec4(half_of(-1));
+            }
+        ";
+        let err = validate_shader(ShaderStage::Fragment, user_function).unwrap_err();
+        assert!(err.to_string().contains("int literal 1 where half_of expects a float"), "{}", err);
+    }
+
+    #[test]
+    fn ints_where_ints_belong() {
+        let frag = "
+            precision mediump float;
+            uniform int u_mode;
+            varying vec3 vColor;
+            int twice(int i) { return i * 2; }
+            void main(void) {
+                // Constructors convert, float s = 1; in a comment doesn't count
+                vec4 color = vec4(vColor, 1);
+                int i = 3;
+                float s = u_mode == 1 ? 0.5 : float(twice(i - 1));
+                for (int k = 0; k < 4; k++) {
+                    s += 1e-3 + 2.0;
+                }
+                gl_FragColor = color * s;
+            }
+        ";
+        validate_shader(ShaderStage::Fragment, frag).unwrap();
+        let tokens: Vec<_> = tokenize("x = 1e-3 + .5 - 0x1F;").iter().map(|t| t.text).collect();
+        assert_eq!(tokens, ["x", "=", "1e-3", "+", ".5", "-", "0x1F", ";"]);
+    }
+
+    #[test]
+    fn replaces_whole_identifiers_only() {
+        assert_eq!(replace_identifier("gl_FragColor = my_gl_FragColor2;", "gl_FragColor", "x"), "x = my_gl_FragColor2;");
+    }
+}
@@ -0,0 +1,197 @@
+// Any triangle mesh drawn as its edges: a LINES index buffer with every edge once, or the filled
+// triangles with their edges drawn over them from barycentric coordinates (Variant::WIREFRAME).
+use std::collections::{HashMap, HashSet};
+
+use web_sys::WebGlRenderingContext;
+
+use crate::mesh::{vertex_bytes, Mesh, MeshIndex, Vertex, VertexLayout, BARYCENTRIC, POSITION};
+
+type Gl = WebGlRenderingContext;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    #[default]
+    Solid,
+    // Only the edges, with LINES
+    Wireframe,
+    // Filled, with the edges on top; needs a Variant::WIREFRAME program
+    Overlay,
+}
+
+impl RenderMode {
+    // "solid", "wireframe" or "overlay"
+    pub fn from_name(name: &str) -> Option<RenderMode> {
+        match name {
+            "solid" => Some(RenderMode::Solid),
+            "wireframe" => Some(RenderMode::Wireframe),
+            "overlay" => Some(RenderMode::Overlay),
+            _ => None,
+        }
+    }
+}
+
+// Turns on OES_standard_derivatives, which the overlay uses to keep its edges a fixed number of
+// pixels wide. Has to happen before the WIREFRAME program is compiled; false if it isn't there.
+pub fn enable_derivatives(gl: &Gl) -> bool {
+    gl.get_extension("OES_standard_derivatives").ok().flatten().is_some()
+}
+
+// Every edge of the triangles once, as pairs for LINES. Edges are matched by vertex index, see
+// same_attribute for meshes whose corners are split along seams.
+pub fn unique_edges(indices: &[u32]) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    let mut edges = Vec::new();
+    for triangle in indices.chunks_exact(3) {
+        for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+            if a != b && seen.insert((a.min(b), a.max(b))) {
+                edges.extend_from_slice(&[a, b]);
+            }
+        }
+    }
+    edges
+}
+
+// For each vertex, the first vertex with the same bytes for `attribute`, e.g. the same position
+// on a cube whose faces each have their own corners
+pub fn same_attribute(layout: &VertexLayout, bytes: &[u8], attribute: &str) -> Vec<u32> {
+    let stride = layout.stride().max(1) as usize;
+    let vertices = bytes.chunks_exact(stride);
+    let Some(attribute) = layout.get(attribute) else {
+        return (0..vertices.len() as u32).collect();
+    };
+    let range = attribute.offset as usize..(attribute.offset + attribute.size_in_bytes()) as usize;
+    let mut first: HashMap<&[u8], u32> = HashMap::new();
+    vertices
+        .enumerate()
+        .map(|(i, vertex)| *first.entry(&vertex[range.clone()]).or_insert(i as u32))
+        .collect()
+}
+
+// The layout of the overlay mesh built from vertices with `layout`
+pub fn overlay_layout(layout: &VertexLayout) -> VertexLayout {
+    layout.clone().float(BARYCENTRIC, 3)
+}
+
+// One vertex per triangle corner with (1, 0, 0), (0, 1, 0) or (0, 0, 1) appended as `barycentric`,
+// for drawing without indices
+pub fn barycentric_vertices(layout: &VertexLayout, bytes: &[u8], indices: &[u32]) -> (VertexLayout, Vec<u8>) {
+    let stride = layout.stride().max(1) as usize;
+    let out_layout = overlay_layout(layout);
+    let mut out = Vec::with_capacity(indices.len() * out_layout.stride() as usize);
+    for triangle in indices.chunks_exact(3) {
+        for (corner, &index) in triangle.iter().enumerate() {
+            let start = index as usize * stride;
+            out.extend_from_slice(&bytes[start..start + stride]);
+            let mut barycentric = [0.0f32; 3];
+            barycentric[corner] = 1.0;
+            out.extend(barycentric.iter().flat_map(|v| v.to_ne_bytes()));
+        }
+    }
+    (out_layout, out)
+}
+
+// A triangle mesh uploaded the three ways it can be drawn, so the mode can change from frame to frame
+pub struct WireframeMesh {
+    solid: Mesh,
+    edges: Mesh,
+    overlay: Mesh,
+}
+
+impl WireframeMesh {
+    pub fn new<V: Vertex, I: MeshIndex>(gl: &Gl, vertices: &[V], indices: &[I]) -> Self {
+        WireframeMesh::from_bytes(gl, V::layout(), &vertex_bytes(vertices), indices)
+    }
+
+    // No indices means the vertices are a triangle list in order
+    pub fn from_bytes<I: MeshIndex>(gl: &Gl, layout: VertexLayout, bytes: &[u8], indices: &[I]) -> Self {
+        let indices: Vec<u32> = if indices.is_empty() {
+            (0..(bytes.len() / layout.stride().max(1) as usize) as u32).collect()
+        } else {
+            indices.iter().map(|i| i.to_u32()).collect()
+        };
+
+        // Corners at the same position share their edges, or every face of a cube would outline itself
+        let same = same_attribute(&layout, bytes, POSITION);
+        let welded: Vec<u32> = indices.iter().map(|&i| same[i as usize]).collect();
+        let mut edges = Mesh::new(gl, layout.clone(), bytes, &unique_edges(&welded));
+        edges.set_mode(Gl::LINES);
+
+        let (overlay_layout, overlay_bytes) = barycentric_vertices(&layout, bytes, &indices);
+        let overlay = Mesh::new::<u32>(gl, overlay_layout, &overlay_bytes, &[]);
+
+        WireframeMesh {
+            solid: Mesh::new(gl, layout, bytes, &indices),
+            edges,
+            overlay,
+        }
+    }
+
+    pub fn mesh(&self, mode: RenderMode) -> &Mesh {
+        match mode {
+            RenderMode::Solid => &self.solid,
+            RenderMode::Wireframe => &self.edges,
+            RenderMode::Overlay => &self.overlay,
+        }
+    }
+
+    pub fn delete(self) {
+        self.solid.delete();
+        self.edges.delete();
+        self.overlay.delete();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+    use crate::GeometryVertex;
+
+    #[test]
+    fn shared_edges_appear_once() {
+        // Two triangles of a quad share the diagonal
+        let edges = unique_edges(&[0, 1, 2, 0, 2, 3]);
+        assert_eq!(edges, vec![0, 1, 1, 2, 2, 0, 2, 3, 3, 0]);
+        assert!(unique_edges(&[4, 4, 5]).len() == 2);
+    }
+
+    #[test]
+    fn cube_edges_by_position() {
+        let cube = primitives::cube(2.0, 1);
+        let layout = GeometryVertex::layout();
+        let bytes = vertex_bytes(&cube.vertices());
+        let same = same_attribute(&layout, &bytes, POSITION);
+        let mut corners = same.clone();
+        corners.sort();
+        corners.dedup();
+        assert_eq!(corners.len(), 8);
+
+        let welded: Vec<u32> = cube.indices.iter().map(|&i| same[i as usize]).collect();
+        // 12 cube edges and a diagonal across each face
+        assert_eq!(unique_edges(&welded).len(), 2 * 18);
+        // Split corners alone give every face its own outline
+        assert_eq!(unique_edges(&cube.indices).len(), 2 * 6 * 5);
+    }
+
+    #[test]
+    fn barycentric_corners() {
+        let cube = primitives::cube(2.0, 1);
+        let layout = GeometryVertex::layout();
+        let (out_layout, bytes) = barycentric_vertices(&layout, &vertex_bytes(&cube.vertices()), &cube.indices);
+        assert_eq!(out_layout.stride(), layout.stride() + 12);
+        assert_eq!(out_layout.get(BARYCENTRIC).unwrap().offset, layout.stride());
+
+        let stride = out_layout.stride() as usize;
+        assert_eq!(bytes.len(), cube.indices.len() * stride);
+        let barycentric = |corner: usize| -> [f32; 3] {
+            let start = corner * stride + layout.stride() as usize;
+            std::array::from_fn(|k| f32::from_ne_bytes(bytes[start + 4 * k..start + 4 * k + 4].try_into().unwrap()))
+        };
+        assert_eq!([barycentric(0), barycentric(1), barycentric(2)], [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        assert_eq!(barycentric(4), [0.0, 1.0, 0.0]);
+        // The copied vertex is the one the index pointed at
+        assert_eq!(&bytes[stride * 5..stride * 5 + 12], &vertex_bytes(&cube.vertices())[cube.indices[5] as usize * 32..][..12]);
+        assert_eq!(RenderMode::from_name("overlay"), Some(RenderMode::Overlay));
+        assert_eq!(RenderMode::from_name("lines"), None);
+    }
+}
@@ -0,0 +1,136 @@
+// #[derive(Vertex)] for webgl_common. Use it through `webgl_common::Vertex`, the generated code
+// refers to `::webgl_common::mesh`.
+//
+//     #[derive(Clone, Copy, Vertex)]
+//     #[repr(C)]
+//     struct ColorVertex {
+//         position: [f32; 3],
+//         #[vertex(normalized)]
+//         color: [u8; 4],
+//         #[vertex(name = "uv")]
+//         tex_coord: [f32; 2],
+//         #[vertex(skip)]
+//         _pad: u32,
+//     }
+//
+// Every field becomes an attribute named after the field (or `name = ".."`), with the component
+// count and GL type taken from the field's VertexField impl and the offset from offset_of!.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitStr};
+
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+struct FieldOptions {
+    name: Option<String>,
+    normalized: bool,
+    skip: bool,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let ident = &input.ident;
+
+    // Offsets have to mean the same thing in Rust and in the GL buffer
+    if !has_repr_c(input)? {
+        return Err(Error::new_spanned(ident, "#[derive(Vertex)] needs #[repr(C)] so the field offsets are fixed"));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, "#[derive(Vertex)] doesn't support generic structs"));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(ident, "#[derive(Vertex)] needs a struct with named fields")),
+        },
+        _ => return Err(Error::new_spanned(ident, "#[derive(Vertex)] only works on structs")),
+    };
+
+    let mut attributes = Vec::new();
+    let mut writes = Vec::new();
+    for field in fields {
+        let options = field_options(field)?;
+        if options.skip {
+            continue;
+        }
+        let field_ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let name = options.name.unwrap_or_else(|| field_ident.to_string());
+        let normalized = options.normalized;
+
+        attributes.push(quote! {
+            ::webgl_common::mesh::VertexAttribute {
+                name: #name,
+                components: <#ty as ::webgl_common::mesh::VertexField>::COMPONENTS,
+                gl_type: <#ty as ::webgl_common::mesh::VertexField>::GL_TYPE,
+                normalized: #normalized,
+                offset: ::core::mem::offset_of!(#ident, #field_ident) as i32,
+            }
+        });
+        // Copied out first, a field of a repr(C, packed) struct can't be borrowed where it is
+        writes.push(quote! {
+            ::webgl_common::mesh::VertexField::write(
+                &{ self.#field_ident },
+                &mut out[::core::mem::offset_of!(#ident, #field_ident)..],
+            );
+        });
+    }
+
+    Ok(quote! {
+        impl ::webgl_common::mesh::Vertex for #ident {
+            const ATTRIBUTES: &'static [::webgl_common::mesh::VertexAttribute] = &[#(#attributes),*];
+
+            fn write(&self, out: &mut [u8]) {
+                #(#writes)*
+            }
+        }
+    })
+}
+
+fn has_repr_c(input: &DeriveInput) -> Result<bool, Error> {
+    let mut repr_c = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                repr_c = true;
+            }
+            // align(16), packed(2): the arguments don't matter, only that they get consumed
+            if meta.input.peek(syn::token::Paren) {
+                let arguments;
+                syn::parenthesized!(arguments in meta.input);
+                arguments.parse::<TokenStream2>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr_c)
+}
+
+fn field_options(field: &syn::Field) -> Result<FieldOptions, Error> {
+    let mut options = FieldOptions {
+        name: None,
+        normalized: false,
+        skip: false,
+    };
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("vertex")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                options.name = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else if meta.path.is_ident("normalized") {
+                options.normalized = true;
+                Ok(())
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"..\"`, `normalized` or `skip`"))
+            }
+        })?;
+    }
+    Ok(options)
+}
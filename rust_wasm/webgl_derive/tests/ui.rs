@@ -0,0 +1,8 @@
+// What #[derive(Vertex)] accepts and the errors it gives otherwise. Run with
+// TRYBUILD=overwrite to regenerate the .stderr files after a message changes.
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass_*.rs");
+    t.compile_fail("tests/ui/fail_*.rs");
+}
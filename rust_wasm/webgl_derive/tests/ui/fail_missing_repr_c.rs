@@ -0,0 +1,8 @@
+use webgl_common::Vertex;
+
+#[derive(Clone, Copy, Vertex)]
+struct NoRepr {
+    position: [f32; 3],
+}
+
+fn main() {}
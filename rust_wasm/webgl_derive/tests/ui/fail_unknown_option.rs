@@ -0,0 +1,10 @@
+use webgl_common::Vertex;
+
+#[derive(Clone, Copy, Vertex)]
+#[repr(C)]
+struct UnknownOption {
+    #[vertex(normalised)]
+    color: [u8; 4],
+}
+
+fn main() {}
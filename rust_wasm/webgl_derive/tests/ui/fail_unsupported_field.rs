@@ -0,0 +1,10 @@
+use webgl_common::Vertex;
+
+#[derive(Clone, Copy, Vertex)]
+#[repr(C)]
+struct UnsupportedField {
+    position: [f32; 3],
+    weight: f64,
+}
+
+fn main() {}
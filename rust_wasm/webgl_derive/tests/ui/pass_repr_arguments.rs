@@ -0,0 +1,22 @@
+use webgl_common::Vertex;
+
+#[derive(Clone, Copy, Vertex)]
+#[repr(C, align(32))]
+struct Aligned {
+    position: [f32; 3],
+    #[vertex(normalized)]
+    color: [u8; 4],
+}
+
+#[derive(Clone, Copy, Vertex)]
+#[repr(C, packed(2))]
+struct Packed {
+    position: [f32; 3],
+    #[vertex(name = "uv")]
+    tex_coord: [f32; 2],
+}
+
+fn main() {
+    assert_eq!(Aligned::layout().stride(), 32);
+    assert_eq!(Packed::layout().get("uv").unwrap().offset, 12);
+}